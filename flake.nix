@@ -37,6 +37,7 @@
           profile.cargo
           profile.rustfmt
           profile.clippy
+          profile.rust-std
           std-lib.rust-std
         ];
       in
@@ -1,46 +1,82 @@
+pub mod burst;
 mod factory_calibrated_values;
+pub mod stats;
+#[cfg(feature = "stm32")]
+mod stm32;
 mod util;
 
-use embassy_time::{Duration, Instant, Timer};
+#[cfg(feature = "stm32")]
+use embassy_time::Duration;
+use embassy_time::{Instant, Timer};
 use util::Sortable;
 
 use derive_more::Constructor;
-use embassy_stm32::{
-    Peri,
-    adc::{Adc, AdcChannel, AnyAdcChannel, RxDma, SampleTime},
-    peripherals::{ADC1, DMA1_CH1},
-};
 use embassy_sync::watch::DynSender;
 use heapless::Vec;
+use stats::SharedStatistics;
+
+#[cfg(feature = "stm32")]
+pub use stm32::Stm32Adc;
+
+/// channels sampled by the adc task, including the internal temperature sensor
+pub const ADC_CHANNELS: usize = 12;
+
+/// Hardware independent access to an adc able to sample a sequence of channels
+#[allow(async_fn_in_trait)]
+pub trait AdcSampler {
+    type Channel;
+
+    /// channel connected to the internal temperature sensor
+    fn temperature_channel(&mut self) -> Self::Channel;
+    /// hardware channel number, sequences are sampled in ascending order
+    fn hw_channel(channel: &Self::Channel) -> u8;
+    /// sample all channels of the sequence into `readings`
+    async fn read<'c>(
+        &mut self,
+        sequence: impl ExactSizeIterator<Item = &'c mut Self::Channel>,
+        readings: &mut [u16],
+    ) where
+        Self::Channel: 'c;
+}
 
 // Adc reading task
+#[cfg(feature = "stm32")]
 #[embassy_executor::task]
-pub async fn adc_thread(mut adc: AdcCtrl<'static, 'static, DMA1_CH1, 4>) {
-    const ADC_LOOP_LEN: Duration = Duration::from_millis(100);
+pub async fn adc_thread(
+    mut adc: AdcCtrl<
+        'static,
+        Stm32Adc<'static, embassy_stm32::peripherals::DMA1_CH1>,
+        ADC_CHANNELS,
+    >,
+    loop_len: Duration,
+) {
     let mut loop_time = Instant::now();
     loop {
         adc.run().await;
-        loop_time += ADC_LOOP_LEN;
-        Timer::at(loop_time).await;
+        loop_time += loop_len;
+        adc.sample_bursts_until(loop_time).await;
     }
 }
 
 #[derive(Constructor)]
-pub struct AdcCtrlChannel<'a> {
-    channel: AnyAdcChannel<'a, ADC1>,
+pub struct AdcCtrlChannel<'a, C> {
+    channel: C,
     sender: DynSender<'a, i16>,
+    stats: &'a SharedStatistics,
     conversion_func: fn(u16) -> i16,
 }
 
 pub mod conversion {
     use super::factory_calibrated_values::FactoryCalibratedValues;
+    use core::sync::atomic::{AtomicU16, AtomicU32, Ordering};
     use embassy_sync::lazy_lock::LazyLock;
 
     static CALIB: LazyLock<FactoryCalibratedValues> =
         LazyLock::new(|| FactoryCalibratedValues::new());
 
+    // adc reference voltage, taken from the configuration at boot
+    static VREF_10MV: AtomicU16 = AtomicU16::new(3_30);
     // datasheet reference conditions
-    const VREF_10MV: i32 = 3_30;
     const VREF_CALIB_10MV: i32 = 3_00;
     const TS_1_VAL_TENTH_DEG: i32 = 30_0;
     const TS_2_VAL_TENTH_DEG: i32 = 130_0;
@@ -48,14 +84,38 @@ pub mod conversion {
 
     const RAW_VALUE_RANGE_X100: i32 = 4096_00;
 
-    // voltage divider
-    const R1_OHM: i32 = 100;
-    const R2_OHM: i32 = 10;
-    const V_DIVIDER_MULT: i32 = (R1_OHM + R2_OHM) / R2_OHM;
+    // voltage divider resistors, r1 in the low and r2 in the high 16 bits
+    static V_DIVIDER_OHM: AtomicU32 = AtomicU32::new(100 | 10 << 16);
+
+    pub fn set_vref(vref_10mv: u16) {
+        VREF_10MV.store(vref_10mv, Ordering::Relaxed);
+    }
+    fn vref_10mv() -> i32 {
+        VREF_10MV.load(Ordering::Relaxed) as i32
+    }
+
+    /// set the resistors of the voltage dividers, taken from the configuration at boot
+    pub fn set_voltage_divider(r1_ohm: u16, r2_ohm: u16) {
+        V_DIVIDER_OHM.store(r1_ohm as u32 | (r2_ohm as u32) << 16, Ordering::Relaxed);
+    }
+    /// total and lower resistance of the voltage divider
+    fn voltage_divider() -> (i64, i64) {
+        let ohm = V_DIVIDER_OHM.load(Ordering::Relaxed);
+        let (r1, r2) = ((ohm & 0xFFFF) as i64, (ohm >> 16) as i64);
+        (r1 + r2, r2)
+    }
+
+    /// Current sense amplifier measuring the voltage drop over a shunt
+    pub trait CurrentSense {
+        const SHUNT_MOHM: i32;
+        const GAIN: i32;
+        /// raw measurement at zero current, non zero for bidirectional amplifiers
+        const OFFSET_RAW: i32;
+    }
 
     pub fn calculate_temperature_tenth_deg(measurement: u16) -> i16 {
         let temp_measurement_x10 = 10 * measurement as i32;
-        let temp_calibrated_measurement = temp_measurement_x10 * VREF_10MV / VREF_CALIB_10MV;
+        let temp_calibrated_measurement = temp_measurement_x10 * vref_10mv() / VREF_CALIB_10MV;
         let calib = CALIB.get();
         let temp_tenth_deg = TS_REL_VAL_TENTH_DEG
             * (temp_calibrated_measurement - calib.ts_cal_1_x10)
@@ -65,57 +125,89 @@ pub mod conversion {
     }
 
     pub fn calculate_voltage_10mv(measurement: u16) -> i16 {
-        let vbat_1_measurement_x100 = 100 * measurement as i32;
-        let voltage_mv =
-            vbat_1_measurement_x100 * V_DIVIDER_MULT * VREF_10MV / RAW_VALUE_RANGE_X100;
+        let (r_total, r2) = voltage_divider();
+        let vbat_1_measurement_x100 = 100 * measurement as i64;
+        let voltage_mv = vbat_1_measurement_x100 * r_total * vref_10mv() as i64
+            / (r2 * RAW_VALUE_RANGE_X100 as i64);
         voltage_mv as i16
     }
+
+    pub fn calculate_current_ma<S: CurrentSense>(measurement: u16) -> i16 {
+        let sense_measurement_x100 = 100 * (measurement as i32 - S::OFFSET_RAW);
+        let sense_voltage_mv = sense_measurement_x100 * vref_10mv() * 10 / RAW_VALUE_RANGE_X100;
+        let current_ma = sense_voltage_mv * 1000 / (S::GAIN * S::SHUNT_MOHM);
+        current_ma as i16
+    }
+
+    /// raw measurement of a temperature, inverse of [`calculate_temperature_tenth_deg`]
+    pub fn temperature_tenth_deg_to_raw(temp_tenth_deg: i16) -> u16 {
+        let calib = CALIB.get();
+        let temp_calibrated_measurement = (temp_tenth_deg as i32 - TS_1_VAL_TENTH_DEG)
+            * calib.ts_cal_rel_x10
+            / TS_REL_VAL_TENTH_DEG
+            + calib.ts_cal_1_x10;
+        (temp_calibrated_measurement * VREF_CALIB_10MV / vref_10mv() / 10) as u16
+    }
+
+    /// raw measurement of a voltage, inverse of [`calculate_voltage_10mv`]
+    pub fn voltage_10mv_to_raw(voltage_10mv: i16) -> u16 {
+        let (r_total, r2) = voltage_divider();
+        let measurement_x100 =
+            voltage_10mv as i64 * RAW_VALUE_RANGE_X100 as i64 * r2 / (vref_10mv() as i64 * r_total);
+        (measurement_x100 / 100) as u16
+    }
+
+    /// raw measurement of a current, inverse of [`calculate_current_ma`]
+    pub fn current_ma_to_raw<S: CurrentSense>(current_ma: i16) -> u16 {
+        let sense_voltage_mv = current_ma as i32 * S::GAIN * S::SHUNT_MOHM / 1000;
+        let sense_measurement_x100 = sense_voltage_mv * RAW_VALUE_RANGE_X100 / vref_10mv() / 10;
+        (sense_measurement_x100 / 100 + S::OFFSET_RAW).clamp(0, 4095) as u16
+    }
+
+    /// power of a rail from its voltage and current
+    pub fn calculate_power_10mw(voltage_10mv: i16, current_ma: i16) -> i16 {
+        (voltage_10mv as i32 * current_ma as i32 / 1000) as i16
+    }
 }
 
-pub struct AdcCtrl<'a, 'd, D: RxDma<ADC1>, const N: usize> {
-    adc: Adc<'d, ADC1>,
-    dma_channel: Peri<'d, D>,
+pub struct AdcCtrl<'a, A: AdcSampler, const N: usize> {
+    adc: A,
     // adc channels
-    channels: Vec<AdcCtrlChannel<'a>, N>,
+    channels: Vec<AdcCtrlChannel<'a, A::Channel>, N>,
+    // next sample of an armed burst capture, kept across the regular measurements
+    next_burst_sample: Option<Instant>,
 }
 
-impl<'a, 'd, D: RxDma<ADC1>, const N: usize> AdcCtrl<'a, 'd, D, N> {
+impl<'a, A: AdcSampler, const N: usize> AdcCtrl<'a, A, N> {
     pub fn new(
-        adc: Adc<'d, ADC1>,
-        dma_channel: Peri<'d, D>,
+        mut adc: A,
         temp_sender: DynSender<'a, i16>,
-        external_channels: [AdcCtrlChannel<'a>; N - 1],
+        temp_stats: &'a SharedStatistics,
+        external_channels: [AdcCtrlChannel<'a, A::Channel>; N - 1],
     ) -> Self {
         let temp_channel = AdcCtrlChannel::new(
-            adc.enable_temperature().degrade_adc(),
+            adc.temperature_channel(),
             temp_sender,
+            temp_stats,
             conversion::calculate_temperature_tenth_deg,
         );
-        let mut channels: Vec<AdcCtrlChannel<'a>, N> = external_channels.into_iter().collect();
+        let mut channels: Vec<AdcCtrlChannel<'a, A::Channel>, N> =
+            external_channels.into_iter().collect();
         channels.push(temp_channel).ok();
-        channels.sort_by(|c1, c2| {
-            c1.channel
-                .get_hw_channel()
-                .cmp(&c2.channel.get_hw_channel())
-        });
+        channels.sort_by(|c1, c2| A::hw_channel(&c1.channel).cmp(&A::hw_channel(&c2.channel)));
 
         Self {
             adc,
-            dma_channel,
             channels,
+            next_burst_sample: None,
         }
     }
 
     async fn measure(&mut self) -> Vec<u16, N> {
         let mut measurements = [0u16; N];
-        let sequence = self
-            .channels
-            .iter_mut()
-            .map(|c| (&mut c.channel, SampleTime::CYCLES160_5));
+        let sequence = self.channels.iter_mut().map(|c| &mut c.channel);
 
-        self.adc
-            .read(self.dma_channel.reborrow(), sequence, &mut measurements)
-            .await;
+        self.adc.read(sequence, &mut measurements).await;
 
         Vec::from_array(measurements)
     }
@@ -127,10 +219,54 @@ impl<'a, 'd, D: RxDma<ADC1>, const N: usize> AdcCtrl<'a, 'd, D, N> {
             .collect()
     }
     fn send(&self, values: Vec<i16, N>) {
-        self.channels
+        self.channels.iter().zip(values).for_each(|(c, v)| {
+            c.stats.add(v);
+            c.sender.send(v);
+        });
+    }
+
+    /// sample the channels selected for a burst capture only
+    async fn run_burst(&mut self, channels: u16) {
+        let mut measurements = [0u16; N];
+        let mut selected: Vec<_, N> = self
+            .channels
+            .iter_mut()
+            .enumerate()
+            .filter(|(index, _)| channels & 1 << index != 0)
+            .map(|(_, c)| c)
+            .collect();
+        let count = selected.len();
+        let sequence = selected.iter_mut().map(|c| &mut c.channel);
+        self.adc.read(sequence, &mut measurements[..count]).await;
+        let values: Vec<i16, N> = selected
             .iter()
-            .zip(values)
-            .for_each(|(c, v)| c.sender.send(v));
+            .zip(measurements)
+            .map(|(c, v)| (c.conversion_func)(v))
+            .collect();
+        burst::record(&values);
+    }
+    /// wait for the next regular measurement at `next_run`, sampling an armed
+    /// burst capture meanwhile at its period
+    pub async fn sample_bursts_until(&mut self, next_run: Instant) {
+        while let Some((channels, period)) = burst::sampling() {
+            let now = Instant::now();
+            let sample_time = match self.next_burst_sample {
+                Some(time) if time + period >= now => time,
+                // newly armed or fallen behind by more than a period
+                _ => now,
+            };
+            if sample_time >= next_run {
+                self.next_burst_sample = Some(sample_time);
+                break;
+            }
+            Timer::at(sample_time).await;
+            self.run_burst(channels).await;
+            self.next_burst_sample = Some(sample_time + period);
+        }
+        if burst::sampling().is_none() {
+            self.next_burst_sample = None;
+        }
+        Timer::at(next_run).await;
     }
 
     pub async fn run(&mut self) {
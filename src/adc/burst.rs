@@ -0,0 +1,403 @@
+//! Triggered burst capture of adc channels, e.g. to see the inrush of a sink or a
+//! brown-out while the source is switched. An armed capture samples the selected
+//! channels at a high rate in between the regular measurements, keeping the
+//! configured history before the trigger in a ring buffer. Once the buffer is
+//! full the capture is complete and streamed down by the burst service
+use core::cell::RefCell;
+
+use defmt::Format;
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    channel::DynamicSender,
+    signal::Signal,
+};
+use embassy_time::Duration;
+use south_common::telemetry::eps as tm;
+
+use super::ADC_CHANNELS;
+use crate::{
+    EpsTMContainer,
+    event_log::{self, Event},
+    telecommand::TcFailure,
+};
+
+/// values of all selected channels together
+pub const BUFFER_LEN: usize = 4096;
+/// values per burst data frame
+pub const FRAME_VALUES: usize = 3;
+
+// Burst service task
+#[cfg(feature = "stm32")]
+#[embassy_executor::task]
+pub async fn burst_thread(mut service: BurstService<'static>) {
+    loop {
+        service.run().await;
+    }
+}
+
+/// Cause of a burst capture
+#[repr(u8)]
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// trigger telecommand, always enabled
+    Command,
+    SinkEnable,
+    SourceSwitch,
+    Threshold,
+}
+
+/// Set of enabled triggers, stored as bitmask over the [`Trigger`] ids
+#[derive(Format, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TriggerSet(u8);
+
+impl TriggerSet {
+    pub const EMPTY: Self = Self(0);
+
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+    pub const fn with(self, trigger: Trigger) -> Self {
+        Self(self.0 | 1 << trigger as u8)
+    }
+    pub const fn contains(self, trigger: Trigger) -> bool {
+        trigger as u8 == Trigger::Command as u8 || self.0 & 1 << trigger as u8 != 0
+    }
+}
+
+#[repr(u8)]
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+impl Edge {
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::Rising),
+            1 => Some(Self::Falling),
+            _ => None,
+        }
+    }
+}
+
+/// Level of a channel whose crossing triggers the capture, in the unit of the
+/// channel's conversion, e.g. 10 mV or mA
+#[derive(Format, Clone, Copy)]
+pub struct Threshold {
+    /// index in the sampling sequence
+    pub channel: u8,
+    pub level: i16,
+    pub edge: Edge,
+}
+
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureState {
+    Idle,
+    /// recording the history and waiting for a trigger
+    Armed,
+    Triggered {
+        trigger: Trigger,
+        remaining: usize,
+    },
+    Complete {
+        trigger: Trigger,
+    },
+}
+
+/// Burst settings given when arming
+#[derive(Format, Clone, Copy)]
+pub struct BurstConfig {
+    /// channels as bitmask over their index in the sampling sequence, which is
+    /// ordered by hw channel
+    pub channels: u16,
+    /// sampling period in 100 µs
+    pub period_100us: u8,
+    /// share of the buffer holding samples from before the trigger
+    pub pre_trigger_percent: u8,
+    pub triggers: TriggerSet,
+}
+
+/// Ring buffer of a burst capture, values of a sample are stored together in
+/// sequence order
+pub struct BurstCapture {
+    buffer: [i16; BUFFER_LEN],
+    config: BurstConfig,
+    threshold: Option<Threshold>,
+    state: CaptureState,
+    // channels per sample, samples per channel and samples taken after the trigger
+    width: usize,
+    samples: usize,
+    post_trigger: usize,
+    // next sample slot and samples stored
+    head: usize,
+    stored: usize,
+    // previous value of the threshold channel
+    last: Option<i16>,
+    // counts arming, so readers notice that a capture was replaced
+    capture: u8,
+}
+
+impl Default for BurstCapture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BurstCapture {
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; BUFFER_LEN],
+            config: BurstConfig {
+                channels: 0,
+                period_100us: 0,
+                pre_trigger_percent: 0,
+                triggers: TriggerSet::EMPTY,
+            },
+            threshold: None,
+            state: CaptureState::Idle,
+            width: 0,
+            samples: 0,
+            post_trigger: 0,
+            head: 0,
+            stored: 0,
+            last: None,
+            capture: 0,
+        }
+    }
+    pub fn state(&self) -> CaptureState {
+        self.state
+    }
+    /// start recording, a previous capture is dropped
+    pub fn arm(&mut self, config: BurstConfig) -> Result<(), TcFailure> {
+        let width = config.channels.count_ones() as usize;
+        let threshold_selected = self
+            .threshold
+            .is_some_and(|t| config.channels & 1 << t.channel != 0);
+        if width == 0
+            || config.channels >> ADC_CHANNELS != 0
+            || config.period_100us == 0
+            || config.pre_trigger_percent >= 100
+            || config.triggers.contains(Trigger::Threshold) && !threshold_selected
+        {
+            return Err(TcFailure::InvalidBurst);
+        }
+        self.config = config;
+        self.width = width;
+        self.samples = BUFFER_LEN / width;
+        self.post_trigger = self.samples - self.samples * config.pre_trigger_percent as usize / 100;
+        self.head = 0;
+        self.stored = 0;
+        self.last = None;
+        self.capture = self.capture.wrapping_add(1);
+        self.state = CaptureState::Armed;
+        Ok(())
+    }
+    pub fn disarm(&mut self) {
+        if matches!(
+            self.state,
+            CaptureState::Armed | CaptureState::Triggered { .. }
+        ) {
+            self.state = CaptureState::Idle;
+        }
+    }
+    /// used by captures armed with the threshold trigger afterwards and by the
+    /// recording capture, which has to sample the channel
+    pub fn set_threshold(&mut self, threshold: Threshold) -> Result<(), TcFailure> {
+        if threshold.channel as usize >= ADC_CHANNELS {
+            return Err(TcFailure::InvalidBurst);
+        }
+        let unselected = self.config.channels & 1 << threshold.channel == 0;
+        if self.sampling().is_some() && unselected {
+            return Err(TcFailure::InvalidBurst);
+        }
+        self.threshold = Some(threshold);
+        // the previous value may be of another channel
+        self.last = None;
+        Ok(())
+    }
+    /// selected channels and sampling period while samples are recorded
+    pub fn sampling(&self) -> Option<(u16, Duration)> {
+        match self.state {
+            CaptureState::Armed | CaptureState::Triggered { .. } => Some((
+                self.config.channels,
+                Duration::from_micros(self.config.period_100us as u64 * 100),
+            )),
+            _ => None,
+        }
+    }
+    /// true if the trigger is enabled and started the rest of the capture
+    pub fn trigger(&mut self, trigger: Trigger) -> bool {
+        if self.state != CaptureState::Armed || !self.config.triggers.contains(trigger) {
+            return false;
+        }
+        self.state = CaptureState::Triggered {
+            trigger,
+            remaining: self.post_trigger,
+        };
+        true
+    }
+    fn crossed(&mut self, values: &[i16]) -> bool {
+        let Some(threshold) = self.threshold else {
+            return false;
+        };
+        if self.config.channels & 1 << threshold.channel == 0 {
+            return false;
+        }
+        let below = self.config.channels & ((1 << threshold.channel) - 1);
+        let value = values[below.count_ones() as usize];
+        let last = self.last.replace(value);
+        match (last, threshold.edge) {
+            (Some(last), Edge::Rising) => last < threshold.level && value >= threshold.level,
+            (Some(last), Edge::Falling) => last > threshold.level && value <= threshold.level,
+            (None, _) => false,
+        }
+    }
+    /// store a sample of the selected channels, returns the new state if it changed
+    pub fn record(&mut self, values: &[i16]) -> Option<CaptureState> {
+        if self.sampling().is_none() || values.len() != self.width {
+            return None;
+        }
+        let start = self.head * self.width;
+        self.buffer[start..start + self.width].copy_from_slice(values);
+        self.head = (self.head + 1) % self.samples;
+        self.stored = (self.stored + 1).min(self.samples);
+        match self.state {
+            CaptureState::Armed => {
+                let crossed =
+                    self.config.triggers.contains(Trigger::Threshold) && self.crossed(values);
+                (crossed && self.trigger(Trigger::Threshold)).then_some(self.state)
+            }
+            CaptureState::Triggered { trigger, remaining } => {
+                self.state = match remaining - 1 {
+                    0 => CaptureState::Complete { trigger },
+                    remaining => CaptureState::Triggered { trigger, remaining },
+                };
+                matches!(self.state, CaptureState::Complete { .. }).then_some(self.state)
+            }
+            _ => None,
+        }
+    }
+    /// capture id and header of a complete capture, packed as
+    /// `[channels (u16 le), samples per channel (u16 le), first sample after
+    /// the trigger (u16 le), trigger, period in 100 µs]`
+    pub fn header(&self) -> Option<(u8, u64)> {
+        let CaptureState::Complete { trigger } = self.state else {
+            return None;
+        };
+        let mut header = [0; 8];
+        header[0..2].copy_from_slice(&self.config.channels.to_le_bytes());
+        header[2..4].copy_from_slice(&(self.stored as u16).to_le_bytes());
+        let trigger_sample = self.stored - self.post_trigger;
+        header[4..6].copy_from_slice(&(trigger_sample as u16).to_le_bytes());
+        header[6] = trigger as u8;
+        header[7] = self.config.period_100us;
+        Some((self.capture, u64::from_le_bytes(header)))
+    }
+    /// values of a complete capture
+    pub fn len(&self) -> usize {
+        match self.state {
+            CaptureState::Complete { .. } => self.stored * self.width,
+            _ => 0,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// copy values of a complete capture from `index` on, oldest sample first,
+    /// returns the number of values copied
+    pub fn read(&self, index: usize, values: &mut [i16]) -> usize {
+        let len = self.len();
+        let ring_len = self.samples * self.width;
+        let oldest = (self.head + self.samples - self.stored) % self.samples * self.width;
+        let count = values.len().min(len.saturating_sub(index));
+        for (offset, value) in values[..count].iter_mut().enumerate() {
+            *value = self.buffer[(oldest + index + offset) % ring_len];
+        }
+        count
+    }
+}
+
+// armed by telecommand, triggered by the control loop and filled by the adc task
+static CAPTURE: Mutex<CriticalSectionRawMutex, RefCell<BurstCapture>> =
+    Mutex::new(RefCell::new(BurstCapture::new()));
+static COMPLETED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+pub fn arm(config: BurstConfig) -> Result<(), TcFailure> {
+    CAPTURE.lock(|capture| capture.borrow_mut().arm(config))
+}
+pub fn disarm() {
+    CAPTURE.lock(|capture| capture.borrow_mut().disarm())
+}
+pub fn set_threshold(threshold: Threshold) -> Result<(), TcFailure> {
+    CAPTURE.lock(|capture| capture.borrow_mut().set_threshold(threshold))
+}
+/// selected channels and sampling period while an armed capture records
+pub fn sampling() -> Option<(u16, Duration)> {
+    CAPTURE.lock(|capture| capture.borrow().sampling())
+}
+/// true if the armed capture was triggered
+pub fn trigger(trigger: Trigger) -> bool {
+    let triggered = CAPTURE.lock(|capture| capture.borrow_mut().trigger(trigger));
+    if triggered {
+        event_log::log(Event::BurstTrigger, [trigger as u8, 0, 0]);
+    }
+    triggered
+}
+/// store a sample of the selected channels
+pub fn record(values: &[i16]) {
+    match CAPTURE.lock(|capture| capture.borrow_mut().record(values)) {
+        Some(CaptureState::Triggered { trigger, .. }) => {
+            event_log::log(Event::BurstTrigger, [trigger as u8, 0, 0]);
+        }
+        Some(CaptureState::Complete { .. }) => COMPLETED.signal(()),
+        _ => (),
+    }
+}
+
+/// Streams complete burst captures down, `tm_sender` should feed a channel
+/// that is only sent while no other telemetry is waiting
+pub struct BurstService<'a> {
+    tm_sender: DynamicSender<'a, EpsTMContainer>,
+}
+
+impl<'a> BurstService<'a> {
+    pub fn new(tm_sender: DynamicSender<'a, EpsTMContainer>) -> Self {
+        Self { tm_sender }
+    }
+    /// send the header of a complete capture followed by its values, packed as
+    /// `[index of the first value (u16 le), values (3 x i16 le)]`
+    pub async fn run(&mut self) {
+        COMPLETED.wait().await;
+        let Some((capture, header)) = CAPTURE.lock(|c| c.borrow().header()) else {
+            return;
+        };
+        let container = EpsTMContainer::new(&tm::BurstHeader, &header).unwrap();
+        self.tm_sender.send(container).await;
+        let len = CAPTURE.lock(|c| c.borrow().len());
+        for index in (0..len).step_by(FRAME_VALUES) {
+            let mut values = [0; FRAME_VALUES];
+            // a capture armed meanwhile replaces this one
+            let read = CAPTURE.lock(|c| {
+                let c = c.borrow();
+                c.header()
+                    .is_some_and(|(current, _)| current == capture)
+                    .then(|| c.read(index, &mut values))
+            });
+            if read.is_none() {
+                return;
+            }
+            let mut frame = [0; 8];
+            frame[0..2].copy_from_slice(&(index as u16).to_le_bytes());
+            for (bytes, value) in frame[2..].chunks_exact_mut(2).zip(values) {
+                bytes.copy_from_slice(&value.to_le_bytes());
+            }
+            let frame = u64::from_le_bytes(frame);
+            let container = EpsTMContainer::new(&tm::BurstData, &frame).unwrap();
+            self.tm_sender.send(container).await;
+        }
+    }
+}
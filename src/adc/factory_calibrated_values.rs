@@ -1,22 +1,41 @@
+#[cfg(feature = "stm32")]
 use core::ptr::read_volatile;
 
+#[cfg(feature = "stm32")]
 const TS_CAL_1_REG: usize = 0x1FFF_75A8;
+#[cfg(feature = "stm32")]
 const TS_CAL_2_REG: usize = 0x1FFF_75CA;
 
+// datasheet typical values for builds without access to the engineering flash
+#[cfg(not(feature = "stm32"))]
+const TS_CAL_1_TYPICAL: u16 = 1037;
+#[cfg(not(feature = "stm32"))]
+const TS_CAL_2_TYPICAL: u16 = 1379;
+
 pub struct FactoryCalibratedValues {
     pub ts_cal_1_x10: i32,
     pub ts_cal_rel_x10: i32,
 }
 impl FactoryCalibratedValues {
+    #[cfg(feature = "stm32")]
     pub fn new() -> Self {
         unsafe {
-            let ts_cal_1_x10 = 10 * read_volatile(TS_CAL_1_REG as *const u16) as i32;
-            let ts_cal_2_x10 = 10 * read_volatile(TS_CAL_2_REG as *const u16) as i32;
-            let ts_cal_rel_x10 = ts_cal_2_x10 - ts_cal_1_x10;
-            Self {
-                ts_cal_1_x10,
-                ts_cal_rel_x10,
-            }
+            let ts_cal_1 = read_volatile(TS_CAL_1_REG as *const u16);
+            let ts_cal_2 = read_volatile(TS_CAL_2_REG as *const u16);
+            Self::from_raw(ts_cal_1, ts_cal_2)
+        }
+    }
+    #[cfg(not(feature = "stm32"))]
+    pub fn new() -> Self {
+        Self::from_raw(TS_CAL_1_TYPICAL, TS_CAL_2_TYPICAL)
+    }
+    fn from_raw(ts_cal_1: u16, ts_cal_2: u16) -> Self {
+        let ts_cal_1_x10 = 10 * ts_cal_1 as i32;
+        let ts_cal_2_x10 = 10 * ts_cal_2 as i32;
+        let ts_cal_rel_x10 = ts_cal_2_x10 - ts_cal_1_x10;
+        Self {
+            ts_cal_1_x10,
+            ts_cal_rel_x10,
         }
     }
 }
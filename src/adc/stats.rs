@@ -0,0 +1,91 @@
+//! Statistics of a measured channel between two telemetry reports. The adc task
+//! measures faster than most values are reported, so short dips and peaks
+//! would be lost without the aggregates of all measurements in between
+use core::cell::Cell;
+
+use defmt::Format;
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use south_common::TelemetryDefinition;
+
+use crate::EpsTMContainer;
+
+/// Min, max, mean and number of the measurements of a channel
+#[derive(Format, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Statistics {
+    pub min: i16,
+    pub max: i16,
+    sum: i32,
+    pub count: u16,
+}
+
+impl Statistics {
+    pub const EMPTY: Self = Self {
+        min: i16::MAX,
+        max: i16::MIN,
+        sum: 0,
+        count: 0,
+    };
+
+    pub fn add(&mut self, value: i16) {
+        // the sum can't overflow before the count saturates
+        if self.count == u16::MAX {
+            return;
+        }
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value as i32;
+        self.count += 1;
+    }
+    /// mean rounded towards zero, `None` without measurements
+    pub fn mean(&self) -> Option<i16> {
+        (self.count > 0).then(|| (self.sum / self.count as i32) as i16)
+    }
+    /// statistics telemetry, packed as `[min, max, mean (i16 le), count (u16 le)]`,
+    /// all zero without measurements
+    pub fn container(&self, topic: &dyn TelemetryDefinition) -> EpsTMContainer {
+        let [min, max, mean] = match self.mean() {
+            Some(mean) => [self.min, self.max, mean],
+            None => [0; 3],
+        };
+        let [min_low, min_high] = min.to_le_bytes();
+        let [max_low, max_high] = max.to_le_bytes();
+        let [mean_low, mean_high] = mean.to_le_bytes();
+        let [count_low, count_high] = self.count.to_le_bytes();
+        let stats = u64::from_le_bytes([
+            min_low, min_high, max_low, max_high, mean_low, mean_high, count_low, count_high,
+        ]);
+        EpsTMContainer::new(topic, &stats).unwrap()
+    }
+}
+
+impl Default for Statistics {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+/// Statistics filled by the adc task and taken by the task reporting the channel
+pub struct SharedStatistics(Mutex<CriticalSectionRawMutex, Cell<Statistics>>);
+
+impl SharedStatistics {
+    pub const fn new() -> Self {
+        Self(Mutex::new(Cell::new(Statistics::EMPTY)))
+    }
+    pub fn add(&self, value: i16) {
+        self.0.lock(|stats| {
+            let mut updated = stats.get();
+            updated.add(value);
+            stats.set(updated);
+        });
+    }
+    /// statistics since the last report, the next interval starts empty
+    pub fn take(&self) -> Statistics {
+        self.0.lock(|stats| stats.replace(Statistics::EMPTY))
+    }
+}
+
+impl Default for SharedStatistics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,42 @@
+use embassy_stm32::{
+    Peri,
+    adc::{Adc, AdcChannel, AnyAdcChannel, RxDma, SampleTime},
+    peripherals::ADC1,
+};
+
+use super::AdcSampler;
+
+/// ADC1 of the STM32G0 sampled in dma sequences
+pub struct Stm32Adc<'d, D: RxDma<ADC1>> {
+    adc: Adc<'d, ADC1>,
+    dma_channel: Peri<'d, D>,
+}
+
+impl<'d, D: RxDma<ADC1>> Stm32Adc<'d, D> {
+    pub fn new(adc: Adc<'d, ADC1>, dma_channel: Peri<'d, D>) -> Self {
+        Self { adc, dma_channel }
+    }
+}
+
+impl<'d, D: RxDma<ADC1>> AdcSampler for Stm32Adc<'d, D> {
+    type Channel = AnyAdcChannel<'d, ADC1>;
+
+    fn temperature_channel(&mut self) -> Self::Channel {
+        self.adc.enable_temperature().degrade_adc()
+    }
+    fn hw_channel(channel: &Self::Channel) -> u8 {
+        channel.get_hw_channel()
+    }
+    async fn read<'c>(
+        &mut self,
+        sequence: impl ExactSizeIterator<Item = &'c mut Self::Channel>,
+        readings: &mut [u16],
+    ) where
+        Self::Channel: 'c,
+    {
+        let sequence = sequence.map(|c| (c, SampleTime::CYCLES160_5));
+        self.adc
+            .read(self.dma_channel.reborrow(), sequence, readings)
+            .await;
+    }
+}
@@ -0,0 +1,844 @@
+//! Host simulator of the EPS board.
+//!
+//! Runs the flight control logic on mocked hardware. Telecommands are read from stdin
+//! and telemetry is written to stdout, one CAN frame per line in the can-utils notation
+//! (`123#0102` for classic, `123##0010203` for FD frames). Lines produced by `candump -L`
+//! are accepted as well, so the simulator can be bridged onto a vcan interface:
+//!
+//! ```sh
+//! candump -L vcan0 | cargo sim | xargs -n1 cansend vcan0
+//! ```
+//!
+//! Battery and aux power voltages as well as battery currents follow configurable curves
+//! given as comma separated `<time_s>:<value>` points, e.g. `--bat1 0:840,3600:600`.
+//!
+//! Telecommands are authenticated with `SIM_TC_KEY`, unless the simulator is built
+//! with the `flatsat` feature.
+//!
+//! Time sync broadcasts of the flight computer are read from stdin as well and every
+//! telemetry frame ends in a time stamp, see `eps_software::mission_time`.
+
+use std::{cell::Cell, env, io::BufRead, process::exit, thread};
+
+use embassy_executor::Spawner;
+use embassy_futures::{
+    block_on,
+    select::{Either, select},
+};
+use embassy_sync::{
+    blocking_mutex::raw::{CriticalSectionRawMutex, ThreadModeRawMutex},
+    channel::{Channel, DynamicSender, Receiver, Sender},
+    mutex::Mutex,
+    watch::{DynReceiver, Watch},
+};
+use embassy_time::{Duration, Instant, Timer};
+use eps_software::{
+    EpsTMContainer,
+    adc::{AdcCtrl, AdcCtrlChannel, burst::BurstService, conversion, stats::SharedStatistics},
+    board::{AuxPwrCurrentSense, BATTERY_CAPACITY_MAH, BatteryCurrentSense, SinkCurrentSense},
+    config::{ConfigCommand, ConfigService, EpsConfig, store::ConfigStore},
+    control_loop::{ControlLoop, PowerReadings, mode::ModeReason},
+    event_log::{
+        ERROR_BURST_GAP, ErrorBurst, Event, EventLogService, LogCommand, store::EventLogStore,
+    },
+    mission_time,
+    mock::{
+        adc::{MockAdc, MockAdcChannel},
+        flash::{MOCK_PAGE_SIZE, MockFlash},
+        gpio::{MockFlipFlopChip, MockInputPin, MockNet, MockOutputPin},
+        i2c::{MockI2c, MockTmp100},
+    },
+    protection::ProtectionEvent,
+    pwr_src::{
+        aux_pwr::{self, AuxPwr},
+        battery::{
+            Battery, BatteryTopics, soc::SocEstimator, temp_sensor::TempSensor, tmp100_drv::*,
+        },
+        d_flip_flop::{DFlipFlop, FlipFlopInput},
+        sink_ctrl::{SINKS, SinkCtrl},
+        sink_monitor::{self, SinkMonitor},
+    },
+    script::{Script, ScriptService, store::ScriptStore},
+    telecommand::{
+        ReceivedTc, TcSequencer,
+        auth::{Key, KeyStore, TcAuth},
+    },
+};
+use south_common::{
+    TelemetryContainer, TelemetryDefinition, telecommands, telemetry::eps as tm, types::Sink,
+};
+use static_cell::StaticCell;
+
+const SIM_LOOP_LEN: Duration = Duration::from_millis(100);
+/// telecommand key provisioned on every run
+const SIM_TC_KEY: Key = *b"eps simulator telecommand key 01";
+
+// analog inputs in hw channel order, the last one is the internal temperature sensor
+const BAT_1_INPUT: u8 = 0;
+const BAT_2_INPUT: u8 = 1;
+const AUX_PWR_INPUT: u8 = 2;
+const BAT_1_CURRENT_INPUT: u8 = 3;
+const BAT_2_CURRENT_INPUT: u8 = 4;
+const AUX_PWR_CURRENT_INPUT: u8 = 5;
+const BUS_VOLTAGE_INPUT: u8 = 6;
+// one input per sink in SINKS order
+const SINK_CURRENT_INPUTS: [u8; SINKS.len()] = [7, 8, 9, 10];
+const INTERNAL_TEMP_INPUT: u8 = 11;
+const ANALOG_INPUTS: usize = INTERNAL_TEMP_INPUT as usize + 1;
+
+const INTERNAL_TEMP_TENTH_DEG: i16 = 25_0;
+// current drawn by each enabled sink
+const SINK_LOAD_MA: i16 = 250;
+
+type SimConfigService = ConfigService<'static, MockFlash<'static>>;
+type SimScriptService = ScriptService<'static, MockFlash<'static>>;
+type SimEventLogService = EventLogService<'static, MockFlash<'static>>;
+type SimControlLoop =
+    ControlLoop<'static, MockOutputPin<'static>, MockInputPin<'static>, MockOutputPin<'static>>;
+
+// static concurrency sync management types
+static ITW: StaticCell<Watch<ThreadModeRawMutex, i16, 1>> = StaticCell::new();
+static B1W: StaticCell<Watch<ThreadModeRawMutex, i16, 1>> = StaticCell::new();
+static B2W: StaticCell<Watch<ThreadModeRawMutex, i16, 1>> = StaticCell::new();
+static B1CW: StaticCell<Watch<ThreadModeRawMutex, i16, 1>> = StaticCell::new();
+static B2CW: StaticCell<Watch<ThreadModeRawMutex, i16, 1>> = StaticCell::new();
+static APW: StaticCell<Watch<ThreadModeRawMutex, i16, 1>> = StaticCell::new();
+static APCW: StaticCell<Watch<ThreadModeRawMutex, i16, 1>> = StaticCell::new();
+// bus voltage is used by the sink monitor and the load shedding
+static BVW: StaticCell<Watch<ThreadModeRawMutex, i16, 2>> = StaticCell::new();
+static B1SW: StaticCell<Watch<ThreadModeRawMutex, u16, 1>> = StaticCell::new();
+static B2SW: StaticCell<Watch<ThreadModeRawMutex, u16, 1>> = StaticCell::new();
+static SCW: StaticCell<[Watch<ThreadModeRawMutex, i16, 1>; SINKS.len()]> = StaticCell::new();
+// statistics of the measured channels between two telemetry reports
+static ITST: SharedStatistics = SharedStatistics::new();
+static B1ST: SharedStatistics = SharedStatistics::new();
+static B2ST: SharedStatistics = SharedStatistics::new();
+static B1CST: SharedStatistics = SharedStatistics::new();
+static B2CST: SharedStatistics = SharedStatistics::new();
+static APST: SharedStatistics = SharedStatistics::new();
+static APCST: SharedStatistics = SharedStatistics::new();
+static BVST: SharedStatistics = SharedStatistics::new();
+static SCST: [SharedStatistics; SINKS.len()] = [const { SharedStatistics::new() }; SINKS.len()];
+
+const TM_CHANNEL_BUF_SIZE: usize = 5;
+const BURST_TM_CHANNEL_BUF_SIZE: usize = 2;
+const CMD_CHANNEL_BUF_SIZE: usize = 5;
+const PROTECTION_CHANNEL_BUF_SIZE: usize = 4;
+const FUSE_RESET_CHANNEL_BUF_SIZE: usize = 2;
+const CONFIG_CHANNEL_BUF_SIZE: usize = 2;
+const SCRIPT_CHANNEL_BUF_SIZE: usize = 1;
+const LOG_CHANNEL_BUF_SIZE: usize = 2;
+const FRAME_CHANNEL_BUF_SIZE: usize = 5;
+/// topic id and payload of a received frame
+type RawFrame = (u16, Vec<u8>);
+static TMC: StaticCell<Channel<ThreadModeRawMutex, EpsTMContainer, TM_CHANNEL_BUF_SIZE>> =
+    StaticCell::new();
+// burst captures are streamed only while no other telemetry is waiting
+static BTMC: StaticCell<Channel<ThreadModeRawMutex, EpsTMContainer, BURST_TM_CHANNEL_BUF_SIZE>> =
+    StaticCell::new();
+// telecommand frames are pushed from the stdin thread
+static FRAMEC: StaticCell<Channel<CriticalSectionRawMutex, RawFrame, FRAME_CHANNEL_BUF_SIZE>> =
+    StaticCell::new();
+static CMDC: StaticCell<Channel<ThreadModeRawMutex, ReceivedTc, CMD_CHANNEL_BUF_SIZE>> =
+    StaticCell::new();
+static PROTC: StaticCell<
+    Channel<ThreadModeRawMutex, ProtectionEvent, PROTECTION_CHANNEL_BUF_SIZE>,
+> = StaticCell::new();
+static FUSEC: StaticCell<Channel<ThreadModeRawMutex, Sink, FUSE_RESET_CHANNEL_BUF_SIZE>> =
+    StaticCell::new();
+static CFGC: StaticCell<Channel<ThreadModeRawMutex, ConfigCommand, CONFIG_CHANNEL_BUF_SIZE>> =
+    StaticCell::new();
+static SCRC: StaticCell<Channel<ThreadModeRawMutex, (u8, Script), SCRIPT_CHANNEL_BUF_SIZE>> =
+    StaticCell::new();
+static LOGC: StaticCell<Channel<ThreadModeRawMutex, LogCommand, LOG_CHANNEL_BUF_SIZE>> =
+    StaticCell::new();
+
+/// Piecewise linear value over simulation time
+struct Curve {
+    points: Vec<(f32, i16)>,
+}
+impl Curve {
+    fn constant(value: i16) -> Self {
+        Self {
+            points: vec![(0.0, value)],
+        }
+    }
+    fn parse(arg: &str) -> Option<Self> {
+        let points: Vec<(f32, i16)> = arg
+            .split(',')
+            .map(|point| {
+                let (time, value) = point.split_once(':')?;
+                Some((time.parse().ok()?, value.parse().ok()?))
+            })
+            .collect::<Option<_>>()?;
+        let sorted = points.windows(2).all(|w| w[0].0 < w[1].0);
+        (!points.is_empty() && sorted).then_some(Self { points })
+    }
+    fn at(&self, time_s: f32) -> i16 {
+        let next = self.points.iter().position(|(t, _)| *t > time_s);
+        match next {
+            Some(0) => self.points[0].1,
+            Some(i) => {
+                let (t0, v0) = self.points[i - 1];
+                let (t1, v1) = self.points[i];
+                let ratio = (time_s - t0) / (t1 - t0);
+                v0 + ((v1 - v0) as f32 * ratio) as i16
+            }
+            None => self.points[self.points.len() - 1].1,
+        }
+    }
+}
+
+struct Environment {
+    bat_1: Curve,
+    bat_2: Curve,
+    aux_pwr: Curve,
+    bat_1_current: Curve,
+    bat_2_current: Curve,
+    aux_pwr_current: Curve,
+    temperature_tenth_deg: i16,
+}
+impl Environment {
+    fn from_args() -> Self {
+        let mut environment = Self {
+            bat_1: Curve::constant(7_40),
+            bat_2: Curve::constant(7_40),
+            aux_pwr: Curve::constant(0),
+            bat_1_current: Curve::constant(0),
+            bat_2_current: Curve::constant(0),
+            aux_pwr_current: Curve::constant(0),
+            temperature_tenth_deg: 20_0,
+        };
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let value = args.next().unwrap_or_else(|| usage());
+            let curve = || Curve::parse(&value).unwrap_or_else(|| usage());
+            match arg.as_str() {
+                "--bat1" => environment.bat_1 = curve(),
+                "--bat2" => environment.bat_2 = curve(),
+                "--aux" => environment.aux_pwr = curve(),
+                "--bat1-current" => environment.bat_1_current = curve(),
+                "--bat2-current" => environment.bat_2_current = curve(),
+                "--aux-current" => environment.aux_pwr_current = curve(),
+                "--temp" => {
+                    environment.temperature_tenth_deg = value.parse().unwrap_or_else(|_| usage())
+                }
+                _ => usage(),
+            }
+        }
+        environment
+    }
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: simulator [--bat1 <curve>] [--bat2 <curve>] [--aux <curve>] [--temp <tenth_deg>]\n\
+         \t[--bat1-current <curve>] [--bat2-current <curve>] [--aux-current <curve>]\n\
+         curve: comma separated <time_s>:<value> points in 10mV or mA, e.g. 0:840,3600:600"
+    );
+    exit(1)
+}
+
+/// parse a frame in can-utils notation into id and payload
+fn parse_frame(line: &str) -> Option<RawFrame> {
+    // candump -L prefixes frames with timestamp and interface
+    let frame = line.split_whitespace().last()?;
+    let (id, data) = frame.split_once('#')?;
+    let data = match data.strip_prefix('#') {
+        // skip the fd flags nibble
+        Some(fd_data) => fd_data.get(1..)?,
+        None => data,
+    };
+    let id = u16::from_str_radix(id, 16).ok()?;
+    let bytes = (0..data.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(data.get(i..i + 2)?, 16).ok())
+        .collect::<Option<_>>()?;
+    Some((id, bytes))
+}
+
+fn format_frame(id: u16, data: &[u8]) -> String {
+    let separator = if data.len() > 8 { "##0" } else { "#" };
+    let data: String = data.iter().map(|b| format!("{:02X}", b)).collect();
+    format!("{:03X}{}{}", id, separator, data)
+}
+
+/// stdin reader, runs on its own thread as stdin can not be awaited
+fn tc_reader(
+    frame_channel: &'static Channel<CriticalSectionRawMutex, RawFrame, FRAME_CHANNEL_BUF_SIZE>,
+) {
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let Some((id, data)) = parse_frame(&line) else {
+            eprintln!("invalid frame: {}", line);
+            continue;
+        };
+        let topics = [
+            telecommands::Telecommand.id(),
+            telecommands::TimeTaggedTelecommand.id(),
+            telecommands::TimeSync.id(),
+        ];
+        if !topics.contains(&id) {
+            continue;
+        }
+        block_on(frame_channel.send((id, data)));
+    }
+}
+
+// tc receiving task
+#[embassy_executor::task]
+async fn tc_thread(
+    frame_channel: Receiver<'static, CriticalSectionRawMutex, RawFrame, FRAME_CHANNEL_BUF_SIZE>,
+    tc_channel: Sender<'static, ThreadModeRawMutex, ReceivedTc, CMD_CHANNEL_BUF_SIZE>,
+    tm_sender: DynamicSender<'static, EpsTMContainer>,
+    mut auth: TcAuth<MockFlash<'static>>,
+) {
+    let mut sequencer = TcSequencer::new();
+    loop {
+        let (id, frame) = frame_channel.receive().await;
+        if id == telecommands::TimeSync.id() {
+            match mission_time::sync(&frame) {
+                Some(report) => tm_sender.send(report.container()).await,
+                None => eprintln!("invalid time sync"),
+            }
+            continue;
+        }
+        let received = match id == telecommands::TimeTaggedTelecommand.id() {
+            true => sequencer.receive_time_tagged(&frame, &mut auth),
+            false => sequencer.receive(&frame, &mut auth),
+        };
+        match received {
+            Ok(Some(tc)) => tc_channel.send(tc).await,
+            Ok(None) => (),
+            Err(ack) => {
+                eprintln!("tc rejected");
+                tm_sender.send(ack).await;
+                tm_sender.send(auth.rejects_container()).await;
+            }
+        }
+    }
+}
+
+// analog environment task
+#[embassy_executor::task]
+async fn environment_thread(
+    environment: Environment,
+    inputs: &'static [Cell<u16>; ANALOG_INPUTS],
+    flip_flop_chip: &'static MockFlipFlopChip,
+    sink_enables: &'static [MockNet; SINKS.len()],
+) {
+    let start = Instant::now();
+    let mut loop_time = start;
+    inputs[INTERNAL_TEMP_INPUT as usize].set(conversion::temperature_tenth_deg_to_raw(
+        INTERNAL_TEMP_TENTH_DEG,
+    ));
+    loop {
+        let time_s = (loop_time - start).as_millis() as f32 / 1000.0;
+        for (input, curve) in [
+            (BAT_1_INPUT, &environment.bat_1),
+            (BAT_2_INPUT, &environment.bat_2),
+            (AUX_PWR_INPUT, &environment.aux_pwr),
+        ] {
+            inputs[input as usize].set(conversion::voltage_10mv_to_raw(curve.at(time_s)));
+        }
+        for (input, curve) in [
+            (BAT_1_CURRENT_INPUT, &environment.bat_1_current),
+            (BAT_2_CURRENT_INPUT, &environment.bat_2_current),
+        ] {
+            let current = curve.at(time_s);
+            inputs[input as usize].set(conversion::current_ma_to_raw::<BatteryCurrentSense>(
+                current,
+            ));
+        }
+        let aux_pwr_current = environment.aux_pwr_current.at(time_s);
+        inputs[AUX_PWR_CURRENT_INPUT as usize].set(conversion::current_ma_to_raw::<
+            AuxPwrCurrentSense,
+        >(aux_pwr_current));
+
+        // the enabled sources are or-ed onto the power path
+        let bus_voltage = [&environment.bat_1, &environment.bat_2, &environment.aux_pwr]
+            .into_iter()
+            .zip(&flip_flop_chip.state)
+            .filter(|(_, enabled)| enabled.is_high())
+            .map(|(curve, _)| curve.at(time_s))
+            .max()
+            .unwrap_or(0);
+        inputs[BUS_VOLTAGE_INPUT as usize].set(conversion::voltage_10mv_to_raw(bus_voltage));
+        for (input, enabled) in SINK_CURRENT_INPUTS.into_iter().zip(sink_enables) {
+            let current = if enabled.is_high() { SINK_LOAD_MA } else { 0 };
+            inputs[input as usize].set(conversion::current_ma_to_raw::<SinkCurrentSense>(current));
+        }
+        loop_time += SIM_LOOP_LEN;
+        Timer::at(loop_time).await;
+    }
+}
+
+// Adc reading task
+#[embassy_executor::task]
+async fn adc_thread(mut adc: AdcCtrl<'static, MockAdc<'static>, ANALOG_INPUTS>) {
+    let mut loop_time = Instant::now();
+    loop {
+        adc.run().await;
+        loop_time += SIM_LOOP_LEN;
+        adc.sample_bursts_until(loop_time).await;
+    }
+}
+
+// Burst service task
+#[embassy_executor::task]
+async fn burst_thread(mut service: BurstService<'static>) {
+    loop {
+        service.run().await;
+    }
+}
+
+// Battery task
+#[embassy_executor::task(pool_size = 2)]
+async fn battery_thread(mut battery: Battery<'static, MockI2c<'static>>, loop_len: Duration) {
+    let mut loop_time = Instant::now();
+    loop {
+        battery.run().await;
+        loop_time += loop_len;
+        Timer::at(loop_time).await;
+    }
+}
+
+// control loop task
+#[embassy_executor::task]
+async fn ctrl_thread(mut control_loop: SimControlLoop) {
+    loop {
+        control_loop.run().await;
+    }
+}
+
+// Config service task
+#[embassy_executor::task]
+async fn config_thread(mut service: SimConfigService) {
+    loop {
+        service.run().await;
+    }
+}
+
+// Script service task
+#[embassy_executor::task]
+async fn script_thread(mut service: SimScriptService) {
+    loop {
+        service.run().await;
+    }
+}
+
+// Event log service task
+#[embassy_executor::task]
+async fn event_log_thread(mut service: SimEventLogService) {
+    loop {
+        service.run().await;
+    }
+}
+
+// Internal temperature tm task
+#[embassy_executor::task]
+async fn internal_temp_thread(
+    tm_sender: DynamicSender<'static, EpsTMContainer>,
+    mut temp_receiver: DynReceiver<'static, i16>,
+    temp_stats: &'static SharedStatistics,
+    loop_len: Duration,
+) {
+    let mut loop_time = Instant::now();
+    loop {
+        let container =
+            EpsTMContainer::new(&tm::InternalTemperature, &temp_receiver.get().await).unwrap();
+        tm_sender.send(container).await;
+        let stats = temp_stats.take();
+        tm_sender
+            .send(stats.container(&tm::InternalTemperatureStats))
+            .await;
+
+        loop_time += loop_len;
+        Timer::at(loop_time).await;
+    }
+}
+
+// tm printing task
+#[embassy_executor::task]
+async fn tm_thread(
+    tm_channel: Receiver<'static, ThreadModeRawMutex, EpsTMContainer, TM_CHANNEL_BUF_SIZE>,
+    burst_channel: Receiver<'static, ThreadModeRawMutex, EpsTMContainer, BURST_TM_CHANNEL_BUF_SIZE>,
+) {
+    let mut tx_errors = ErrorBurst::new(Event::CanTxError, ERROR_BURST_GAP);
+    loop {
+        // the regular telemetry goes first
+        let (Either::First(container) | Either::Second(container)) =
+            select(tm_channel.receive(), burst_channel.receive()).await;
+        match mission_time::stamped(container.bytes()) {
+            Some(data) => {
+                println!("{}", format_frame(container.id(), &data));
+                tx_errors.ok();
+            }
+            None => {
+                eprintln!("telemetry too long to stamp: {}", container.id());
+                let [id_low, id_high] = container.id().to_le_bytes();
+                tx_errors.error([id_low, id_high, 0]);
+            }
+        }
+    }
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let environment = Environment::from_args();
+
+    // simulated hardware, leaked as the mocks share state through cells
+    let analog_inputs: &'static [Cell<u16>; ANALOG_INPUTS] = Box::leak(Default::default());
+    let flip_flop_chip: &'static MockFlipFlopChip = Box::leak(Box::new(MockFlipFlopChip::new()));
+    let sink_enables: &'static [MockNet; SINKS.len()] =
+        Box::leak(Box::new(core::array::from_fn(|_| MockNet::new(true))));
+    // configuration flash starts erased on every run
+    let flash_memory: &'static [Cell<u8>] = Box::leak(
+        (0..2 * MOCK_PAGE_SIZE)
+            .map(|_| Cell::new(0xFF))
+            .collect::<Box<_>>(),
+    );
+    let mut config_store = ConfigStore::new(MockFlash::new(flash_memory), 0);
+    let key_memory: &'static [Cell<u8>] = Box::leak(
+        (0..2 * MOCK_PAGE_SIZE)
+            .map(|_| Cell::new(0xFF))
+            .collect::<Box<_>>(),
+    );
+    let mut key_store = KeyStore::new(MockFlash::new(key_memory), 0);
+    let script_memory: &'static [Cell<u8>] = Box::leak(
+        (0..2 * MOCK_PAGE_SIZE)
+            .map(|_| Cell::new(0xFF))
+            .collect::<Box<_>>(),
+    );
+    let mut script_store = ScriptStore::new(MockFlash::new(script_memory), 0);
+    let scripts = script_store.load().unwrap_or_default();
+    let event_log_memory: &'static [Cell<u8>] = Box::leak(
+        (0..2 * MOCK_PAGE_SIZE)
+            .map(|_| Cell::new(0xFF))
+            .collect::<Box<_>>(),
+    );
+    let mut event_log_store = EventLogStore::new(MockFlash::new(event_log_memory), 0);
+    event_log_store.load().unwrap();
+    key_store.provision(&SIM_TC_KEY).unwrap();
+    let tc_auth = TcAuth::new(key_store);
+    let eps_config = config_store.load().unwrap_or(EpsConfig::DEFAULT);
+    let (divider_r1_ohm, divider_r2_ohm) = eps_config.voltage_divider_ohm();
+    conversion::set_voltage_divider(divider_r1_ohm, divider_r2_ohm);
+    conversion::set_vref(eps_config.adc_vref_10mv());
+
+    let tmp100s: &'static [MockTmp100; 2] = Box::leak(Box::new([
+        MockTmp100::new(eps_config.bat_1_temp_sensor_addr().get_addr()),
+        MockTmp100::new(eps_config.bat_2_temp_sensor_addr().get_addr()),
+    ]));
+    tmp100s
+        .iter()
+        .for_each(|t| t.set_temperature_tenth_deg(environment.temperature_tenth_deg));
+    let temp_sensor_i2c: &'static Mutex<ThreadModeRawMutex, _> =
+        Box::leak(Box::new(Mutex::new(MockI2c::new(tmp100s))));
+
+    // flip flop
+    let (bat_1_pins, bat_2_pins, aux_pwr_pins, clk) = flip_flop_chip.pins();
+    let source_flip_flop = DFlipFlop::from_pins(bat_1_pins, bat_2_pins, aux_pwr_pins, clk);
+
+    // sink ctrl
+    let [lst_enable, sens_enable, gps_enable, rhd_enable] =
+        sink_enables.each_ref().map(MockOutputPin::new);
+    let sink_ctrl = SinkCtrl::from_pins(lst_enable, sens_enable, gps_enable, rhd_enable);
+
+    // ADC setup
+    let internal_temperature_watch = ITW.init(Watch::new());
+    let bat_1_watch = B1W.init(Watch::new());
+    let bat_2_watch = B2W.init(Watch::new());
+    let bat_1_current_watch = B1CW.init(Watch::new());
+    let bat_2_current_watch = B2CW.init(Watch::new());
+    let aux_pwr_watch = APW.init(Watch::new());
+    let aux_pwr_current_watch = APCW.init(Watch::new());
+    let bus_voltage_watch = BVW.init(Watch::new());
+    let bat_1_soc_watch = B1SW.init(Watch::new());
+    let bat_2_soc_watch = B2SW.init(Watch::new());
+    let sink_current_watches: &'static [_; SINKS.len()] =
+        SCW.init(core::array::from_fn(|_| Watch::new()));
+
+    let [lst_current, sens_current, gps_current, rhd_current] = core::array::from_fn(|i| {
+        AdcCtrlChannel::new(
+            MockAdcChannel(SINK_CURRENT_INPUTS[i]),
+            sink_current_watches[i].dyn_sender(),
+            &SCST[i],
+            conversion::calculate_current_ma::<SinkCurrentSense>,
+        )
+    });
+    let adc = AdcCtrl::new(
+        MockAdc::new(analog_inputs),
+        internal_temperature_watch.dyn_sender(),
+        &ITST,
+        [
+            AdcCtrlChannel::new(
+                MockAdcChannel(BAT_1_INPUT),
+                bat_1_watch.dyn_sender(),
+                &B1ST,
+                conversion::calculate_voltage_10mv,
+            ),
+            AdcCtrlChannel::new(
+                MockAdcChannel(BAT_2_INPUT),
+                bat_2_watch.dyn_sender(),
+                &B2ST,
+                conversion::calculate_voltage_10mv,
+            ),
+            AdcCtrlChannel::new(
+                MockAdcChannel(AUX_PWR_INPUT),
+                aux_pwr_watch.dyn_sender(),
+                &APST,
+                conversion::calculate_voltage_10mv,
+            ),
+            AdcCtrlChannel::new(
+                MockAdcChannel(BAT_1_CURRENT_INPUT),
+                bat_1_current_watch.dyn_sender(),
+                &B1CST,
+                conversion::calculate_current_ma::<BatteryCurrentSense>,
+            ),
+            AdcCtrlChannel::new(
+                MockAdcChannel(BAT_2_CURRENT_INPUT),
+                bat_2_current_watch.dyn_sender(),
+                &B2CST,
+                conversion::calculate_current_ma::<BatteryCurrentSense>,
+            ),
+            AdcCtrlChannel::new(
+                MockAdcChannel(AUX_PWR_CURRENT_INPUT),
+                aux_pwr_current_watch.dyn_sender(),
+                &APCST,
+                conversion::calculate_current_ma::<AuxPwrCurrentSense>,
+            ),
+            AdcCtrlChannel::new(
+                MockAdcChannel(BUS_VOLTAGE_INPUT),
+                bus_voltage_watch.dyn_sender(),
+                &BVST,
+                conversion::calculate_voltage_10mv,
+            ),
+            lst_current,
+            sens_current,
+            gps_current,
+            rhd_current,
+        ],
+    );
+
+    // telemetry is sent from here on, the boot below waits on the TM channel
+    let tm_channel = TMC.init(Channel::new());
+    let burst_tm_channel = BTMC.init(Channel::new());
+    spawner.must_spawn(tm_thread(
+        tm_channel.receiver(),
+        burst_tm_channel.receiver(),
+    ));
+
+    // channel setup
+    let cmd_channel = CMDC.init(Channel::new());
+    let protection_channel = PROTC.init(Channel::new());
+    let fuse_reset_channel = FUSEC.init(Channel::new());
+    let config_channel = CFGC.init(Channel::new());
+    let script_channel = SCRC.init(Channel::new());
+    let log_channel = LOGC.init(Channel::new());
+
+    // check which temperature sensors are on the bus
+    let temp_sensor_scan = scan_bus(temp_sensor_i2c).await;
+    for (name, addr) in [
+        ("bat 1", eps_config.bat_1_temp_sensor_addr()),
+        ("bat 2", eps_config.bat_2_temp_sensor_addr()),
+    ] {
+        if temp_sensor_scan & addr.scan_bit() == 0 {
+            eprintln!(
+                "{} temp sensor not answering, devices found: {:#010b}",
+                name, temp_sensor_scan
+            );
+        }
+    }
+    let container = EpsTMContainer::new(&tm::TempSensorScan, &temp_sensor_scan).unwrap();
+    tm_channel.send(container).await;
+    tm_channel.send(tc_auth.counter_container()).await;
+
+    // batteries
+    let bat_1_tmp = TempSensor::new(
+        temp_sensor_i2c,
+        eps_config.bat_1_temp_sensor_addr(),
+        eps_config.temp_sensor_resolution(),
+    )
+    .await;
+    let bat_1 = Battery::new(
+        FlipFlopInput::Bat1,
+        bat_1_tmp,
+        bat_1_watch.dyn_receiver().unwrap(),
+        bat_1_current_watch.dyn_receiver().unwrap(),
+        &B1ST,
+        &B1CST,
+        SocEstimator::new(BATTERY_CAPACITY_MAH),
+        bat_1_soc_watch.dyn_sender(),
+        eps_config.battery_voltage_limits(),
+        eps_config.battery_temperature_limits(),
+        protection_channel.dyn_sender(),
+        tm_channel.dyn_sender(),
+        BatteryTopics {
+            temperature: &tm::Bat1Temperature,
+            voltage: &tm::Bat1Voltage,
+            voltage_stats: &tm::Bat1VoltageStats,
+            temp_sensor_errors: &tm::Bat1TempSensorErrors,
+            temp_sensor_state: &tm::Bat1TempSensorState,
+            current: &tm::Bat1Current,
+            current_stats: &tm::Bat1CurrentStats,
+            power: &tm::Bat1Power,
+            state_of_charge: &tm::Bat1StateOfCharge,
+            remaining_capacity: &tm::Bat1RemainingCapacity,
+            time_to_empty: &tm::Bat1TimeToEmpty,
+        },
+    )
+    .await;
+    let bat_2_tmp = TempSensor::new(
+        temp_sensor_i2c,
+        eps_config.bat_2_temp_sensor_addr(),
+        eps_config.temp_sensor_resolution(),
+    )
+    .await;
+    let bat_2 = Battery::new(
+        FlipFlopInput::Bat2,
+        bat_2_tmp,
+        bat_2_watch.dyn_receiver().unwrap(),
+        bat_2_current_watch.dyn_receiver().unwrap(),
+        &B2ST,
+        &B2CST,
+        SocEstimator::new(BATTERY_CAPACITY_MAH),
+        bat_2_soc_watch.dyn_sender(),
+        eps_config.battery_voltage_limits(),
+        eps_config.battery_temperature_limits(),
+        protection_channel.dyn_sender(),
+        tm_channel.dyn_sender(),
+        BatteryTopics {
+            temperature: &tm::Bat2Temperature,
+            voltage: &tm::Bat2Voltage,
+            voltage_stats: &tm::Bat2VoltageStats,
+            temp_sensor_errors: &tm::Bat2TempSensorErrors,
+            temp_sensor_state: &tm::Bat2TempSensorState,
+            current: &tm::Bat2Current,
+            current_stats: &tm::Bat2CurrentStats,
+            power: &tm::Bat2Power,
+            state_of_charge: &tm::Bat2StateOfCharge,
+            remaining_capacity: &tm::Bat2RemainingCapacity,
+            time_to_empty: &tm::Bat2TimeToEmpty,
+        },
+    )
+    .await;
+
+    // aux power
+    let aux_pwr = AuxPwr::new(
+        aux_pwr_watch.dyn_receiver().unwrap(),
+        aux_pwr_current_watch.dyn_receiver().unwrap(),
+        &APST,
+        &APCST,
+        protection_channel.dyn_sender(),
+        tm_channel.dyn_sender(),
+    )
+    .await;
+
+    // sinks
+    let sink_monitor = SinkMonitor::new(
+        bus_voltage_watch.dyn_receiver().unwrap(),
+        sink_current_watches
+            .each_ref()
+            .map(|watch| watch.dyn_receiver().unwrap()),
+        &BVST,
+        SCST.each_ref(),
+        eps_config.sink_fuse_limits(),
+        fuse_reset_channel.dyn_receiver(),
+        protection_channel.dyn_sender(),
+        tm_channel.dyn_sender(),
+    );
+
+    // configuration telecommands
+    let config_service = ConfigService::new(
+        config_store,
+        eps_config,
+        config_channel.dyn_receiver(),
+        tm_channel.dyn_sender(),
+    );
+
+    // script uploads
+    let script_service = ScriptService::new(
+        script_store,
+        scripts.clone(),
+        script_channel.dyn_receiver(),
+        tm_channel.dyn_sender(),
+    );
+
+    // event log downloads
+    let event_log_service = EventLogService::new(
+        event_log_store,
+        log_channel.dyn_receiver(),
+        tm_channel.dyn_sender(),
+    );
+
+    // burst capture downloads
+    let burst_service = BurstService::new(burst_tm_channel.dyn_sender());
+
+    // Main control loop setup
+    let control_loop = ControlLoop::spawn(
+        ModeReason::PowerOn,
+        eps_config,
+        scripts,
+        source_flip_flop,
+        sink_ctrl,
+        cmd_channel.dyn_receiver(),
+        protection_channel.dyn_receiver(),
+        fuse_reset_channel.dyn_sender(),
+        config_channel.dyn_sender(),
+        script_channel.dyn_sender(),
+        log_channel.dyn_sender(),
+        PowerReadings {
+            bus_voltage: bus_voltage_watch.dyn_receiver().unwrap(),
+            soc: [
+                bat_1_soc_watch.dyn_receiver().unwrap(),
+                bat_2_soc_watch.dyn_receiver().unwrap(),
+            ],
+        },
+        tm_channel.dyn_sender(),
+    )
+    .await;
+
+    let frame_channel = FRAMEC.init(Channel::new());
+    thread::spawn(move || tc_reader(frame_channel));
+
+    spawner.must_spawn(environment_thread(
+        environment,
+        analog_inputs,
+        flip_flop_chip,
+        sink_enables,
+    ));
+    spawner.must_spawn(adc_thread(adc));
+    spawner.must_spawn(burst_thread(burst_service));
+    spawner.must_spawn(ctrl_thread(control_loop));
+    spawner.must_spawn(config_thread(config_service));
+    spawner.must_spawn(script_thread(script_service));
+    spawner.must_spawn(event_log_thread(event_log_service));
+
+    let battery_loop_len = eps_config.battery_loop_len();
+    spawner.must_spawn(battery_thread(bat_1, battery_loop_len));
+    spawner.must_spawn(battery_thread(bat_2, battery_loop_len));
+    spawner.must_spawn(aux_pwr::aux_pwr_thread(
+        aux_pwr,
+        eps_config.aux_pwr_loop_len(),
+    ));
+    spawner.must_spawn(sink_monitor::sink_monitor_thread(
+        sink_monitor,
+        eps_config.sink_loop_len(),
+    ));
+
+    spawner.must_spawn(internal_temp_thread(
+        tm_channel.dyn_sender(),
+        internal_temperature_watch.dyn_receiver().unwrap(),
+        &ITST,
+        eps_config.internal_temp_loop_len(),
+    ));
+    spawner.must_spawn(tc_thread(
+        frame_channel.receiver(),
+        cmd_channel.sender(),
+        tm_channel.dyn_sender(),
+        tc_auth,
+    ));
+}
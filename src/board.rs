@@ -0,0 +1,43 @@
+//! Wiring of the EPS board
+
+use crate::{adc::conversion::CurrentSense, pwr_src::battery::tmp100_drv::Addr0State};
+
+/// ADD0 strapping of the battery temperature sensors
+pub const BAT_1_TEMP_SENSOR_ADDR: Addr0State = Addr0State::Low;
+pub const BAT_2_TEMP_SENSOR_ADDR: Addr0State = Addr0State::High;
+
+/// Start of the two flash pages holding the configuration, the last pages of bank 2
+pub const CONFIG_FLASH_OFFSET: u32 = 0x7_F000;
+/// Start of the two flash pages holding the telecommand key, right before the configuration
+pub const TC_KEY_FLASH_OFFSET: u32 = 0x7_E000;
+/// Start of the two flash pages holding the power scripts, right before the telecommand key
+pub const SCRIPT_FLASH_OFFSET: u32 = 0x7_D000;
+/// Start of the two flash pages holding the event log, right before the power scripts
+pub const EVENT_LOG_FLASH_OFFSET: u32 = 0x7_C000;
+
+/// Nominal capacity of each battery pack
+pub const BATTERY_CAPACITY_MAH: u16 = 3_400;
+
+/// Bidirectional battery current sense, positive currents discharge the battery
+pub struct BatteryCurrentSense;
+impl CurrentSense for BatteryCurrentSense {
+    const SHUNT_MOHM: i32 = 10;
+    const GAIN: i32 = 50;
+    const OFFSET_RAW: i32 = 2048;
+}
+
+/// Unidirectional current sense of the aux power input
+pub struct AuxPwrCurrentSense;
+impl CurrentSense for AuxPwrCurrentSense {
+    const SHUNT_MOHM: i32 = 10;
+    const GAIN: i32 = 50;
+    const OFFSET_RAW: i32 = 0;
+}
+
+/// Unidirectional current sense of the sink high side switches
+pub struct SinkCurrentSense;
+impl CurrentSense for SinkCurrentSense {
+    const SHUNT_MOHM: i32 = 20;
+    const GAIN: i32 = 50;
+    const OFFSET_RAW: i32 = 0;
+}
@@ -0,0 +1,293 @@
+//! Parameter table and configuration kept in flash, loaded at boot with fallback
+//! to the defaults. Parameters changed by telecommand take effect after they are
+//! committed and the EPS is reset
+pub mod param;
+pub mod store;
+
+use defmt::Format;
+use embassy_sync::channel::{DynamicReceiver, DynamicSender};
+use embassy_time::Duration;
+use embedded_storage::nor_flash::NorFlash;
+use south_common::telemetry::eps as tm;
+
+#[cfg(feature = "stm32")]
+use crate::shared_flash::BoardFlash;
+use crate::{
+    EpsTMContainer,
+    board::{BAT_1_TEMP_SENSOR_ADDR, BAT_2_TEMP_SENSOR_ADDR},
+    control_loop::{arming::CriticalSet, mode::Mode},
+    protection::{
+        overcurrent::{FuseLimits, SINK_FUSE_LIMITS},
+        temperature::{BATTERY_TEMPERATURE_LIMITS, TemperatureLimits},
+        voltage::{BATTERY_VOLTAGE_LIMITS, VoltageLimits},
+    },
+    pwr_src::{
+        battery::tmp100_drv::{Addr0State, Resolution},
+        sink_ctrl::{SINKS, SinkSet},
+    },
+};
+use param::{PARAM_COUNT, Param, Unit};
+use store::ConfigStore;
+
+// Config service task
+#[cfg(feature = "stm32")]
+#[embassy_executor::task]
+pub async fn config_thread(mut service: ConfigService<'static, BoardFlash>) {
+    loop {
+        service.run().await;
+    }
+}
+
+#[repr(u8)]
+#[derive(Format, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigError {
+    UnknownParam = 1,
+    OutOfRange,
+    /// flash access failed or did not read back what was written
+    Flash,
+    /// no valid record in flash
+    NoRecord,
+    /// the battery under- and overvoltage windows would overlap
+    VoltageWindowsOverlap,
+}
+
+/// Configuration record, one raw value per [`Param`]
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub struct EpsConfig {
+    values: [u16; PARAM_COUNT],
+}
+
+impl Default for EpsConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl EpsConfig {
+    pub const DEFAULT: Self = {
+        let mut values = [0; PARAM_COUNT];
+        let mut i = 0;
+        while i < PARAM_COUNT {
+            values[i] = Param::ALL[i].info().default as u16;
+            i += 1;
+        }
+        Self { values }
+    };
+
+    pub fn get(&self, param: Param) -> i32 {
+        param.info().kind.decode(self.raw(param))
+    }
+    /// value as sent in telemetry and stored in flash
+    pub fn raw(&self, param: Param) -> u16 {
+        self.values[param as usize]
+    }
+    pub fn set(&mut self, param: Param, value: i32) -> Result<(), ConfigError> {
+        if !param.info().contains(value) {
+            return Err(ConfigError::OutOfRange);
+        }
+        let mut config = *self;
+        config.values[param as usize] = value as u16;
+        // an undervoltage has to recover below the level an overvoltage recovers at
+        let limits = config.battery_voltage_limits();
+        if limits.under_recover_10mv >= limits.over_recover_10mv {
+            return Err(ConfigError::VoltageWindowsOverlap);
+        }
+        *self = config;
+        Ok(())
+    }
+    pub fn set_raw(&mut self, param: Param, raw: u16) -> Result<(), ConfigError> {
+        self.set(param, param.info().kind.decode(raw))
+    }
+    pub fn reset(&mut self, param: Param) {
+        self.values[param as usize] = param.info().default as u16;
+    }
+
+    fn duration(&self, param: Param) -> Duration {
+        let value = self.get(param) as u64;
+        match param.info().unit {
+            Unit::Second => Duration::from_secs(value),
+            _ => Duration::from_millis(value),
+        }
+    }
+    pub fn tm_interval(&self) -> Duration {
+        self.duration(Param::TmIntervalMs)
+    }
+    pub fn adc_loop_len(&self) -> Duration {
+        self.duration(Param::AdcLoopMs)
+    }
+    pub fn battery_loop_len(&self) -> Duration {
+        self.duration(Param::BatteryLoopMs)
+    }
+    pub fn aux_pwr_loop_len(&self) -> Duration {
+        self.duration(Param::AuxPwrLoopMs)
+    }
+    pub fn sink_loop_len(&self) -> Duration {
+        self.duration(Param::SinkLoopMs)
+    }
+    pub fn internal_temp_loop_len(&self) -> Duration {
+        self.duration(Param::InternalTempLoopMs)
+    }
+    /// upper and lower resistor of the voltage dividers in front of the adc
+    pub fn voltage_divider_ohm(&self) -> (u16, u16) {
+        (self.raw(Param::DividerR1Ohm), self.raw(Param::DividerR2Ohm))
+    }
+    pub fn adc_vref_10mv(&self) -> u16 {
+        self.raw(Param::AdcVref)
+    }
+    pub fn temp_sensor_resolution(&self) -> Resolution {
+        // values are range checked, the fallback is never taken
+        Resolution::from_id(self.raw(Param::TempSensorResolution) as u8)
+            .unwrap_or(Resolution::BITS12)
+    }
+    pub fn bat_1_temp_sensor_addr(&self) -> Addr0State {
+        Addr0State::from_id(self.raw(Param::Bat1TempSensorAddr) as u8)
+            .unwrap_or(BAT_1_TEMP_SENSOR_ADDR)
+    }
+    pub fn bat_2_temp_sensor_addr(&self) -> Addr0State {
+        Addr0State::from_id(self.raw(Param::Bat2TempSensorAddr) as u8)
+            .unwrap_or(BAT_2_TEMP_SENSOR_ADDR)
+    }
+    /// sinks enabled when entering `mode`
+    pub fn mode_sinks(&self, mode: Mode) -> SinkSet {
+        let param = match mode {
+            Mode::Standby => Param::StandbySinks,
+            Mode::Recovery => Param::RecoverySinks,
+            Mode::Safe => Param::SafeSinks,
+            Mode::Launch | Mode::Flight => return mode.config().sinks,
+        };
+        SinkSet::from_bits(self.raw(param) as u8)
+    }
+    /// time without telecommands after which `mode` enters safe mode
+    pub fn comm_loss_timeout(&self, mode: Mode) -> Option<Duration> {
+        let param = match mode {
+            Mode::Standby => Param::StandbyCommLossS,
+            Mode::Recovery => Param::RecoveryCommLossS,
+            Mode::Launch | Mode::Flight | Mode::Safe => return mode.config().comm_loss_timeout,
+        };
+        (self.raw(param) != 0).then(|| self.duration(param))
+    }
+    pub fn critical_commands(&self) -> CriticalSet {
+        CriticalSet::from_bits(self.raw(Param::CriticalCommands) as u8)
+    }
+    pub fn arm_window(&self) -> Duration {
+        self.duration(Param::ArmWindowS)
+    }
+    pub fn battery_voltage_limits(&self) -> VoltageLimits {
+        let defaults = BATTERY_VOLTAGE_LIMITS;
+        let under_10mv = self.get(Param::BatUndervoltage) as i16;
+        let over_10mv = self.get(Param::BatOvervoltage) as i16;
+        VoltageLimits {
+            under_10mv,
+            under_recover_10mv: under_10mv + defaults.under_recover_10mv - defaults.under_10mv,
+            over_10mv,
+            over_recover_10mv: over_10mv + defaults.over_recover_10mv - defaults.over_10mv,
+            ..defaults
+        }
+    }
+    pub fn battery_temperature_limits(&self) -> TemperatureLimits {
+        TemperatureLimits {
+            min_tenth_deg: self.get(Param::BatMinTemperature) as i16,
+            max_tenth_deg: self.get(Param::BatMaxTemperature) as i16,
+            ..BATTERY_TEMPERATURE_LIMITS
+        }
+    }
+    /// fuse limits in [`SINKS`] order
+    pub fn sink_fuse_limits(&self) -> [FuseLimits; SINKS.len()] {
+        let limits = [
+            Param::RocketLSTFuseLimit,
+            Param::SensorUpperFuseLimit,
+            Param::GPSFuseLimit,
+            Param::RocketHDFuseLimit,
+        ];
+        core::array::from_fn(|i| FuseLimits {
+            limit_ma: self.get(limits[i]) as i16,
+            ..SINK_FUSE_LIMITS[i]
+        })
+    }
+}
+
+/// Configuration telecommands, forwarded by the control loop
+#[derive(Clone, Copy)]
+pub enum ConfigCommand {
+    Get(u8),
+    Set(u8, u16),
+    /// set a parameter back to its default
+    Reset(u8),
+    /// write the modified configuration to flash
+    Commit,
+}
+
+/// Answers configuration telecommands. Modifications are collected in a
+/// pending configuration until they are committed
+pub struct ConfigService<'a, F: NorFlash> {
+    store: ConfigStore<F>,
+    pending: EpsConfig,
+    cmd_receiver: DynamicReceiver<'a, ConfigCommand>,
+    tm_sender: DynamicSender<'a, EpsTMContainer>,
+}
+
+impl<'a, F: NorFlash> ConfigService<'a, F> {
+    /// `config` is the configuration loaded from `store` at boot
+    pub fn new(
+        store: ConfigStore<F>,
+        config: EpsConfig,
+        cmd_receiver: DynamicReceiver<'a, ConfigCommand>,
+        tm_sender: DynamicSender<'a, EpsTMContainer>,
+    ) -> Self {
+        Self {
+            store,
+            pending: config,
+            cmd_receiver,
+            tm_sender,
+        }
+    }
+    fn modify(&mut self, id: u8, raw: Option<u16>) -> (u16, Result<(), ConfigError>) {
+        let Some(param) = Param::from_id(id) else {
+            return (0, Err(ConfigError::UnknownParam));
+        };
+        let result = match raw {
+            Some(raw) => self.pending.set_raw(param, raw),
+            None => {
+                self.pending.reset(param);
+                Ok(())
+            }
+        };
+        (self.pending.raw(param), result)
+    }
+    /// reply with the current raw value of a parameter and the validation result
+    async fn send_param(&self, id: u8, raw: u16, result: Result<(), ConfigError>) {
+        let status = result.err().map_or(0, |e| e as u8);
+        let [value_low, value_high] = raw.to_le_bytes();
+        let reply = u32::from_le_bytes([id, status, value_low, value_high]);
+        let container = EpsTMContainer::new(&tm::ConfigParam, &reply).unwrap();
+        self.tm_sender.send(container).await;
+    }
+    pub async fn run(&mut self) {
+        match self.cmd_receiver.receive().await {
+            ConfigCommand::Get(id) => {
+                let (value, result) = match Param::from_id(id) {
+                    Some(param) => (self.pending.raw(param), Ok(())),
+                    None => (0, Err(ConfigError::UnknownParam)),
+                };
+                self.send_param(id, value, result).await;
+            }
+            ConfigCommand::Set(id, value) => {
+                let (value, result) = self.modify(id, Some(value));
+                self.send_param(id, value, result).await;
+            }
+            ConfigCommand::Reset(id) => {
+                let (value, result) = self.modify(id, None);
+                self.send_param(id, value, result).await;
+            }
+            ConfigCommand::Commit => {
+                let status = self
+                    .store
+                    .commit(&self.pending)
+                    .err()
+                    .map_or(0, |e| e as u8);
+                let container = EpsTMContainer::new(&tm::ConfigCommit, &status).unwrap();
+                self.tm_sender.send(container).await;
+            }
+        }
+    }
+}
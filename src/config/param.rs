@@ -0,0 +1,255 @@
+use defmt::Format;
+use south_common::types::Sink;
+
+use crate::{
+    board::{BAT_1_TEMP_SENSOR_ADDR, BAT_2_TEMP_SENSOR_ADDR},
+    control_loop::{arming::CriticalSet, mode::Mode},
+    protection::{
+        overcurrent::SINK_FUSE_LIMITS, temperature::BATTERY_TEMPERATURE_LIMITS,
+        voltage::BATTERY_VOLTAGE_LIMITS,
+    },
+    pwr_src::{
+        battery::tmp100_drv::{Addr0State, Resolution},
+        sink_ctrl::{SinkSet, sink_index},
+    },
+};
+
+/// Tunable parameter, the id is its position in the stored configuration record
+/// so new parameters are only ever appended
+#[repr(u8)]
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum Param {
+    TmIntervalMs,
+    AdcLoopMs,
+    BatteryLoopMs,
+    AuxPwrLoopMs,
+    SinkLoopMs,
+    InternalTempLoopMs,
+    DividerR1Ohm,
+    DividerR2Ohm,
+    /// [`Resolution`] of the battery temperature sensors
+    TempSensorResolution,
+    /// [`Addr0State`] strapping of the bat 1 temperature sensor
+    Bat1TempSensorAddr,
+    /// [`Addr0State`] strapping of the bat 2 temperature sensor
+    Bat2TempSensorAddr,
+    /// sinks enabled in standby, the mode entered after power on
+    StandbySinks,
+    BatUndervoltage,
+    BatOvervoltage,
+    BatMinTemperature,
+    BatMaxTemperature,
+    RocketLSTFuseLimit,
+    SensorUpperFuseLimit,
+    GPSFuseLimit,
+    RocketHDFuseLimit,
+    /// adc reference voltage
+    AdcVref,
+    /// comm loss timeout in standby, 0 disables it
+    StandbyCommLossS,
+    /// comm loss timeout in recovery, 0 disables it. Off by default, safe mode
+    /// would turn off the GPS
+    RecoveryCommLossS,
+    RecoverySinks,
+    SafeSinks,
+    /// [`CriticalSet`] requiring arming in launch and flight
+    CriticalCommands,
+    /// time an armed command can be executed in
+    ArmWindowS,
+}
+
+pub const PARAM_COUNT: usize = core::mem::variant_count::<Param>();
+
+/// Encoding of a parameter value in its 16 bit raw form
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum ParamKind {
+    Unsigned,
+    /// two's complement
+    Signed,
+}
+
+impl ParamKind {
+    pub const fn decode(self, raw: u16) -> i32 {
+        match self {
+            Self::Unsigned => raw as i32,
+            Self::Signed => raw as i16 as i32,
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    /// enumeration or bitmask, see the parameter
+    None,
+    Millisecond,
+    Second,
+    Ohm,
+    TenMillivolt,
+    Milliampere,
+    TenthDegree,
+}
+
+/// Entry of the parameter table
+#[derive(Clone, Copy)]
+pub struct ParamInfo {
+    pub kind: ParamKind,
+    pub unit: Unit,
+    pub default: i32,
+    pub min: i32,
+    pub max: i32,
+}
+
+const fn unsigned(unit: Unit, default: i32, min: i32, max: i32) -> ParamInfo {
+    ParamInfo {
+        kind: ParamKind::Unsigned,
+        unit,
+        default,
+        min,
+        max,
+    }
+}
+const fn signed(unit: Unit, default: i32, min: i32, max: i32) -> ParamInfo {
+    ParamInfo {
+        kind: ParamKind::Signed,
+        ..unsigned(unit, default, min, max)
+    }
+}
+const fn sinks(mode: Mode) -> ParamInfo {
+    unsigned(
+        Unit::None,
+        mode.config().sinks.bits() as i32,
+        0,
+        SinkSet::ALL.bits() as i32,
+    )
+}
+const fn comm_loss_s(mode: Mode) -> ParamInfo {
+    let default = match mode.config().comm_loss_timeout {
+        Some(timeout) => timeout.as_secs() as i32,
+        None => 0,
+    };
+    unsigned(Unit::Second, default, 0, u16::MAX as i32)
+}
+const fn fuse_limit(sink: Sink) -> ParamInfo {
+    let default = SINK_FUSE_LIMITS[sink_index(sink)].limit_ma as i32;
+    unsigned(Unit::Milliampere, default, 100, 5_000)
+}
+
+impl Param {
+    pub const ALL: [Self; PARAM_COUNT] = [
+        Self::TmIntervalMs,
+        Self::AdcLoopMs,
+        Self::BatteryLoopMs,
+        Self::AuxPwrLoopMs,
+        Self::SinkLoopMs,
+        Self::InternalTempLoopMs,
+        Self::DividerR1Ohm,
+        Self::DividerR2Ohm,
+        Self::TempSensorResolution,
+        Self::Bat1TempSensorAddr,
+        Self::Bat2TempSensorAddr,
+        Self::StandbySinks,
+        Self::BatUndervoltage,
+        Self::BatOvervoltage,
+        Self::BatMinTemperature,
+        Self::BatMaxTemperature,
+        Self::RocketLSTFuseLimit,
+        Self::SensorUpperFuseLimit,
+        Self::GPSFuseLimit,
+        Self::RocketHDFuseLimit,
+        Self::AdcVref,
+        Self::StandbyCommLossS,
+        Self::RecoveryCommLossS,
+        Self::RecoverySinks,
+        Self::SafeSinks,
+        Self::CriticalCommands,
+        Self::ArmWindowS,
+    ];
+
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id as usize {
+            i if i < PARAM_COUNT => Some(Self::ALL[i]),
+            _ => None,
+        }
+    }
+    /// type, unit, default and allowed range of the parameter
+    pub const fn info(self) -> ParamInfo {
+        match self {
+            Self::TmIntervalMs | Self::BatteryLoopMs | Self::AuxPwrLoopMs => {
+                unsigned(Unit::Millisecond, 500, 100, 10_000)
+            }
+            // the fuse trip delays rely on a fast adc and sink loop
+            Self::AdcLoopMs | Self::SinkLoopMs => unsigned(Unit::Millisecond, 100, 10, 200),
+            Self::InternalTempLoopMs => unsigned(Unit::Millisecond, 2_000, 100, 60_000),
+            Self::DividerR1Ohm => unsigned(Unit::Ohm, 100, 1, u16::MAX as i32),
+            Self::DividerR2Ohm => unsigned(Unit::Ohm, 10, 1, u16::MAX as i32),
+            Self::TempSensorResolution => unsigned(
+                Unit::None,
+                Resolution::BITS12 as i32,
+                0,
+                Resolution::BITS12 as i32,
+            ),
+            Self::Bat1TempSensorAddr => unsigned(
+                Unit::None,
+                BAT_1_TEMP_SENSOR_ADDR as i32,
+                0,
+                Addr0State::Low as i32,
+            ),
+            Self::Bat2TempSensorAddr => unsigned(
+                Unit::None,
+                BAT_2_TEMP_SENSOR_ADDR as i32,
+                0,
+                Addr0State::Low as i32,
+            ),
+            Self::StandbySinks => sinks(Mode::Standby),
+            // each limit keeps its recover distance, the windows are checked
+            // together when setting either of them
+            Self::BatUndervoltage => unsigned(
+                Unit::TenMillivolt,
+                BATTERY_VOLTAGE_LIMITS.under_10mv as i32,
+                5_00,
+                7_50,
+            ),
+            Self::BatOvervoltage => unsigned(
+                Unit::TenMillivolt,
+                BATTERY_VOLTAGE_LIMITS.over_10mv as i32,
+                7_60,
+                9_00,
+            ),
+            Self::BatMinTemperature => signed(
+                Unit::TenthDegree,
+                BATTERY_TEMPERATURE_LIMITS.min_tenth_deg as i32,
+                -40_0,
+                10_0,
+            ),
+            Self::BatMaxTemperature => signed(
+                Unit::TenthDegree,
+                BATTERY_TEMPERATURE_LIMITS.max_tenth_deg as i32,
+                30_0,
+                80_0,
+            ),
+            Self::RocketLSTFuseLimit => fuse_limit(Sink::RocketLST),
+            Self::SensorUpperFuseLimit => fuse_limit(Sink::SensorUpper),
+            Self::GPSFuseLimit => fuse_limit(Sink::GPS),
+            Self::RocketHDFuseLimit => fuse_limit(Sink::RocketHD),
+            Self::AdcVref => unsigned(Unit::TenMillivolt, 3_30, 3_00, 3_60),
+            Self::StandbyCommLossS => comm_loss_s(Mode::Standby),
+            Self::RecoveryCommLossS => comm_loss_s(Mode::Recovery),
+            Self::RecoverySinks => sinks(Mode::Recovery),
+            Self::SafeSinks => sinks(Mode::Safe),
+            Self::CriticalCommands => unsigned(
+                Unit::None,
+                CriticalSet::DEFAULT.bits() as i32,
+                0,
+                CriticalSet::ALL.bits() as i32,
+            ),
+            Self::ArmWindowS => unsigned(Unit::Second, 10, 1, 255),
+        }
+    }
+}
+
+impl ParamInfo {
+    pub const fn contains(&self, value: i32) -> bool {
+        self.min <= value && value <= self.max
+    }
+}
@@ -0,0 +1,71 @@
+use embedded_storage::nor_flash::NorFlash;
+
+use super::{
+    ConfigError, EpsConfig,
+    param::{PARAM_COUNT, Param},
+};
+use crate::record_store::{RecordStore, record_size};
+
+const MAGIC: u16 = 0xC0F6;
+/// bumped whenever the record layout changes, records of other layouts are ignored
+const LAYOUT_VERSION: u8 = 2;
+
+// parameter count and padding
+const PAYLOAD_HEADER_SIZE: usize = 2;
+// room for parameters added later, so records of older firmware stay readable
+const RECORD_PARAMS: usize = 32;
+const PAYLOAD_SIZE: usize = PAYLOAD_HEADER_SIZE + 2 * RECORD_PARAMS;
+/// size of a record slot, padded to the flash double word
+pub const RECORD_SIZE: usize = record_size(PAYLOAD_SIZE);
+
+const _: () = assert!(PARAM_COUNT <= RECORD_PARAMS);
+
+/// Configuration records in a [`RecordStore`], the latest valid record wins
+pub struct ConfigStore<F: NorFlash> {
+    records: RecordStore<F, RECORD_SIZE>,
+}
+
+impl<F: NorFlash> ConfigStore<F> {
+    /// `offset` is the start of two erase pages reserved for the configuration
+    pub fn new(flash: F, offset: u32) -> Self {
+        Self {
+            records: RecordStore::new(flash, offset, MAGIC, LAYOUT_VERSION),
+        }
+    }
+    /// latest valid configuration in flash
+    pub fn load(&mut self) -> Result<EpsConfig, ConfigError> {
+        self.records.load().map_err(|_| ConfigError::Flash)?;
+        let record = self.records.latest().map_err(|_| ConfigError::Flash)?;
+        Ok(decode(record.ok_or(ConfigError::NoRecord)?.payload()))
+    }
+    /// append `config` as the new latest record, the store has to be loaded first
+    pub fn commit(&mut self, config: &EpsConfig) -> Result<(), ConfigError> {
+        self.records
+            .append(&encode(config))
+            .map_err(|_| ConfigError::Flash)
+    }
+}
+
+fn encode(config: &EpsConfig) -> [u8; PAYLOAD_SIZE] {
+    let mut payload = [0; PAYLOAD_SIZE];
+    payload[0] = PARAM_COUNT as u8;
+    let values = payload[PAYLOAD_HEADER_SIZE..].chunks_exact_mut(2);
+    for (bytes, param) in values.zip(Param::ALL) {
+        bytes.copy_from_slice(&config.raw(param).to_le_bytes());
+    }
+    payload
+}
+
+/// configuration of a valid record. Parameters missing in records of older
+/// firmware or out of range keep their default
+fn decode(payload: &[u8]) -> EpsConfig {
+    let count = payload[0] as usize;
+    let mut config = EpsConfig::DEFAULT;
+    let values = payload[PAYLOAD_HEADER_SIZE..PAYLOAD_SIZE]
+        .chunks_exact(2)
+        .take(count);
+    for (bytes, param) in values.zip(Param::ALL) {
+        let _ = config.set_raw(param, u16::from_le_bytes([bytes[0], bytes[1]]));
+    }
+    config
+}
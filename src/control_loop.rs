@@ -1,13 +1,47 @@
+pub mod arming;
+pub mod mode;
+pub mod power_budget;
+mod revert_scheduler;
+mod script_runner;
+mod sink_shedding;
+mod source_protection;
+mod tc_queue;
+
+use core::convert::Infallible;
+
 use crate::bitflags;
-use embassy_futures::select::{Either, select};
-use embassy_sync::channel::{DynamicReceiver, DynamicSender};
+use embassy_futures::select::{Either4, select4};
+use embassy_sync::{
+    channel::{DynamicReceiver, DynamicSender},
+    watch::DynReceiver,
+};
 use embassy_time::{Duration, Instant, Timer};
-use south_common::telemetry::eps as tm;
+use embedded_hal::digital::{InputPin, OutputPin, StatefulOutputPin};
+use south_common::{TelemetryDefinition, telemetry::eps as tm};
 
 use crate::EpsTMContainer;
+use crate::adc::burst::{self, BurstConfig, Edge, Threshold, Trigger, TriggerSet};
+use crate::config::{ConfigCommand, EpsConfig};
+use crate::event_log::{self, Event, LogCommand};
+use crate::mission_time;
+use crate::protection::{
+    ProtectionEvent, SwitchReason,
+    overcurrent::FuseStatus,
+    temperature::{FaultResponse, TemperatureStatus},
+};
 use crate::pwr_src::d_flip_flop::{DFlipFlop, FlipFlopInput};
-use crate::pwr_src::sink_ctrl::SinkCtrl;
-use south_common::types::{EPSCommand, Sink, Telecommand};
+use crate::pwr_src::sink_ctrl::{SINKS, SinkCtrl, SinkSet, sink_index};
+use crate::script::{Script, ScriptState, Scripts, Step, progress_container};
+use crate::telecommand::{AckStage, ReceivedTc, TcFailure, TimeTag, ack_container};
+use arming::{Arming, CriticalCommand};
+use mode::{Mode, ModeReason};
+use power_budget::{LOAD_SHED_TABLE, PowerBudget, ShedEntry};
+use revert_scheduler::{RevertAction, RevertScheduler};
+use script_runner::ScriptRunner;
+use sink_shedding::SinkShedding;
+use source_protection::SourceProtection;
+use south_common::types::{EPSCommand, FlipFlopState, Sink, Telecommand};
+use tc_queue::{QueuedTc, TcQueue};
 
 bitflags! {
     pub struct Enabled: u8 {
@@ -20,81 +54,604 @@ bitflags! {
     }
 }
 
-const CTRL_LOOP_TM_INTERVAL: Duration = Duration::from_millis(500);
+/// Measurements the load shedding is based on
+pub struct PowerReadings<'d> {
+    pub bus_voltage: DynReceiver<'d, i16>,
+    /// state of charge of bat 1 and bat 2 in permille
+    pub soc: [DynReceiver<'d, u16>; 2],
+}
+
+#[cfg(feature = "stm32")]
+pub type BoardControlLoop = ControlLoop<
+    'static,
+    embassy_stm32::gpio::Output<'static>,
+    embassy_stm32::gpio::Input<'static>,
+    embassy_stm32::gpio::Output<'static>,
+>;
 
 // control loop task
+#[cfg(feature = "stm32")]
 #[embassy_executor::task]
-pub async fn ctrl_thread(mut control_loop: ControlLoop<'static>) {
+pub async fn ctrl_thread(mut control_loop: BoardControlLoop) {
     loop {
         control_loop.run().await;
     }
 }
 
-pub struct ControlLoop<'d> {
-    source_flip_flop: DFlipFlop<'d>,
-    sink_ctrl: SinkCtrl<'d>,
+pub struct ControlLoop<'d, O, I, S> {
+    source_flip_flop: DFlipFlop<O, I>,
+    sink_ctrl: SinkCtrl<S>,
+    mode: Mode,
+    last_tc: Instant,
+    eps_config: EpsConfig,
     next_tm: Instant,
-    cmd_receiver: DynamicReceiver<'d, Telecommand>,
+    reverts: RevertScheduler,
+    arming: Arming,
+    tc_queue: TcQueue,
+    scripts: Scripts,
+    script_runner: ScriptRunner,
+    // source state requested by telecommand, protection might override it
+    commanded_source: FlipFlopState,
+    source_protection: SourceProtection,
+    // sinks requested by telecommand, shedding and fuses might keep them off
+    commanded_sinks: SinkSet,
+    sink_shedding: SinkShedding,
+    power_budget: PowerBudget,
+    power_readings: PowerReadings<'d>,
+    open_fuses: SinkSet,
+    cmd_receiver: DynamicReceiver<'d, ReceivedTc>,
+    protection_receiver: DynamicReceiver<'d, ProtectionEvent>,
+    fuse_reset_sender: DynamicSender<'d, Sink>,
+    config_sender: DynamicSender<'d, ConfigCommand>,
+    script_sender: DynamicSender<'d, (u8, Script)>,
+    log_sender: DynamicSender<'d, LogCommand>,
     tm_sender: DynamicSender<'d, EpsTMContainer>,
 }
 
-impl<'d> ControlLoop<'d> {
-    pub fn spawn(
-        source_flip_flop: DFlipFlop<'d>,
-        sink_ctrl: SinkCtrl<'d>,
-        cmd_receiver: DynamicReceiver<'d, Telecommand>,
+impl<'d, O, I, S> ControlLoop<'d, O, I, S>
+where
+    O: OutputPin<Error = Infallible>,
+    I: InputPin<Error = Infallible>,
+    S: StatefulOutputPin<Error = Infallible>,
+{
+    /// set up the control loop and enter the mode following the boot reason
+    #[allow(clippy::too_many_arguments)]
+    pub async fn spawn(
+        boot_reason: ModeReason,
+        eps_config: EpsConfig,
+        scripts: Scripts,
+        source_flip_flop: DFlipFlop<O, I>,
+        sink_ctrl: SinkCtrl<S>,
+        cmd_receiver: DynamicReceiver<'d, ReceivedTc>,
+        protection_receiver: DynamicReceiver<'d, ProtectionEvent>,
+        fuse_reset_sender: DynamicSender<'d, Sink>,
+        config_sender: DynamicSender<'d, ConfigCommand>,
+        script_sender: DynamicSender<'d, (u8, Script)>,
+        log_sender: DynamicSender<'d, LogCommand>,
+        power_readings: PowerReadings<'d>,
         tm_sender: DynamicSender<'d, EpsTMContainer>,
     ) -> Self {
-        Self {
+        let mode = Mode::boot(boot_reason);
+        let config = mode.config();
+        let mut control_loop = Self {
             source_flip_flop,
             sink_ctrl,
+            mode,
+            last_tc: Instant::now(),
+            eps_config,
             next_tm: Instant::now(),
+            reverts: RevertScheduler::new(),
+            arming: Arming::new(),
+            tc_queue: TcQueue::new(),
+            scripts,
+            script_runner: ScriptRunner::new(),
+            commanded_source: config.source,
+            source_protection: SourceProtection::new(),
+            commanded_sinks: eps_config.mode_sinks(mode),
+            sink_shedding: SinkShedding::new(),
+            power_budget: PowerBudget::new(LOAD_SHED_TABLE),
+            power_readings,
+            open_fuses: SinkSet::EMPTY,
             cmd_receiver,
+            protection_receiver,
+            fuse_reset_sender,
+            config_sender,
+            script_sender,
+            log_sender,
             tm_sender,
+        };
+        control_loop.enter_mode(mode, boot_reason).await;
+        control_loop
+    }
+
+    fn revert_deadline(time_s: impl Into<u64>) -> Instant {
+        Instant::now() + Duration::from_secs(time_s.into())
+    }
+    /// switch to the commanded source state, or the closest one protection allows
+    async fn apply_source(&mut self) {
+        let old = self.source_flip_flop.sources();
+        let sources = self
+            .source_protection
+            .effective_sources(self.commanded_source);
+        self.source_flip_flop.set_sources(sources).await;
+        if old != sources {
+            event_log::log(Event::SourceSwitch, [old.bits(), sources.bits(), 0]);
+            burst::trigger(Trigger::SourceSwitch);
+        }
+    }
+    /// switch the commanded sinks that are neither shed nor fused off,
+    /// returns the enabled sinks before and after as bits
+    fn apply_sinks(&mut self) -> (u8, u8) {
+        let old = self.sink_ctrl.enabled();
+        let effective = self
+            .commanded_sinks
+            .difference(self.sink_shedding.active())
+            .difference(self.power_budget.shed())
+            .difference(self.open_fuses);
+        for sink in SINKS {
+            match effective.contains(sink) {
+                true => self.sink_ctrl.enable(sink),
+                false => self.sink_ctrl.disable(sink),
+            }
+        }
+        if old != effective {
+            event_log::log(Event::SinkChange, [old.bits(), effective.bits(), 0]);
+        }
+        if effective.difference(old) != SinkSet::EMPTY {
+            burst::trigger(Trigger::SinkEnable);
         }
+        (old.bits(), effective.bits())
     }
+    /// switch to the default power path of a mode, pending reverts are dropped
+    /// and a running script is aborted
+    async fn enter_mode(&mut self, mode: Mode, reason: ModeReason) {
+        let old_mode = self.mode;
+        self.mode = mode;
+        event_log::log(
+            Event::ModeChange,
+            [reason as u8, old_mode as u8, mode as u8],
+        );
+        self.reverts.clear();
+        self.arming.disarm();
+        self.abort_script(ScriptState::AbortedByModeChange).await;
+        self.commanded_source = mode.config().source;
+        self.commanded_sinks = self.eps_config.mode_sinks(mode);
+        self.apply_source().await;
+        self.apply_sinks();
 
-    async fn handle_cmd(&mut self) {
-        let Telecommand::EPS(telecommand) = self.cmd_receiver.receive().await else {
-            return;
+        let event = u32::from_le_bytes([reason as u8, old_mode as u8, mode as u8, 0]);
+        let container = EpsTMContainer::new(&tm::ModeEvent, &event).unwrap();
+        self.tm_sender.send(container).await;
+    }
+    async fn send_ack(&self, seq: u16, stage: AckStage, result: Result<(), TcFailure>) {
+        self.tm_sender.send(ack_container(seq, stage, result)).await;
+    }
+    /// critical command of `cmd` if it is configured to require arming
+    fn critical(&self, cmd: &EPSCommand) -> Option<CriticalCommand> {
+        CriticalCommand::of(cmd)
+            .filter(|critical| self.eps_config.critical_commands().contains(*critical))
+    }
+    fn requires_arming(&self, cmd: &EPSCommand) -> bool {
+        self.mode.requires_arming() && self.critical(cmd).is_some()
+    }
+    /// critical commands of the configured set need arming in some modes
+    fn check_armed(&mut self, cmd: &EPSCommand) -> Result<(), TcFailure> {
+        if !self.requires_arming(cmd) {
+            return Ok(());
+        }
+        match self
+            .critical(cmd)
+            .is_some_and(|critical| self.arming.fire(critical))
+        {
+            true => Ok(()),
+            false => Err(TcFailure::NotArmed),
+        }
+    }
+    /// execution time of a time tag relative to boot
+    fn exec_instant(&self, time_tag: TimeTag) -> Result<Instant, TcFailure> {
+        let at = match time_tag {
+            TimeTag::Boot(ms) => Instant::from_millis(ms.into()),
+            TimeTag::Mission(ms) => mission_time::instant(ms).ok_or(TcFailure::NoMissionTime)?,
+        };
+        match at < Instant::now() {
+            true => Err(TcFailure::TimeInPast),
+            false => Ok(at),
+        }
+    }
+    /// check a telecommand and queue it if it is time tagged, returns the
+    /// command to execute right away. A queued critical command keeps the
+    /// arming it was sent with, mode and arming are checked again when it is
+    /// executed as the mode may have changed in between
+    fn accept(&mut self, tc: ReceivedTc) -> Result<Option<EPSCommand>, TcFailure> {
+        let Telecommand::EPS(cmd) = tc.cmd else {
+            return Err(TcFailure::NotEps);
         };
-        match telecommand {
+        let Some(time_tag) = tc.time_tag else {
+            if !self.mode.accepts(&cmd) {
+                return Err(TcFailure::RejectedInMode);
+            }
+            self.check_armed(&cmd)?;
+            return Ok(Some(cmd));
+        };
+        let armed = self
+            .critical(&cmd)
+            .is_some_and(|critical| self.arming.fire(critical));
+        if !armed && self.requires_arming(&cmd) {
+            return Err(TcFailure::NotArmed);
+        }
+        let at = self.exec_instant(time_tag)?;
+        let seq = tc.seq;
+        self.tc_queue.push(QueuedTc {
+            seq,
+            at,
+            cmd,
+            armed,
+            mission_tagged: matches!(time_tag, TimeTag::Mission(_)),
+        })?;
+        Ok(None)
+    }
+    /// acknowledge start and completion of an accepted command
+    async fn execute(&mut self, seq: u16, cmd: EPSCommand) {
+        self.send_ack(seq, AckStage::Start, Ok(())).await;
+        let result = self.handle_cmd(cmd).await;
+        self.send_ack(seq, AckStage::Completion, result).await;
+    }
+    async fn handle_tc(&mut self, tc: ReceivedTc) {
+        self.last_tc = Instant::now();
+        let seq = tc.seq;
+        let accepted = self.accept(tc);
+        let result = accepted.as_ref().map(|_| ()).map_err(|e| *e);
+        self.send_ack(seq, AckStage::Acceptance, result).await;
+        if let Ok(Some(cmd)) = accepted {
+            self.execute(seq, cmd).await;
+        }
+    }
+    async fn handle_queue(&mut self) {
+        let now = Instant::now();
+        while let Some(queued) = self.tc_queue.pop_due(now) {
+            let result = match self.mode.accepts_queued(&queued.cmd) {
+                false => Err(TcFailure::RejectedInMode),
+                true if !queued.armed && self.requires_arming(&queued.cmd) => {
+                    Err(TcFailure::NotArmed)
+                }
+                true => Ok(()),
+            };
+            match result {
+                Ok(()) => self.execute(queued.seq, queued.cmd).await,
+                Err(_) => self.send_ack(queued.seq, AckStage::Start, result).await,
+            }
+        }
+    }
+    /// report the queued telecommands, packed as
+    /// `[seq_lo, seq_hi, exec time since boot in ms (u32 le), index, queue len]`
+    async fn send_queue(&self) {
+        let len = self.tc_queue.len() as u8;
+        for (index, queued) in self.tc_queue.iter().enumerate() {
+            let mut entry = [0; 8];
+            entry[0..2].copy_from_slice(&queued.seq.to_le_bytes());
+            entry[2..6].copy_from_slice(&(queued.at.as_millis() as u32).to_le_bytes());
+            entry[6] = index as u8;
+            entry[7] = len;
+            let entry = u64::from_le_bytes(entry);
+            let container = EpsTMContainer::new(&tm::QueuedTc, &entry).unwrap();
+            self.tm_sender.send(container).await;
+        }
+    }
+    async fn send_progress(&self, id: u8, executed: usize, state: ScriptState) {
+        let len = self.scripts[id as usize].steps().len();
+        let container = progress_container(id, executed, len, state);
+        self.tm_sender.send(container).await;
+    }
+    /// script that can be edited, the running one is locked
+    fn editable_script(&mut self, id: u8) -> Result<&mut Script, TcFailure> {
+        if self
+            .script_runner
+            .running()
+            .is_some_and(|(running, _)| running == id)
+        {
+            return Err(TcFailure::ScriptRunning);
+        }
+        self.scripts
+            .get_mut(id as usize)
+            .ok_or(TcFailure::UnknownScript)
+    }
+    async fn abort_script(&mut self, state: ScriptState) {
+        if let Some((id, step)) = self.script_runner.stop() {
+            self.send_progress(id, step, state).await;
+        }
+    }
+    /// execute the due steps of the running script
+    async fn handle_script(&mut self) {
+        let now = Instant::now();
+        while let Some((id, index)) = self.script_runner.due(now) {
+            let Some(step) = self.scripts[id as usize].steps().get(index).copied() else {
+                self.script_runner.stop();
+                self.send_progress(id, index, ScriptState::Completed).await;
+                return;
+            };
+            let result = match step.command() {
+                Some(cmd) => self.handle_cmd(cmd).await,
+                None => Ok(()),
+            };
+            if result.is_err() {
+                self.abort_script(ScriptState::StepFailed).await;
+                return;
+            }
+            self.script_runner.advance(step.delay());
+            self.send_progress(id, index + 1, ScriptState::Running)
+                .await;
+        }
+    }
+    /// execute an accepted command, commands that change nothing complete
+    /// successfully and forwarded ones complete once handed over
+    async fn handle_cmd(&mut self, cmd: EPSCommand) -> Result<(), TcFailure> {
+        match cmd {
+            EPSCommand::SetMode(id) => {
+                let mode = Mode::from_id(id).ok_or(TcFailure::UnknownMode)?;
+                if !self.mode.can_enter(mode) {
+                    return Err(TcFailure::IllegalTransition);
+                }
+                self.enter_mode(mode, ModeReason::Telecommand).await;
+            }
             EPSCommand::SetSource(state, time) => {
-                let old_state = self.source_flip_flop.get_state();
-                self.source_flip_flop.set(state).await;
+                let old_state = self.commanded_source;
+                self.commanded_source = state;
+                self.apply_source().await;
+                // taken once the switch is done, so the pending revert is not
+                // lost if switching is cancelled while the flip flop is clocked
+                let pending = self.reverts.cancel_source();
                 if let Some(time) = time {
-                    // this is blocking and prevents tc during timeout.
-                    // might be good to fix in the future
-                    Timer::after_secs(time as u64).await;
-                    self.source_flip_flop.set(old_state).await;
+                    // an override of an override still reverts to the original state
+                    let revert = pending.unwrap_or(RevertAction::SetSource(old_state));
+                    self.reverts.schedule(Self::revert_deadline(time), revert);
                 }
             }
             EPSCommand::EnableSink(sink, time) => {
-                if self.sink_ctrl.is_enabled(sink) {
-                    return;
+                let pending = self.reverts.cancel_sink(sink);
+                if pending.is_none() && self.commanded_sinks.contains(sink) {
+                    return Ok(());
                 }
-                self.sink_ctrl.enable(sink);
+                self.commanded_sinks = self.commanded_sinks.with(sink);
+                self.apply_sinks();
                 if let Some(time) = time {
-                    // this is blocking and prevents tc during timeout.
-                    // might be good to fix in the future
-                    Timer::after_secs(time as u64).await;
-                    self.sink_ctrl.disable(sink);
+                    let revert = pending.unwrap_or(RevertAction::DisableSink(sink));
+                    self.reverts.schedule(Self::revert_deadline(time), revert);
                 }
             }
             EPSCommand::DisableSink(sink, time) => {
-                if !self.sink_ctrl.is_enabled(sink) {
-                    return;
+                let pending = self.reverts.cancel_sink(sink);
+                if pending.is_none() && !self.commanded_sinks.contains(sink) {
+                    return Ok(());
                 }
-                self.sink_ctrl.disable(sink);
+                self.commanded_sinks = self.commanded_sinks.without(sink);
+                self.apply_sinks();
                 if let Some(time) = time {
-                    // this is blocking and prevents tc during timeout.
-                    // might be good to fix in the future
-                    Timer::after_secs(time as u64).await;
-                    self.sink_ctrl.enable(sink);
+                    let revert = pending.unwrap_or(RevertAction::EnableSink(sink));
+                    self.reverts.schedule(Self::revert_deadline(time), revert);
                 }
             }
+            EPSCommand::Arm(id) => {
+                let critical = CriticalCommand::from_id(id).ok_or(TcFailure::UnknownCommand)?;
+                self.arming.arm(critical, self.eps_config.arm_window());
+            }
+            EPSCommand::ListQueue => self.send_queue().await,
+            EPSCommand::DeleteQueued(seq) => self.tc_queue.delete(seq)?,
+            EPSCommand::ClearQueue => self.tc_queue.clear(),
+            EPSCommand::SetScriptStep(id, index, op, arg, param) => {
+                let step = Step::new(op, arg, param).ok_or(TcFailure::InvalidStep)?;
+                self.editable_script(id)?.set_step(index, step)?;
+            }
+            EPSCommand::ClearScript(id) => self.editable_script(id)?.clear(),
+            EPSCommand::StoreScript(id) => {
+                let script = self
+                    .scripts
+                    .get(id as usize)
+                    .ok_or(TcFailure::UnknownScript)?;
+                self.script_sender.send((id, script.clone())).await
+            }
+            EPSCommand::RunScript(id) => {
+                if self.script_runner.running().is_some() {
+                    return Err(TcFailure::ScriptRunning);
+                }
+                if id as usize >= self.scripts.len() {
+                    return Err(TcFailure::UnknownScript);
+                }
+                self.script_runner.start(id);
+                self.send_progress(id, 0, ScriptState::Running).await;
+            }
+            EPSCommand::AbortScript => self.abort_script(ScriptState::AbortedByTelecommand).await,
+            EPSCommand::DumpEventLog(chunk) => self.log_sender.send(LogCommand::Dump(chunk)).await,
+            EPSCommand::ClearEventLog => self.log_sender.send(LogCommand::Clear).await,
+            EPSCommand::ArmBurst(channels, period_100us, pre_trigger_percent, triggers) => {
+                burst::arm(BurstConfig {
+                    channels,
+                    period_100us,
+                    pre_trigger_percent,
+                    triggers: TriggerSet::from_bits(triggers),
+                })?
+            }
+            EPSCommand::SetBurstThreshold(channel, level, edge) => {
+                let edge = Edge::from_id(edge).ok_or(TcFailure::InvalidBurst)?;
+                burst::set_threshold(Threshold {
+                    channel,
+                    level,
+                    edge,
+                })?
+            }
+            EPSCommand::TriggerBurst => {
+                if !burst::trigger(Trigger::Command) {
+                    return Err(TcFailure::BurstNotArmed);
+                }
+            }
+            EPSCommand::DisarmBurst => burst::disarm(),
+            EPSCommand::ResetFuse(sink) => self.fuse_reset_sender.send(sink).await,
+            EPSCommand::GetParam(id) => self.config_sender.send(ConfigCommand::Get(id)).await,
+            EPSCommand::SetParam(id, value) => {
+                self.config_sender.send(ConfigCommand::Set(id, value)).await
+            }
+            EPSCommand::ResetParam(id) => self.config_sender.send(ConfigCommand::Reset(id)).await,
+            EPSCommand::CommitConfig => self.config_sender.send(ConfigCommand::Commit).await,
+            EPSCommand::SetLoadShedEntry(
+                sink,
+                priority,
+                min_bus_voltage_10mv,
+                min_soc_permille,
+            ) => {
+                let entry = ShedEntry {
+                    priority,
+                    min_bus_voltage_10mv,
+                    min_soc_permille,
+                };
+                self.power_budget.set_entry(sink, entry);
+            }
+        }
+        Ok(())
+    }
+    async fn handle_reverts(&mut self) {
+        let now = Instant::now();
+        while let Some(action) = self.reverts.pop_due(now) {
+            match action {
+                RevertAction::SetSource(state) => {
+                    self.commanded_source = state;
+                    self.apply_source().await
+                }
+                RevertAction::EnableSink(sink) => {
+                    self.commanded_sinks = self.commanded_sinks.with(sink);
+                    self.apply_sinks();
+                }
+                RevertAction::DisableSink(sink) => {
+                    self.commanded_sinks = self.commanded_sinks.without(sink);
+                    self.apply_sinks();
+                }
+            }
+        }
+    }
+    /// report an autonomous change from `old` to `new`, `subject` is the
+    /// source input, sink index or set of budget shed sinks causing it
+    async fn send_event(
+        &self,
+        topic: &'static dyn TelemetryDefinition,
+        reason: SwitchReason,
+        subject: u8,
+        old: u8,
+        new: u8,
+    ) {
+        let event = u32::from_le_bytes([reason as u8, subject, old, new]);
+        let container = EpsTMContainer::new(topic, &event).unwrap();
+        self.tm_sender.send(container).await;
+    }
+    async fn handle_fuse(&mut self, sink: Sink, status: FuseStatus, reason: SwitchReason) {
+        self.open_fuses = match status {
+            FuseStatus::Closed => self.open_fuses.without(sink),
+            FuseStatus::Tripped | FuseStatus::Latched => self.open_fuses.with(sink),
+        };
+        let (old_sinks, new_sinks) = self.apply_sinks();
+        // fuse events are always reported, even if the sink is commanded off
+        let subject = sink_index(sink) as u8;
+        self.send_event(&tm::SinkFuseEvent, reason, subject, old_sinks, new_sinks)
+            .await;
+    }
+    async fn handle_protection(&mut self, event: ProtectionEvent) {
+        let reason = event.reason();
+        let subject = match event {
+            ProtectionEvent::SinkFuse(sink, _) => sink_index(sink) as u8,
+            ProtectionEvent::BatteryVoltage(input, _)
+            | ProtectionEvent::BatteryTemperature(input, _, _) => input as u8,
+            ProtectionEvent::AuxVoltage(_) => FlipFlopInput::AuxPwr as u8,
+        };
+        event_log::log(Event::Protection, [reason as u8, subject, 0]);
+        if event.is_fault() {
+            self.abort_script(ScriptState::AbortedByProtection).await;
+        }
+        let input = match event {
+            ProtectionEvent::SinkFuse(sink, status) => {
+                return self.handle_fuse(sink, status, reason).await;
+            }
+            ProtectionEvent::BatteryVoltage(input, _)
+            | ProtectionEvent::BatteryTemperature(input, _, _) => input,
+            ProtectionEvent::AuxVoltage(_) => FlipFlopInput::AuxPwr,
+        };
+        self.source_protection.update(event);
+
+        if let ProtectionEvent::BatteryTemperature(_, status, response) = event {
+            match (status, response) {
+                (_, FaultResponse::ShedSinks(sinks)) => self.sink_shedding.shed(input, sinks),
+                // sinks shed by a limit fault stay off while the probe is failed
+                (TemperatureStatus::ProbeFailure, _) => (),
+                _ => self.sink_shedding.shed(input, SinkSet::EMPTY),
+            }
+            let (old_sinks, new_sinks) = self.apply_sinks();
+            if old_sinks != new_sinks {
+                self.send_event(
+                    &tm::LoadShedEvent,
+                    reason,
+                    input as u8,
+                    old_sinks,
+                    new_sinks,
+                )
+                .await;
+            }
+        }
+
+        let old_sources = self.source_flip_flop.sources();
+        self.apply_source().await;
+        let new_sources = self.source_flip_flop.sources();
+        if old_sources != new_sources {
+            self.send_event(
+                &tm::SourceSwitchEvent,
+                reason,
+                input as u8,
+                old_sources.bits(),
+                new_sources.bits(),
+            )
+            .await;
+        }
+
+        // flying on aux power alone means the batteries are gone
+        let in_flight = !matches!(self.mode, Mode::Standby | Mode::Safe);
+        if in_flight && !self.source_protection.any_battery_ok() {
+            self.enter_mode(Mode::Safe, ModeReason::LowVoltage).await;
+        }
+    }
+    async fn check_comm_loss(&mut self) {
+        let Some(timeout) = self.eps_config.comm_loss_timeout(self.mode) else {
+            return;
+        };
+        if Instant::now() - self.last_tc >= timeout {
+            self.enter_mode(Mode::Safe, ModeReason::CommLoss).await;
         }
     }
+    /// shed or restore sinks according to the latest bus voltage and battery charge
+    async fn update_power_budget(&mut self) {
+        let Some(bus_voltage) = self.power_readings.bus_voltage.try_get() else {
+            return;
+        };
+        let soc = [FlipFlopInput::Bat1, FlipFlopInput::Bat2]
+            .into_iter()
+            .zip(&mut self.power_readings.soc)
+            .filter(|(input, _)| self.source_flip_flop.is_enabled(*input))
+            .filter_map(|(_, soc)| soc.try_get())
+            .min();
+        let old_shed = self.power_budget.shed();
+        self.power_budget.update(bus_voltage, soc);
+        let new_shed = self.power_budget.shed();
+        if old_shed == new_shed {
+            return;
+        }
+        let reason = match new_shed.difference(old_shed) {
+            SinkSet::EMPTY => SwitchReason::PowerRecovered,
+            _ => SwitchReason::PowerDegraded,
+        };
+        let (old_sinks, new_sinks) = self.apply_sinks();
+        self.send_event(
+            &tm::LoadShedEvent,
+            reason,
+            new_shed.bits(),
+            old_sinks,
+            new_sinks,
+        )
+        .await;
+    }
     async fn send_state(&mut self) {
         Timer::at(self.next_tm).await;
 
@@ -123,14 +680,49 @@ impl<'d> ControlLoop<'d> {
 
         let container = EpsTMContainer::new(&tm::EnableBitmap, &bitmap.bits()).unwrap();
         self.tm_sender.send(container).await;
+        let container = EpsTMContainer::new(&tm::Mode, &(self.mode as u8)).unwrap();
+        self.tm_sender.send(container).await;
+
+        // armed command and its remaining window in seconds, 0xFF if none is armed
+        let (armed, remaining_s) = self.arming.armed().map_or((u8::MAX, 0), |(cmd, left)| {
+            (cmd as u8, left.as_secs().min(u8::MAX as u64) as u8)
+        });
+        let critical = self.eps_config.critical_commands().bits();
+        let arming = u32::from_le_bytes([armed, remaining_s, critical, 0]);
+        let container = EpsTMContainer::new(&tm::Arming, &arming).unwrap();
+        self.tm_sender.send(container).await;
+        let queue_len = self.tc_queue.len() as u8;
+        let container = EpsTMContainer::new(&tm::TcQueueLen, &queue_len).unwrap();
+        self.tm_sender.send(container).await;
     }
     pub async fn run(&mut self) {
-        if let Either::First(_) = select(
+        match select4(
             Timer::at(self.next_tm),
-            self.handle_cmd()
-        ).await {
-            self.send_state().await;
-            self.next_tm += CTRL_LOOP_TM_INTERVAL;
+            Timer::at(
+                self.reverts
+                    .next_deadline()
+                    .min(self.tc_queue.next_deadline())
+                    .min(self.script_runner.next_deadline()),
+            ),
+            self.cmd_receiver.receive(),
+            self.protection_receiver.receive(),
+        )
+        .await
+        {
+            Either4::First(_) => {
+                self.check_comm_loss().await;
+                self.update_power_budget().await;
+                self.send_state().await;
+                self.next_tm += self.eps_config.tm_interval();
+            }
+            Either4::Second(_) => {
+                self.handle_reverts().await;
+                self.handle_queue().await;
+                self.handle_script().await;
+            }
+            Either4::Third(tc) => self.handle_tc(tc).await,
+            Either4::Fourth(event) => self.handle_protection(event).await,
         }
+        mission_time::hold_jumps(self.tc_queue.has_mission_tagged());
     }
 }
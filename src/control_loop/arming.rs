@@ -0,0 +1,104 @@
+use defmt::Format;
+use embassy_time::{Duration, Instant};
+use south_common::types::{EPSCommand, Sink};
+
+/// Command that can be configured to require arming before it is executed
+#[repr(u8)]
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum CriticalCommand {
+    SetSource,
+    DisableRocketLST,
+    DisableSensorUpper,
+    DisableGPS,
+    DisableRocketHD,
+    SetMode,
+    ResetFuse,
+    CommitConfig,
+}
+
+impl CriticalCommand {
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::SetSource),
+            1 => Some(Self::DisableRocketLST),
+            2 => Some(Self::DisableSensorUpper),
+            3 => Some(Self::DisableGPS),
+            4 => Some(Self::DisableRocketHD),
+            5 => Some(Self::SetMode),
+            6 => Some(Self::ResetFuse),
+            7 => Some(Self::CommitConfig),
+            _ => None,
+        }
+    }
+    /// kind of a telecommand, `None` if it can never require arming
+    pub const fn of(cmd: &EPSCommand) -> Option<Self> {
+        match cmd {
+            EPSCommand::SetSource(..) => Some(Self::SetSource),
+            EPSCommand::DisableSink(sink, _) => Some(match sink {
+                Sink::RocketLST => Self::DisableRocketLST,
+                Sink::SensorUpper => Self::DisableSensorUpper,
+                Sink::GPS => Self::DisableGPS,
+                Sink::RocketHD => Self::DisableRocketHD,
+            }),
+            EPSCommand::SetMode(_) => Some(Self::SetMode),
+            EPSCommand::ResetFuse(_) => Some(Self::ResetFuse),
+            EPSCommand::CommitConfig => Some(Self::CommitConfig),
+            _ => None,
+        }
+    }
+}
+
+/// Set of critical commands, stored as bitmask over the [`CriticalCommand`] ids
+#[derive(Format, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CriticalSet(u8);
+
+impl CriticalSet {
+    pub const EMPTY: Self = Self(0);
+    pub const ALL: Self = Self(u8::MAX);
+    /// switching the batteries and cutting the flight computer
+    pub const DEFAULT: Self = Self::EMPTY
+        .with(CriticalCommand::SetSource)
+        .with(CriticalCommand::DisableRocketHD);
+
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+    pub const fn with(self, cmd: CriticalCommand) -> Self {
+        Self(self.0 | 1 << cmd as u8)
+    }
+    pub const fn contains(self, cmd: CriticalCommand) -> bool {
+        self.0 & 1 << cmd as u8 != 0
+    }
+}
+
+/// Two step confirmation of critical commands. An arm command opens a window in
+/// which the armed command is executed once, any other critical command disarms
+#[derive(Default)]
+pub struct Arming {
+    armed: Option<(CriticalCommand, Instant)>,
+}
+
+impl Arming {
+    pub const fn new() -> Self {
+        Self { armed: None }
+    }
+    pub fn arm(&mut self, cmd: CriticalCommand, window: Duration) {
+        self.armed = Some((cmd, Instant::now() + window));
+    }
+    pub fn disarm(&mut self) {
+        self.armed = None;
+    }
+    /// consume the arming, true if `cmd` was armed and its window is still open
+    pub fn fire(&mut self, cmd: CriticalCommand) -> bool {
+        matches!(self.armed.take(), Some((armed, until)) if armed == cmd && Instant::now() <= until)
+    }
+    /// armed command and the time left to execute it
+    pub fn armed(&self) -> Option<(CriticalCommand, Duration)> {
+        let (cmd, until) = self.armed?;
+        let now = Instant::now();
+        (now <= until).then(|| (cmd, until - now))
+    }
+}
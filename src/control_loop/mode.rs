@@ -0,0 +1,128 @@
+use defmt::Format;
+use embassy_time::Duration;
+use south_common::types::{EPSCommand, FlipFlopState, Sink};
+
+use crate::pwr_src::sink_ctrl::SinkSet;
+
+/// Operating mode of the EPS over the mission
+#[repr(u8)]
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// on the pad, everything powered for checkout
+    Standby,
+    /// power path locked against telecommands
+    Launch,
+    Flight,
+    /// after landing, only what is needed to be found
+    Recovery,
+    /// minimal power after a fault, only left by telecommand
+    Safe,
+}
+
+/// Cause of a mode change, reported in event telemetry
+#[repr(u8)]
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum ModeReason {
+    PowerOn,
+    WatchdogReset,
+    Telecommand,
+    /// no battery left in the power path
+    LowVoltage,
+    /// too many resets without a stable uptime in between
+    RepeatedResets,
+    /// no telecommand received for the comm loss timeout of the mode
+    CommLoss,
+}
+
+/// Power path configured when entering a mode
+pub struct ModeConfig {
+    pub source: FlipFlopState,
+    pub sinks: SinkSet,
+    /// time without telecommands after which safe mode is entered
+    pub comm_loss_timeout: Option<Duration>,
+}
+
+impl Mode {
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::Standby),
+            1 => Some(Self::Launch),
+            2 => Some(Self::Flight),
+            3 => Some(Self::Recovery),
+            4 => Some(Self::Safe),
+            _ => None,
+        }
+    }
+    /// mode entered after a reset
+    pub const fn boot(reason: ModeReason) -> Self {
+        match reason {
+            ModeReason::WatchdogReset | ModeReason::RepeatedResets => Self::Safe,
+            _ => Self::Standby,
+        }
+    }
+    pub const fn config(self) -> ModeConfig {
+        let sinks = match self {
+            Self::Standby | Self::Launch | Self::Flight => SinkSet::ALL,
+            Self::Recovery => SinkSet::EMPTY.with(Sink::RocketLST).with(Sink::GPS),
+            Self::Safe => SinkSet::EMPTY.with(Sink::RocketLST),
+        };
+        // the rocket does not talk to the EPS while launching and flying. After
+        // landing safe mode would turn off the GPS needed to find it
+        let comm_loss_timeout = match self {
+            Self::Standby => Some(Duration::from_secs(10 * 60)),
+            Self::Launch | Self::Flight | Self::Recovery | Self::Safe => None,
+        };
+        ModeConfig {
+            source: FlipFlopState::On,
+            sinks,
+            comm_loss_timeout,
+        }
+    }
+    /// mode changes allowed by telecommand, safe mode can be entered from anywhere
+    pub const fn can_enter(self, next: Self) -> bool {
+        matches!(
+            (self, next),
+            (_, Self::Safe)
+                | (Self::Standby, Self::Launch)
+                | (Self::Launch, Self::Flight)
+                | (Self::Flight, Self::Recovery)
+                | (Self::Recovery | Self::Safe, Self::Standby)
+        )
+    }
+    /// commands accepted in this mode. Script steps are not armed, so scripts
+    /// only run in standby and recovery
+    pub const fn accepts(self, cmd: &EPSCommand) -> bool {
+        !matches!(
+            (self, cmd),
+            (
+                Self::Launch,
+                EPSCommand::SetSource(..)
+                    | EPSCommand::EnableSink(..)
+                    | EPSCommand::DisableSink(..)
+            ) | (
+                Self::Safe,
+                EPSCommand::SetSource(..) | EPSCommand::EnableSink(..)
+            ) | (
+                Self::Launch | Self::Flight | Self::Safe,
+                EPSCommand::RunScript(_)
+            )
+        )
+    }
+    /// time tagged commands executed in this mode. The launch timeline is
+    /// uploaded ahead, so queued power path commands still run in launch
+    pub const fn accepts_queued(self, cmd: &EPSCommand) -> bool {
+        match (self, cmd) {
+            (
+                Self::Launch,
+                EPSCommand::SetSource(..)
+                | EPSCommand::EnableSink(..)
+                | EPSCommand::DisableSink(..),
+            ) => true,
+            _ => self.accepts(cmd),
+        }
+    }
+    /// critical commands have to be armed before they are executed
+    pub const fn requires_arming(self) -> bool {
+        matches!(self, Self::Launch | Self::Flight)
+    }
+}
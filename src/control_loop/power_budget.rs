@@ -0,0 +1,96 @@
+use crate::pwr_src::sink_ctrl::{SINKS, SinkSet, sink_index};
+use south_common::types::Sink;
+
+/// Load shedding configuration of a sink
+#[derive(Clone, Copy)]
+pub struct ShedEntry {
+    /// sinks with lower priority are shed first
+    pub priority: u8,
+    pub min_bus_voltage_10mv: i16,
+    /// only checked while a battery is in the power path
+    pub min_soc_permille: u16,
+}
+
+/// default table in [`SINKS`] order. RocketHD carries the flight data of the
+/// rocket, it has the lowest thresholds and is shed last
+pub const LOAD_SHED_TABLE: [ShedEntry; SINKS.len()] = [
+    // RocketLST
+    ShedEntry {
+        priority: 2,
+        min_bus_voltage_10mv: 6_20,
+        min_soc_permille: 50,
+    },
+    // SensorUpper
+    ShedEntry {
+        priority: 0,
+        min_bus_voltage_10mv: 6_80,
+        min_soc_permille: 300,
+    },
+    // GPS
+    ShedEntry {
+        priority: 1,
+        min_bus_voltage_10mv: 6_60,
+        min_soc_permille: 200,
+    },
+    // RocketHD
+    ShedEntry {
+        priority: 3,
+        min_bus_voltage_10mv: 6_00,
+        min_soc_permille: 30,
+    },
+];
+
+// distance above the thresholds needed to restore a sink
+const HYSTERESIS_10MV: i16 = 20;
+const HYSTERESIS_PERMILLE: u16 = 50;
+
+/// Sinks disabled in priority order as the bus voltage or battery charge degrades
+pub struct PowerBudget {
+    table: [ShedEntry; SINKS.len()],
+    // sinks whose own thresholds are violated
+    degraded: SinkSet,
+}
+
+impl PowerBudget {
+    pub const fn new(table: [ShedEntry; SINKS.len()]) -> Self {
+        Self {
+            table,
+            degraded: SinkSet::EMPTY,
+        }
+    }
+    pub fn set_entry(&mut self, sink: Sink, entry: ShedEntry) {
+        self.table[sink_index(sink)] = entry;
+    }
+    /// reevaluate the thresholds, `soc_permille` is the lowest charge of the
+    /// batteries in the power path
+    pub fn update(&mut self, bus_voltage_10mv: i16, soc_permille: Option<u16>) {
+        for sink in SINKS {
+            let entry = &self.table[sink_index(sink)];
+            let below = bus_voltage_10mv < entry.min_bus_voltage_10mv
+                || soc_permille.is_some_and(|soc| soc < entry.min_soc_permille);
+            let recovered = bus_voltage_10mv >= entry.min_bus_voltage_10mv + HYSTERESIS_10MV
+                && soc_permille
+                    .is_none_or(|soc| soc >= entry.min_soc_permille + HYSTERESIS_PERMILLE);
+            if below {
+                self.degraded = self.degraded.with(sink);
+            } else if recovered {
+                self.degraded = self.degraded.without(sink);
+            }
+        }
+    }
+    /// degraded sinks together with all sinks of lower or equal priority
+    pub fn shed(&self) -> SinkSet {
+        let Some(level) = self
+            .degraded
+            .iter()
+            .map(|sink| self.table[sink_index(sink)].priority)
+            .max()
+        else {
+            return SinkSet::EMPTY;
+        };
+        SINKS
+            .into_iter()
+            .filter(|sink| self.table[sink_index(*sink)].priority <= level)
+            .fold(SinkSet::EMPTY, SinkSet::with)
+    }
+}
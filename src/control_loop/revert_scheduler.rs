@@ -0,0 +1,134 @@
+use embassy_time::Instant;
+use south_common::types::{FlipFlopState, Sink};
+
+use crate::pwr_src::sink_ctrl::{SINKS, sink_index};
+
+/// Action executed once a timed override runs out
+#[derive(Clone, Copy)]
+pub enum RevertAction {
+    SetSource(FlipFlopState),
+    EnableSink(Sink),
+    DisableSink(Sink),
+}
+impl RevertAction {
+    fn slot(&self) -> usize {
+        match self {
+            Self::SetSource(_) => 0,
+            Self::EnableSink(sink) | Self::DisableSink(sink) => sink_slot(*sink),
+        }
+    }
+}
+
+/// Revert slot of a sink, slot 0 is reserved for the source flip flop
+fn sink_slot(sink: Sink) -> usize {
+    sink_index(sink) + 1
+}
+
+const SLOTS: usize = SINKS.len() + 1;
+
+/// Pending reverts of timed overrides, one slot per sink and one for the flip flop.
+/// Scheduling into an occupied slot replaces the pending revert.
+pub struct RevertScheduler {
+    slots: [Option<(Instant, RevertAction)>; SLOTS],
+}
+
+impl RevertScheduler {
+    pub const fn new() -> Self {
+        Self {
+            slots: [None; SLOTS],
+        }
+    }
+    pub fn schedule(&mut self, at: Instant, action: RevertAction) {
+        self.slots[action.slot()] = Some((at, action));
+    }
+    /// cancel the pending source revert and return its action
+    pub fn cancel_source(&mut self) -> Option<RevertAction> {
+        self.slots[0].take().map(|(_, action)| action)
+    }
+    /// cancel the pending revert of a sink and return its action
+    pub fn cancel_sink(&mut self, sink: Sink) -> Option<RevertAction> {
+        self.slots[sink_slot(sink)].take().map(|(_, action)| action)
+    }
+    /// drop all pending reverts
+    pub fn clear(&mut self) {
+        self.slots = [None; SLOTS];
+    }
+    /// earliest pending deadline, `Instant::MAX` if nothing is pending
+    pub fn next_deadline(&self) -> Instant {
+        self.slots
+            .iter()
+            .flatten()
+            .map(|(at, _)| *at)
+            .min()
+            .unwrap_or(Instant::MAX)
+    }
+    /// remove and return one revert that is due at `now`
+    pub fn pop_due(&mut self, now: Instant) -> Option<RevertAction> {
+        self.slots
+            .iter_mut()
+            .find(|slot| matches!(slot, Some((at, _)) if *at <= now))?
+            .take()
+            .map(|(_, action)| action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reverts_of_different_slots_overlap() {
+        let mut reverts = RevertScheduler::new();
+        assert!(reverts.next_deadline() == Instant::MAX);
+        reverts.schedule(
+            Instant::from_secs(2),
+            RevertAction::EnableSink(Sink::RocketHD),
+        );
+        reverts.schedule(Instant::from_secs(1), RevertAction::EnableSink(Sink::GPS));
+        assert!(reverts.next_deadline() == Instant::from_secs(1));
+
+        assert!(reverts.pop_due(Instant::from_millis(500)).is_none());
+        let due = reverts.pop_due(Instant::from_secs(1));
+        assert!(matches!(due, Some(RevertAction::EnableSink(Sink::GPS))));
+        assert!(reverts.pop_due(Instant::from_secs(1)).is_none());
+        assert!(reverts.next_deadline() == Instant::from_secs(2));
+        let due = reverts.pop_due(Instant::from_secs(3));
+        assert!(matches!(
+            due,
+            Some(RevertAction::EnableSink(Sink::RocketHD))
+        ));
+        assert!(reverts.next_deadline() == Instant::MAX);
+    }
+
+    #[test]
+    fn scheduling_replaces_the_pending_revert_of_a_slot() {
+        let mut reverts = RevertScheduler::new();
+        reverts.schedule(Instant::from_secs(1), RevertAction::EnableSink(Sink::GPS));
+        reverts.schedule(Instant::from_secs(5), RevertAction::DisableSink(Sink::GPS));
+        assert!(reverts.next_deadline() == Instant::from_secs(5));
+        let due = reverts.pop_due(Instant::from_secs(5));
+        assert!(matches!(due, Some(RevertAction::DisableSink(Sink::GPS))));
+    }
+
+    #[test]
+    fn cancelled_reverts_are_returned_and_dropped() {
+        let mut reverts = RevertScheduler::new();
+        reverts.schedule(
+            Instant::from_secs(1),
+            RevertAction::SetSource(FlipFlopState::On),
+        );
+        reverts.schedule(Instant::from_secs(1), RevertAction::EnableSink(Sink::GPS));
+        assert!(reverts.cancel_sink(Sink::RocketHD).is_none());
+        let cancelled = reverts.cancel_source();
+        assert!(matches!(
+            cancelled,
+            Some(RevertAction::SetSource(FlipFlopState::On))
+        ));
+        let cancelled = reverts.cancel_sink(Sink::GPS);
+        assert!(matches!(
+            cancelled,
+            Some(RevertAction::EnableSink(Sink::GPS))
+        ));
+        assert!(reverts.next_deadline() == Instant::MAX);
+    }
+}
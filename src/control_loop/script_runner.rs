@@ -0,0 +1,50 @@
+use embassy_time::{Duration, Instant};
+
+struct Run {
+    id: u8,
+    // index of the next step
+    step: usize,
+    at: Instant,
+}
+
+/// Position of the running script, at most one script runs at a time
+pub struct ScriptRunner {
+    run: Option<Run>,
+}
+
+impl ScriptRunner {
+    pub const fn new() -> Self {
+        Self { run: None }
+    }
+    pub fn start(&mut self, id: u8) {
+        let at = Instant::now();
+        self.run = Some(Run { id, step: 0, at });
+    }
+    /// id and next step of the running script
+    pub fn running(&self) -> Option<(u8, usize)> {
+        self.run.as_ref().map(|run| (run.id, run.step))
+    }
+    /// stop the running script and return its id and next step
+    pub fn stop(&mut self) -> Option<(u8, usize)> {
+        self.run.take().map(|run| (run.id, run.step))
+    }
+    /// execution time of the next step, `Instant::MAX` if no script is running
+    pub fn next_deadline(&self) -> Instant {
+        self.run.as_ref().map_or(Instant::MAX, |run| run.at)
+    }
+    /// id and next step of the running script if the step is due at `now`
+    pub fn due(&self, now: Instant) -> Option<(u8, usize)> {
+        self.run
+            .as_ref()
+            .filter(|run| run.at <= now)
+            .map(|run| (run.id, run.step))
+    }
+    /// continue with the next step after `delay`, counted from the current step
+    /// so waits do not add up the control loop latency
+    pub fn advance(&mut self, delay: Duration) {
+        if let Some(run) = &mut self.run {
+            run.step += 1;
+            run.at += delay;
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use crate::pwr_src::{d_flip_flop::FlipFlopInput, sink_ctrl::SinkSet};
+
+/// Sinks disabled because of battery faults.
+/// Commanded sinks are switched on again once no battery sheds them anymore
+pub struct SinkShedding {
+    shed: [SinkSet; 2],
+}
+
+impl SinkShedding {
+    pub const fn new() -> Self {
+        Self {
+            shed: [SinkSet::EMPTY; 2],
+        }
+    }
+    /// replace the sinks shed for a battery
+    pub fn shed(&mut self, input: FlipFlopInput, sinks: SinkSet) {
+        match input {
+            FlipFlopInput::Bat1 => self.shed[0] = sinks,
+            FlipFlopInput::Bat2 => self.shed[1] = sinks,
+            FlipFlopInput::AuxPwr => (),
+        }
+    }
+    /// sinks shed by any battery
+    pub fn active(&self) -> SinkSet {
+        self.shed[0].union(self.shed[1])
+    }
+}
@@ -0,0 +1,124 @@
+use south_common::types::FlipFlopState;
+
+use crate::{
+    protection::{
+        ProtectionEvent,
+        temperature::{FaultResponse, TemperatureStatus},
+        voltage::VoltageStatus,
+    },
+    pwr_src::d_flip_flop::{FlipFlopInput, SourceSet},
+};
+
+#[derive(Clone, Copy)]
+struct BatteryFaults {
+    voltage: bool,
+    temperature: bool,
+}
+impl BatteryFaults {
+    const fn ok(&self) -> bool {
+        !self.voltage && !self.temperature
+    }
+    /// how bad it is to keep the battery, a hot or cold cell is worse than an
+    /// empty or full one
+    const fn severity(&self) -> u8 {
+        (self.temperature as u8) << 1 | self.voltage as u8
+    }
+}
+
+/// Health of the batteries and aux power as reported by the protection events.
+/// Maps the commanded source state onto the sources that are safe to use
+pub struct SourceProtection {
+    bat_1: BatteryFaults,
+    bat_2: BatteryFaults,
+    // aux power is absent in flight, it is only used once measured
+    aux_pwr_ok: bool,
+}
+
+impl SourceProtection {
+    pub const fn new() -> Self {
+        let no_faults = BatteryFaults {
+            voltage: false,
+            temperature: false,
+        };
+        Self {
+            bat_1: no_faults,
+            bat_2: no_faults,
+            aux_pwr_ok: false,
+        }
+    }
+    fn faults(&self, battery: FlipFlopInput) -> BatteryFaults {
+        match battery {
+            FlipFlopInput::Bat1 => self.bat_1,
+            _ => self.bat_2,
+        }
+    }
+    pub fn update(&mut self, event: ProtectionEvent) {
+        let faults = match event {
+            ProtectionEvent::AuxVoltage(status) => {
+                self.aux_pwr_ok = status == VoltageStatus::Nominal;
+                return;
+            }
+            _ => match event.input() {
+                Some(FlipFlopInput::Bat1) => &mut self.bat_1,
+                Some(FlipFlopInput::Bat2) => &mut self.bat_2,
+                Some(FlipFlopInput::AuxPwr) | None => return,
+            },
+        };
+        match event {
+            ProtectionEvent::BatteryVoltage(_, status) => {
+                faults.voltage = status != VoltageStatus::Nominal
+            }
+            // a failed probe can't tell that the limit fault is gone, it stays
+            // latched until the probe reads nominal again
+            ProtectionEvent::BatteryTemperature(_, TemperatureStatus::ProbeFailure, response) => {
+                faults.temperature |= response == FaultResponse::Disconnect
+            }
+            ProtectionEvent::BatteryTemperature(_, _, response) => {
+                faults.temperature = response == FaultResponse::Disconnect
+            }
+            ProtectionEvent::SinkFuse(_, _) | ProtectionEvent::AuxVoltage(_) => {}
+        }
+    }
+    pub const fn any_battery_ok(&self) -> bool {
+        self.bat_1.ok() || self.bat_2.ok()
+    }
+    /// commanded sources with unhealthy batteries dropped from the power path.
+    /// Without a commanded battery left it falls back to the other battery, to
+    /// aux power if it is within its limits and else keeps the least faulted
+    /// battery, so the power path is never cut
+    pub fn effective_sources(&self, commanded: FlipFlopState) -> SourceSet {
+        let batteries = [FlipFlopInput::Bat1, FlipFlopInput::Bat2];
+        let sources = batteries
+            .into_iter()
+            .filter(|battery| !self.faults(*battery).ok())
+            .fold(SourceSet::of(commanded), SourceSet::without);
+        if commanded == FlipFlopState::AuxPwr
+            || sources.intersection(SourceSet::BATTERIES) != SourceSet::EMPTY
+        {
+            return sources;
+        }
+        if let Some(battery) = batteries
+            .into_iter()
+            .find(|battery| self.faults(*battery).ok())
+        {
+            return SourceSet::EMPTY.with(battery);
+        }
+        if self.aux_pwr_ok {
+            return SourceSet::of(FlipFlopState::AuxPwr);
+        }
+        // the commanded battery wins a tie
+        let preferred = match commanded {
+            FlipFlopState::Bat2 => FlipFlopInput::Bat2,
+            _ => FlipFlopInput::Bat1,
+        };
+        let other = match preferred {
+            FlipFlopInput::Bat1 => FlipFlopInput::Bat2,
+            _ => FlipFlopInput::Bat1,
+        };
+        let battery = match self.faults(other).severity() < self.faults(preferred).severity() {
+            true => other,
+            false => preferred,
+        };
+        SourceSet::EMPTY.with(battery)
+    }
+}
@@ -0,0 +1,79 @@
+use embassy_time::Instant;
+use heapless::Vec;
+use south_common::types::EPSCommand;
+
+use crate::telecommand::TcFailure;
+
+pub const TC_QUEUE_LEN: usize = 16;
+
+/// Time tagged telecommand waiting for its execution time
+pub struct QueuedTc {
+    /// reception sequence number, used in the acknowledgements and to delete it
+    pub seq: u16,
+    pub at: Instant,
+    pub cmd: EPSCommand,
+    /// the command was armed when it was queued
+    pub armed: bool,
+    /// the execution time was given in mission time
+    pub mission_tagged: bool,
+}
+
+/// Bounded queue of time tagged telecommands ordered by execution time.
+/// Telecommands due at the same time keep their order of reception
+pub struct TcQueue {
+    entries: Vec<QueuedTc, TC_QUEUE_LEN>,
+}
+
+impl TcQueue {
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+    pub fn push(&mut self, entry: QueuedTc) -> Result<(), TcFailure> {
+        let index = self
+            .entries
+            .iter()
+            .position(|queued| queued.at > entry.at)
+            .unwrap_or(self.entries.len());
+        self.entries
+            .insert(index, entry)
+            .map_err(|_| TcFailure::QueueFull)
+    }
+    pub fn delete(&mut self, seq: u16) -> Result<(), TcFailure> {
+        let index = self
+            .entries
+            .iter()
+            .position(|queued| queued.seq == seq)
+            .ok_or(TcFailure::NotQueued)?;
+        self.entries.remove(index);
+        Ok(())
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    /// true if a queued telecommand was tagged with mission time
+    pub fn has_mission_tagged(&self) -> bool {
+        self.entries.iter().any(|queued| queued.mission_tagged)
+    }
+    /// queued telecommands in execution order
+    pub fn iter(&self) -> impl Iterator<Item = &QueuedTc> {
+        self.entries.iter()
+    }
+    /// execution time of the next telecommand, `Instant::MAX` if the queue is empty
+    pub fn next_deadline(&self) -> Instant {
+        self.entries
+            .first()
+            .map_or(Instant::MAX, |queued| queued.at)
+    }
+    /// remove and return the next telecommand if it is due at `now`
+    pub fn pop_due(&mut self, now: Instant) -> Option<QueuedTc> {
+        match self.entries.first() {
+            Some(queued) if queued.at <= now => Some(self.entries.remove(0)),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! Onboard log of events worth knowing about after the fact, e.g. power path
+//! changes, protection trips, resets and bus errors. Events are stamped with the
+//! mission time and kept in a RAM ring buffer until the event log service
+//! mirrors them to flash, from where they are downloaded in chunks via
+//! telecommand. Entries are packed in 8 bytes as
+//! `[time (u32 le), time reference << 7 | event, parameters (3 bytes)]`
+pub mod store;
+
+use core::cell::RefCell;
+
+use defmt::{Format, warn};
+use embassy_futures::select::{Either, select};
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    channel::{DynamicReceiver, DynamicSender},
+    signal::Signal,
+};
+use embassy_time::{Duration, Instant};
+use embedded_storage::nor_flash::NorFlash;
+use heapless::Deque;
+use south_common::telemetry::eps as tm;
+
+#[cfg(feature = "stm32")]
+use crate::shared_flash::BoardFlash;
+use crate::{EpsTMContainer, mission_time, telecommand::TimeTag};
+use store::EventLogStore;
+
+/// events kept in RAM until they are written to flash, the oldest ones are
+/// dropped if flash falls behind
+pub const RAM_LOG_LEN: usize = 32;
+/// entries per downloaded chunk
+pub const CHUNK_LEN: usize = 16;
+pub const ENTRY_SIZE: usize = 8;
+/// time without errors that ends a burst of bus errors
+pub const ERROR_BURST_GAP: Duration = Duration::from_secs(10);
+
+// bit of the event byte holding the time reference
+const MISSION_TIME_FLAG: u8 = 1 << 7;
+
+// Event log service task
+#[cfg(feature = "stm32")]
+#[embassy_executor::task]
+pub async fn event_log_thread(mut service: EventLogService<'static, BoardFlash>) {
+    loop {
+        service.run().await;
+    }
+}
+
+/// Logged event, the parameters of each are listed in order
+#[repr(u8)]
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// reset cause, boots since the last stable uptime and low byte of all boots
+    Boot,
+    /// mode reason, old mode and new mode
+    ModeChange,
+    /// switch reason and source input or sink index of a protection status change
+    Protection,
+    /// old and new enabled sources as bits
+    SourceSwitch,
+    /// old and new enabled sinks as bits
+    SinkChange,
+    /// source input and new state of a battery temperature sensor
+    TempSensor,
+    /// topic id (u16 le) of telemetry that could not be sent
+    CanTxError,
+    /// none, a frame was received broken
+    CanRxError,
+    /// trigger of a burst capture
+    BurstTrigger,
+    /// event id and count (u16 le) of the errors following the first one of a burst
+    ErrorBurst,
+}
+
+impl Event {
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::Boot),
+            1 => Some(Self::ModeChange),
+            2 => Some(Self::Protection),
+            3 => Some(Self::SourceSwitch),
+            4 => Some(Self::SinkChange),
+            5 => Some(Self::TempSensor),
+            6 => Some(Self::CanTxError),
+            7 => Some(Self::CanRxError),
+            8 => Some(Self::BurstTrigger),
+            9 => Some(Self::ErrorBurst),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub time: TimeTag,
+    pub event: Event,
+    pub params: [u8; 3],
+}
+
+impl Entry {
+    pub fn encode(&self) -> [u8; ENTRY_SIZE] {
+        let (flag, time) = match self.time {
+            TimeTag::Boot(ms) => (0, ms),
+            TimeTag::Mission(ms) => (MISSION_TIME_FLAG, ms),
+        };
+        let [t0, t1, t2, t3] = time.to_le_bytes();
+        let [p0, p1, p2] = self.params;
+        [t0, t1, t2, t3, flag | self.event as u8, p0, p1, p2]
+    }
+    pub fn decode(bytes: [u8; ENTRY_SIZE]) -> Option<Self> {
+        let ms = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let time = match bytes[4] & MISSION_TIME_FLAG {
+            0 => TimeTag::Boot(ms),
+            _ => TimeTag::Mission(ms),
+        };
+        Some(Self {
+            time,
+            event: Event::from_id(bytes[4] & !MISSION_TIME_FLAG)?,
+            params: [bytes[5], bytes[6], bytes[7]],
+        })
+    }
+}
+
+/// Events not written to flash yet
+struct RamLog {
+    entries: Deque<Entry, RAM_LOG_LEN>,
+    // events dropped since the log was last cleared
+    lost: u16,
+}
+
+// events are logged by all tasks and written to flash by the event log service
+static RAM_LOG: Mutex<CriticalSectionRawMutex, RefCell<RamLog>> =
+    Mutex::new(RefCell::new(RamLog {
+        entries: Deque::new(),
+        lost: 0,
+    }));
+static LOGGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// log an event stamped with the current mission time
+pub fn log(event: Event, params: [u8; 3]) {
+    let entry = Entry {
+        time: mission_time::now(),
+        event,
+        params,
+    };
+    RAM_LOG.lock(|log| {
+        let mut log = log.borrow_mut();
+        if log.entries.is_full() {
+            log.entries.pop_front();
+            log.lost = log.lost.saturating_add(1);
+        }
+        let _ = log.entries.push_back(entry);
+    });
+    LOGGED.signal(());
+}
+fn take_logged() -> Option<Entry> {
+    RAM_LOG.lock(|log| log.borrow_mut().entries.pop_front())
+}
+fn count_lost() {
+    RAM_LOG.lock(|log| {
+        let mut log = log.borrow_mut();
+        log.lost = log.lost.saturating_add(1);
+    });
+}
+/// events dropped since the log was last cleared
+pub fn lost() -> u16 {
+    RAM_LOG.lock(|log| log.borrow().lost)
+}
+
+/// Logs only the first of a burst of repeated errors, e.g. of a failing bus,
+/// and counts the rest. The count is logged as [`Event::ErrorBurst`] once no
+/// error occurred for `gap`
+pub struct ErrorBurst {
+    event: Event,
+    gap: Duration,
+    // time of the last error and errors not logged since the first one
+    burst: Option<(Instant, u16)>,
+}
+
+impl ErrorBurst {
+    pub const fn new(event: Event, gap: Duration) -> Self {
+        Self {
+            event,
+            gap,
+            burst: None,
+        }
+    }
+    /// an error occurred, logged if it starts a new burst
+    pub fn error(&mut self, params: [u8; 3]) {
+        let now = Instant::now();
+        self.end_at(now);
+        self.burst = match self.burst {
+            Some((_, suppressed)) => Some((now, suppressed.saturating_add(1))),
+            None => {
+                log(self.event, params);
+                Some((now, 0))
+            }
+        };
+    }
+    /// the operation succeeded, ends the burst if the last error is long enough ago
+    pub fn ok(&mut self) {
+        self.end_at(Instant::now());
+    }
+    fn end_at(&mut self, now: Instant) {
+        let Some((last, suppressed)) = self.burst else {
+            return;
+        };
+        if now - last < self.gap {
+            return;
+        }
+        self.burst = None;
+        if suppressed > 0 {
+            let [count_low, count_high] = suppressed.to_le_bytes();
+            log(Event::ErrorBurst, [self.event as u8, count_low, count_high]);
+        }
+    }
+}
+
+/// Event log telecommands forwarded by the control loop
+#[derive(Format, Clone, Copy)]
+pub enum LogCommand {
+    /// send the entries of a chunk, chunk 0 holds the oldest entries
+    Dump(u8),
+    Clear,
+}
+
+/// Mirrors logged events to flash and answers the event log telecommands
+pub struct EventLogService<'a, F: NorFlash> {
+    store: EventLogStore<F>,
+    cmd_receiver: DynamicReceiver<'a, LogCommand>,
+    tm_sender: DynamicSender<'a, EpsTMContainer>,
+}
+
+impl<'a, F: NorFlash> EventLogService<'a, F> {
+    /// `store` has to be loaded
+    pub fn new(
+        store: EventLogStore<F>,
+        cmd_receiver: DynamicReceiver<'a, LogCommand>,
+        tm_sender: DynamicSender<'a, EpsTMContainer>,
+    ) -> Self {
+        Self {
+            store,
+            cmd_receiver,
+            tm_sender,
+        }
+    }
+    /// write the logged events to flash
+    fn flush(&mut self) {
+        while let Some(entry) = take_logged() {
+            if let Err(e) = self.store.append(&entry) {
+                warn!("event not stored: {}", e);
+                count_lost();
+            }
+        }
+    }
+    /// send the entries of a chunk followed by the chunk telemetry, packed as
+    /// `[chunk, chunk count, entries sent, lost events (saturated)]`
+    async fn send_chunk(&mut self, chunk: u8) {
+        let len = self.store.len();
+        let start = chunk as usize * CHUNK_LEN;
+        let mut sent = 0;
+        for position in start..(start + CHUNK_LEN).min(len) {
+            match self.store.read(position) {
+                Ok(Some(entry)) => {
+                    let entry = u64::from_le_bytes(entry.encode());
+                    let container = EpsTMContainer::new(&tm::EventLogEntry, &entry).unwrap();
+                    self.tm_sender.send(container).await;
+                    sent += 1;
+                }
+                // slot of an interrupted write
+                Ok(None) => (),
+                Err(e) => warn!("event log not readable: {}", e),
+            }
+        }
+        let chunks = len.div_ceil(CHUNK_LEN) as u8;
+        let lost = lost().min(u8::MAX.into()) as u8;
+        let chunk = u32::from_le_bytes([chunk, chunks, sent, lost]);
+        let container = EpsTMContainer::new(&tm::EventLogChunk, &chunk).unwrap();
+        self.tm_sender.send(container).await;
+    }
+    pub async fn run(&mut self) {
+        match select(LOGGED.wait(), self.cmd_receiver.receive()).await {
+            Either::First(()) => self.flush(),
+            Either::Second(LogCommand::Dump(chunk)) => {
+                self.flush();
+                self.send_chunk(chunk).await;
+            }
+            Either::Second(LogCommand::Clear) => {
+                if let Err(e) = self.store.clear() {
+                    warn!("event log not cleared: {}", e);
+                }
+                RAM_LOG.lock(|log| {
+                    let mut log = log.borrow_mut();
+                    log.entries.clear();
+                    log.lost = 0;
+                });
+            }
+        }
+    }
+}
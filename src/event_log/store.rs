@@ -0,0 +1,62 @@
+use defmt::Format;
+use embedded_storage::nor_flash::NorFlash;
+
+use super::{ENTRY_SIZE, Entry};
+use crate::record_store::{RecordStore, record_size};
+
+#[repr(u8)]
+#[derive(Format, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventLogError {
+    Flash = 1,
+}
+
+const MAGIC: u16 = 0xE7E1;
+/// bumped whenever the record layout changes, records of other layouts are ignored
+const LAYOUT_VERSION: u8 = 1;
+
+/// size of a record slot, padded to the flash double word
+pub const RECORD_SIZE: usize = record_size(ENTRY_SIZE);
+
+/// Log entries in a [`RecordStore`], one record per entry. The log holds at
+/// least a page of the latest entries
+pub struct EventLogStore<F: NorFlash> {
+    records: RecordStore<F, RECORD_SIZE>,
+}
+
+impl<F: NorFlash> EventLogStore<F> {
+    /// `offset` is the start of two erase pages reserved for the event log
+    pub fn new(flash: F, offset: u32) -> Self {
+        Self {
+            records: RecordStore::new(flash, offset, MAGIC, LAYOUT_VERSION),
+        }
+    }
+    /// find the end of the log
+    pub fn load(&mut self) -> Result<(), EventLogError> {
+        self.records.load().map_err(|_| EventLogError::Flash)
+    }
+    /// used slots, including the ones of interrupted writes
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+    /// entry at `position` counted from the oldest one, `None` for slots
+    /// without a valid entry
+    pub fn read(&mut self, position: usize) -> Result<Option<Entry>, EventLogError> {
+        let record = self
+            .records
+            .read(position)
+            .map_err(|_| EventLogError::Flash)?;
+        Ok(record.and_then(|r| Entry::decode(r.payload()[..ENTRY_SIZE].try_into().unwrap())))
+    }
+    pub fn append(&mut self, entry: &Entry) -> Result<(), EventLogError> {
+        self.records
+            .append(&entry.encode())
+            .map_err(|_| EventLogError::Flash)
+    }
+    /// erase all entries
+    pub fn clear(&mut self) -> Result<(), EventLogError> {
+        self.records.clear().map_err(|_| EventLogError::Flash)
+    }
+}
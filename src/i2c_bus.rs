@@ -0,0 +1,13 @@
+//! Shared I2C bus of the temperature sensors
+#[cfg(feature = "stm32")]
+mod stm32;
+
+#[cfg(feature = "stm32")]
+pub use stm32::Stm32I2cBus;
+
+/// I2C bus able to recover from a lockup
+#[allow(async_fn_in_trait)]
+pub trait BusRecovery {
+    /// release a slave holding SDA low and reinitialize the bus peripheral
+    async fn recover(&mut self);
+}
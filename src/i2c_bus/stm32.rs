@@ -0,0 +1,112 @@
+use embassy_stm32::{
+    Peri, bind_interrupts,
+    gpio::{Level, OutputOpenDrain, Speed},
+    i2c::{self, I2c, Master},
+    mode::Async,
+    peripherals::{self, DMA1_CH2, DMA1_CH3, I2C2, PA6, PA7},
+};
+use embassy_time::Timer;
+use embedded_hal_async::i2c::{ErrorType, Operation};
+
+use super::BusRecovery;
+
+bind_interrupts!(struct Irqs {
+    I2C2_3 => i2c::EventInterruptHandler<peripherals::I2C2>, i2c::ErrorInterruptHandler<peripherals::I2C2>;
+});
+
+// half period of the recovery clock, 100kHz
+const RECOVERY_HALF_PERIOD_US: u64 = 5;
+// a slave can at most be in the middle of a byte plus ack
+const RECOVERY_CLOCKS: usize = 9;
+
+/// I2C2 on PA7 (SCL) and PA6 (SDA)
+pub struct Stm32I2cBus {
+    // only none while the bus is recovered
+    i2c: Option<I2c<'static, Async, Master>>,
+    config: i2c::Config,
+}
+
+impl Stm32I2cBus {
+    pub fn new(
+        _i2c: Peri<'static, I2C2>,
+        _scl: Peri<'static, PA7>,
+        _sda: Peri<'static, PA6>,
+        _tx_dma: Peri<'static, DMA1_CH2>,
+        _rx_dma: Peri<'static, DMA1_CH3>,
+        config: i2c::Config,
+    ) -> Self {
+        Self {
+            i2c: Some(Self::create(config)),
+            config,
+        }
+    }
+    /// the driver is recreated during recovery, so the peripherals owned by this bus are stolen
+    fn create(config: i2c::Config) -> I2c<'static, Async, Master> {
+        unsafe {
+            I2c::new(
+                I2C2::steal(),
+                PA7::steal(),
+                PA6::steal(),
+                Irqs,
+                DMA1_CH2::steal(),
+                DMA1_CH3::steal(),
+                config,
+            )
+        }
+    }
+    async fn half_period() {
+        Timer::after_micros(RECOVERY_HALF_PERIOD_US).await;
+    }
+}
+
+impl BusRecovery for Stm32I2cBus {
+    async fn recover(&mut self) {
+        // dropping the driver releases the pins and disables the peripheral
+        self.i2c = None;
+        {
+            let mut scl = OutputOpenDrain::new(unsafe { PA7::steal() }, Level::High, Speed::Low);
+            let mut sda = OutputOpenDrain::new(unsafe { PA6::steal() }, Level::High, Speed::Low);
+            Self::half_period().await;
+
+            // clock out the remaining bits of a slave stuck in a read
+            for _ in 0..RECOVERY_CLOCKS {
+                if sda.is_high() {
+                    break;
+                }
+                scl.set_low();
+                Self::half_period().await;
+                scl.set_high();
+                Self::half_period().await;
+            }
+
+            // stop condition, SDA rising while SCL is high
+            scl.set_low();
+            sda.set_low();
+            Self::half_period().await;
+            scl.set_high();
+            Self::half_period().await;
+            sda.set_high();
+            Self::half_period().await;
+        }
+        // creating the driver resets the peripheral through the rcc
+        self.i2c = Some(Self::create(self.config));
+    }
+}
+
+impl ErrorType for Stm32I2cBus {
+    type Error = i2c::Error;
+}
+
+impl embedded_hal_async::i2c::I2c for Stm32I2cBus {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.i2c
+            .as_mut()
+            .expect("i2c bus in recovery")
+            .transaction(address, operations)
+            .await
+    }
+}
@@ -0,0 +1,30 @@
+#![no_std]
+#![feature(variant_count)]
+#![feature(type_alias_impl_trait)]
+#![feature(iter_collect_into)]
+#![feature(iterator_try_collect)]
+#![allow(incomplete_features)]
+#![feature(generic_const_exprs)] // This feature is incomplete but beeing used in a benign context
+
+pub mod adc;
+pub mod board;
+pub mod config;
+pub mod control_loop;
+pub mod event_log;
+pub mod i2c_bus;
+pub mod mission_time;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod protection;
+#[allow(dead_code)]
+pub mod pwr_src;
+pub mod record_store;
+pub mod reset;
+pub mod script;
+pub mod shared_flash;
+pub mod telecommand;
+
+use south_common::{telemetry::eps as tm, telemetry_container};
+
+// TM container
+pub type EpsTMContainer = telemetry_container!(tm);
@@ -1,29 +1,48 @@
 #![no_std]
 #![no_main]
-#![feature(variant_count)]
-#![feature(type_alias_impl_trait)]
-#![feature(iter_collect_into)]
-#![feature(iterator_try_collect)]
-#![allow(incomplete_features)]
-#![feature(generic_const_exprs)] // This feature is incomplete but beeing used in a benign context
-
-mod adc;
-mod control_loop;
-#[allow(dead_code)]
-mod pwr_src;
-
-use adc::AdcCtrl;
-use control_loop::ControlLoop;
-use pwr_src::{
-    aux_pwr::AuxPwr,
-    battery::{Battery, tmp100_drv::*},
-    d_flip_flop::DFlipFlop,
-    sink_ctrl::SinkCtrl,
+
+use core::cell::RefCell;
+
+use eps_software::{
+    EpsTMContainer,
+    adc::{self, AdcCtrl, AdcCtrlChannel, Stm32Adc, burst::BurstService, stats::SharedStatistics},
+    board::{
+        AuxPwrCurrentSense, BATTERY_CAPACITY_MAH, BatteryCurrentSense, CONFIG_FLASH_OFFSET,
+        EVENT_LOG_FLASH_OFFSET, SCRIPT_FLASH_OFFSET, SinkCurrentSense, TC_KEY_FLASH_OFFSET,
+    },
+    config::{self, ConfigCommand, ConfigService, EpsConfig, store::ConfigStore},
+    control_loop::{
+        self, ControlLoop, PowerReadings,
+        mode::{Mode, ModeReason},
+    },
+    event_log::{
+        self, ERROR_BURST_GAP, ErrorBurst, Event, EventLogService, LogCommand, store::EventLogStore,
+    },
+    i2c_bus::Stm32I2cBus,
+    mission_time,
+    protection::ProtectionEvent,
+    pwr_src::{
+        aux_pwr::{self, AuxPwr},
+        battery::{
+            self, Battery, BatteryTopics, soc::SocEstimator, temp_sensor::TempSensor, tmp100_drv::*,
+        },
+        d_flip_flop::{DFlipFlop, FlipFlopInput},
+        sink_ctrl::{SINKS, SinkCtrl},
+        sink_monitor::{self, SinkMonitor},
+    },
+    reset::{self, BackupRegisters},
+    script::{self, Script, ScriptService, Scripts, store::ScriptStore},
+    shared_flash::{BoardFlash, SharedFlash},
+    telecommand::{
+        ReceivedTc, TcSequencer,
+        auth::{KeyStore, TcAuth},
+    },
 };
 
 use defmt::*;
 
 use embassy_executor::Spawner;
+use embassy_futures::select::{Either, select};
 use embassy_stm32::{
     Config,
     adc::{Adc, AdcChannel, AdcConfig},
@@ -32,37 +51,32 @@ use embassy_stm32::{
         self, BufferedFdCanReceiver, BufferedFdCanSender, CanConfigurator, RxFdBuf, TxFdBuf,
         frame::FdFrame,
     },
+    flash::{Blocking, Flash},
     gpio::{Level, Output, Speed},
-    i2c::{self, I2c, Master},
-    mode::Async,
-    peripherals::{self, FDCAN1, IWDG},
+    i2c,
+    peripherals::{FDCAN1, IWDG},
     rcc::{self, mux::Fdcansel},
     time::khz,
     wdg::IndependentWatchdog,
 };
 use embassy_sync::{
-    blocking_mutex::raw::ThreadModeRawMutex,
+    blocking_mutex::{self, raw::ThreadModeRawMutex},
     channel::{Channel, DynamicSender, Receiver, Sender},
     mutex::Mutex,
     watch::{DynReceiver, Watch},
 };
 use embassy_time::{Duration, Instant, Timer};
+use embedded_can::Id;
 use south_common::{
-    TMValue, TelemetryContainer, TelemetryDefinition, can_config::CanPeriphConfig, telecommands,
-    telemetry::eps as tm, telemetry_container, types::Telecommand,
+    TelemetryContainer, TelemetryDefinition, can_config::CanPeriphConfig, telecommands,
+    telemetry::eps as tm, types::Sink,
 };
 use static_cell::StaticCell;
 
-use crate::{
-    adc::AdcCtrlChannel,
-    pwr_src::{aux_pwr, battery},
-};
-
 use {defmt_rtt as _, panic_probe as _};
 
 // bind interrupts
 bind_interrupts!(struct Irqs {
-    I2C2_3 => i2c::EventInterruptHandler<peripherals::I2C2>, i2c::ErrorInterruptHandler<peripherals::I2C2>;
     TIM16_FDCAN_IT0 => can::IT0InterruptHandler<FDCAN1>;
     TIM17_FDCAN_IT1 => can::IT1InterruptHandler<FDCAN1>;
 });
@@ -91,24 +105,63 @@ fn get_rcc_config() -> rcc::Config {
 const WATCHDOG_TIMEOUT_US: u32 = 300_000;
 const WATCHDOG_PETTING_INTERVAL_US: u32 = WATCHDOG_TIMEOUT_US / 2;
 
-// TM container
-type EpsTMContainer = telemetry_container!(tm);
-
 // static concurrency sync management types
 static ITW: StaticCell<Watch<ThreadModeRawMutex, i16, 1>> = StaticCell::new();
 static B1W: StaticCell<Watch<ThreadModeRawMutex, i16, 1>> = StaticCell::new();
 static B2W: StaticCell<Watch<ThreadModeRawMutex, i16, 1>> = StaticCell::new();
+static B1CW: StaticCell<Watch<ThreadModeRawMutex, i16, 1>> = StaticCell::new();
+static B2CW: StaticCell<Watch<ThreadModeRawMutex, i16, 1>> = StaticCell::new();
 static APW: StaticCell<Watch<ThreadModeRawMutex, i16, 1>> = StaticCell::new();
+static APCW: StaticCell<Watch<ThreadModeRawMutex, i16, 1>> = StaticCell::new();
+// bus voltage is used by the sink monitor and the load shedding
+static BVW: StaticCell<Watch<ThreadModeRawMutex, i16, 2>> = StaticCell::new();
+static B1SW: StaticCell<Watch<ThreadModeRawMutex, u16, 1>> = StaticCell::new();
+static B2SW: StaticCell<Watch<ThreadModeRawMutex, u16, 1>> = StaticCell::new();
+static SCW: StaticCell<[Watch<ThreadModeRawMutex, i16, 1>; SINKS.len()]> = StaticCell::new();
+// statistics of the measured channels between two telemetry reports
+static ITST: SharedStatistics = SharedStatistics::new();
+static B1ST: SharedStatistics = SharedStatistics::new();
+static B2ST: SharedStatistics = SharedStatistics::new();
+static B1CST: SharedStatistics = SharedStatistics::new();
+static B2CST: SharedStatistics = SharedStatistics::new();
+static APST: SharedStatistics = SharedStatistics::new();
+static APCST: SharedStatistics = SharedStatistics::new();
+static BVST: SharedStatistics = SharedStatistics::new();
+static SCST: [SharedStatistics; SINKS.len()] = [const { SharedStatistics::new() }; SINKS.len()];
 
 const TM_CHANNEL_BUF_SIZE: usize = 5;
+const BURST_TM_CHANNEL_BUF_SIZE: usize = 2;
 const CMD_CHANNEL_BUF_SIZE: usize = 5;
+const PROTECTION_CHANNEL_BUF_SIZE: usize = 4;
+const FUSE_RESET_CHANNEL_BUF_SIZE: usize = 2;
+const CONFIG_CHANNEL_BUF_SIZE: usize = 2;
+// scripts are stored rarely and take some memory
+const SCRIPT_CHANNEL_BUF_SIZE: usize = 1;
+const LOG_CHANNEL_BUF_SIZE: usize = 2;
 static TMC: StaticCell<Channel<ThreadModeRawMutex, EpsTMContainer, TM_CHANNEL_BUF_SIZE>> =
     StaticCell::new();
-static CMDC: StaticCell<Channel<ThreadModeRawMutex, Telecommand, CMD_CHANNEL_BUF_SIZE>> =
+// burst captures are streamed only while no other telemetry is waiting
+static BTMC: StaticCell<Channel<ThreadModeRawMutex, EpsTMContainer, BURST_TM_CHANNEL_BUF_SIZE>> =
+    StaticCell::new();
+static CMDC: StaticCell<Channel<ThreadModeRawMutex, ReceivedTc, CMD_CHANNEL_BUF_SIZE>> =
+    StaticCell::new();
+static PROTC: StaticCell<
+    Channel<ThreadModeRawMutex, ProtectionEvent, PROTECTION_CHANNEL_BUF_SIZE>,
+> = StaticCell::new();
+static FUSEC: StaticCell<Channel<ThreadModeRawMutex, Sink, FUSE_RESET_CHANNEL_BUF_SIZE>> =
+    StaticCell::new();
+static CFGC: StaticCell<Channel<ThreadModeRawMutex, ConfigCommand, CONFIG_CHANNEL_BUF_SIZE>> =
+    StaticCell::new();
+static SCRC: StaticCell<Channel<ThreadModeRawMutex, (u8, Script), SCRIPT_CHANNEL_BUF_SIZE>> =
+    StaticCell::new();
+static LOGC: StaticCell<Channel<ThreadModeRawMutex, LogCommand, LOG_CHANNEL_BUF_SIZE>> =
     StaticCell::new();
 
 // static peripherals
-static I2C: StaticCell<Mutex<ThreadModeRawMutex, I2c<'static, Async, Master>>> = StaticCell::new();
+static FLASH: StaticCell<
+    blocking_mutex::Mutex<ThreadModeRawMutex, RefCell<Flash<'static, Blocking>>>,
+> = StaticCell::new();
+static I2C: StaticCell<Mutex<ThreadModeRawMutex, Stm32I2cBus>> = StaticCell::new();
 
 // can configuration
 const RX_BUF_SIZE: usize = 500;
@@ -131,15 +184,20 @@ async fn petter(mut watchdog: IndependentWatchdog<'static, IWDG>) {
 pub async fn internal_temp_thread(
     tm_sender: DynamicSender<'static, EpsTMContainer>,
     mut temp_receiver: DynReceiver<'static, i16>,
+    temp_stats: &'static SharedStatistics,
+    loop_len: Duration,
 ) {
-    const INTERNAL_TEMP_LOOP_LEN: Duration = Duration::from_secs(2);
     let mut loop_time = Instant::now();
     loop {
         let container =
             EpsTMContainer::new(&tm::InternalTemperature, &temp_receiver.get().await).unwrap();
         tm_sender.send(container).await;
+        let stats = temp_stats.take();
+        tm_sender
+            .send(stats.container(&tm::InternalTemperatureStats))
+            .await;
 
-        loop_time += INTERNAL_TEMP_LOOP_LEN;
+        loop_time += loop_len;
         Timer::at(loop_time).await;
     }
 }
@@ -149,12 +207,28 @@ pub async fn internal_temp_thread(
 pub async fn tm_thread(
     mut can_sender: BufferedFdCanSender,
     tm_channel: Receiver<'static, ThreadModeRawMutex, EpsTMContainer, TM_CHANNEL_BUF_SIZE>,
+    burst_channel: Receiver<'static, ThreadModeRawMutex, EpsTMContainer, BURST_TM_CHANNEL_BUF_SIZE>,
 ) {
+    let mut tx_errors = ErrorBurst::new(Event::CanTxError, ERROR_BURST_GAP);
     loop {
-        let container = tm_channel.receive().await;
-        match FdFrame::new_standard(container.id(), container.bytes()) {
-            Ok(frame) => can_sender.write(frame).await,
-            Err(e) => error!("error constructing can message: {}", e),
+        // the regular telemetry goes first
+        let (Either::First(container) | Either::Second(container)) =
+            select(tm_channel.receive(), burst_channel.receive()).await;
+        let [id_low, id_high] = container.id().to_le_bytes();
+        let Some(data) = mission_time::stamped(container.bytes()) else {
+            error!("telemetry too long to stamp: {}", container.id());
+            tx_errors.error([id_low, id_high, 0]);
+            continue;
+        };
+        match FdFrame::new_standard(container.id(), &data) {
+            Ok(frame) => {
+                can_sender.write(frame).await;
+                tx_errors.ok();
+            }
+            Err(e) => {
+                error!("error constructing can message: {}", e);
+                tx_errors.error([id_low, id_high, 0]);
+            }
         }
     }
 }
@@ -163,15 +237,45 @@ pub async fn tm_thread(
 #[embassy_executor::task]
 pub async fn tc_thread(
     can_receiver: BufferedFdCanReceiver,
-    tc_channel: Sender<'static, ThreadModeRawMutex, Telecommand, TM_CHANNEL_BUF_SIZE>,
+    tc_channel: Sender<'static, ThreadModeRawMutex, ReceivedTc, CMD_CHANNEL_BUF_SIZE>,
+    tm_sender: DynamicSender<'static, EpsTMContainer>,
+    mut auth: TcAuth<BoardFlash>,
 ) {
+    let mut sequencer = TcSequencer::new();
+    let mut rx_errors = ErrorBurst::new(Event::CanRxError, ERROR_BURST_GAP);
     loop {
         match can_receiver.receive().await {
-            Ok(envelope) => match Telecommand::read(envelope.frame.data()) {
-                Ok((_, cmd)) => tc_channel.send(cmd).await,
-                Err(_) => error!("error parsing tc"),
-            },
-            Err(e) => error!("error in frame! {}", e),
+            Ok(envelope) => {
+                rx_errors.ok();
+                let data = envelope.frame.data();
+                let received = match envelope.frame.header().id() {
+                    // broadcast by the flight computer, not a telecommand
+                    Id::Standard(id) if id.as_raw() == telecommands::TimeSync.id() => {
+                        match mission_time::sync(data) {
+                            Some(report) => tm_sender.send(report.container()).await,
+                            None => error!("invalid time sync"),
+                        }
+                        continue;
+                    }
+                    Id::Standard(id) if id.as_raw() == telecommands::TimeTaggedTelecommand.id() => {
+                        sequencer.receive_time_tagged(data, &mut auth)
+                    }
+                    _ => sequencer.receive(data, &mut auth),
+                };
+                match received {
+                    Ok(Some(tc)) => tc_channel.send(tc).await,
+                    Ok(None) => (),
+                    Err(ack) => {
+                        error!("tc rejected");
+                        tm_sender.send(ack).await;
+                        tm_sender.send(auth.rejects_container()).await;
+                    }
+                }
+            }
+            Err(e) => {
+                error!("error in frame! {}", e);
+                rx_errors.error([0; 3]);
+            }
         }
     }
 }
@@ -184,15 +288,103 @@ async fn main(spawner: Spawner) {
     let p = embassy_stm32::init(config);
     info!("Launching");
 
+    // count this boot before anything else can go wrong
+    let reset_cause = reset::read_reset_cause();
+    let mut backup_registers = BackupRegisters::new();
+    let reset_counters = backup_registers.load().count_boot();
+    backup_registers.store(reset_counters);
+    let boot_reason = reset::boot_reason(reset_cause, reset_counters);
+    info!("reset by {}, {}", reset_cause, reset_counters);
+    event_log::log(
+        Event::Boot,
+        [
+            reset_cause as u8,
+            reset_counters.rapid,
+            reset_counters.total as u8,
+        ],
+    );
+
     // unleash independent watchdog
     let mut watchdog = IndependentWatchdog::new(p.IWDG, WATCHDOG_TIMEOUT_US);
     watchdog.unleash();
+    spawner.must_spawn(petter(watchdog));
+
+    // debug leds not used at the moment (might disrupt can)
+    let _led1 = Output::new(p.PB7, Level::Low, Speed::Low);
+    let _led2 = Output::new(p.PB8, Level::Low, Speed::Low);
+
+    // set can standby pin to low
+    let _can_standby = Output::new(p.PA10, Level::Low, Speed::Low);
+    //let _can_2_standby = Output::new(p.PB2, Level::High, Speed::Low);
+
+    // -- CAN configuration
+    let mut can_configurator =
+        CanPeriphConfig::new(CanConfigurator::new(p.FDCAN1, p.PA11, p.PA12, Irqs));
+
+    can_configurator
+        .add_receive_topic(telecommands::Telecommand.id())
+        .unwrap();
+    can_configurator
+        .add_receive_topic(telecommands::TimeTaggedTelecommand.id())
+        .unwrap();
+    can_configurator
+        .add_receive_topic(telecommands::TimeSync.id())
+        .unwrap();
+
+    let can_interface = can_configurator.activate(
+        TX_BUF.init(TxFdBuf::<TX_BUF_SIZE>::new()),
+        RX_BUF.init(RxFdBuf::<RX_BUF_SIZE>::new()),
+    );
+
+    // telemetry is sent from here on, the boot below waits on the TM channel
+    let tm_channel = TMC.init(Channel::new());
+    let burst_tm_channel = BTMC.init(Channel::new());
+    spawner.must_spawn(tm_thread(
+        can_interface.writer(),
+        tm_channel.receiver(),
+        burst_tm_channel.receiver(),
+    ));
+
+    // persistent configuration
+    let flash = FLASH.init(blocking_mutex::Mutex::new(RefCell::new(
+        Flash::new_blocking(p.FLASH),
+    )));
+    let mut config_store = ConfigStore::new(SharedFlash::new(flash), CONFIG_FLASH_OFFSET);
+    let eps_config = config_store.load().unwrap_or_else(|e| {
+        warn!("using default config: {}", e);
+        EpsConfig::DEFAULT
+    });
+    let (divider_r1_ohm, divider_r2_ohm) = eps_config.voltage_divider_ohm();
+    adc::conversion::set_voltage_divider(divider_r1_ohm, divider_r2_ohm);
+    adc::conversion::set_vref(eps_config.adc_vref_10mv());
+
+    // power scripts
+    let mut script_store = ScriptStore::new(SharedFlash::new(flash), SCRIPT_FLASH_OFFSET);
+    let scripts = script_store.load().unwrap_or_else(|e| {
+        warn!("no power scripts: {}", e);
+        Scripts::default()
+    });
+
+    // event log, entries of earlier boots are kept
+    let mut event_log_store = EventLogStore::new(SharedFlash::new(flash), EVENT_LOG_FLASH_OFFSET);
+    if let Err(e) = event_log_store.load() {
+        warn!("event log not loaded: {}", e);
+    }
+
+    // telecommand authentication, provisioning builds store the key built into them
+    #[cfg_attr(not(feature = "provision-key"), allow(unused_mut))]
+    let mut key_store = KeyStore::new(SharedFlash::new(flash), TC_KEY_FLASH_OFFSET);
+    #[cfg(feature = "provision-key")]
+    if let Err(e) = key_store.ensure(&eps_software::telecommand::auth::PROVISION_KEY) {
+        error!("telecommand key not provisioned: {}", e);
+    }
+    let tc_auth = TcAuth::new(key_store);
 
     // i2c for temperature sensors
     let mut i2c_config = i2c::Config::default();
     i2c_config.frequency = khz(400);
-    let temp_sensor_i2c = I2C.init(Mutex::new(I2c::new(
-        p.I2C2, p.PA7, p.PA6, Irqs, p.DMA1_CH2, p.DMA1_CH3, i2c_config,
+    let temp_sensor_i2c = I2C.init(Mutex::new(Stm32I2cBus::new(
+        p.I2C2, p.PA7, p.PA6, p.DMA1_CH2, p.DMA1_CH3, i2c_config,
     )));
 
     // flip flop
@@ -204,7 +396,8 @@ async fn main(spawner: Spawner) {
     );
 
     // sink ctrl
-    let sink_ctrl = SinkCtrl::new(p.PA9, p.PA5, p.PA0, p.PA15);
+    let boot_sinks = eps_config.mode_sinks(Mode::boot(boot_reason));
+    let sink_ctrl = SinkCtrl::new(p.PA9, p.PA5, p.PA0, p.PA15, boot_sinks);
 
     // ADC setup
     let mut adc_config = AdcConfig::default();
@@ -219,112 +412,305 @@ async fn main(spawner: Spawner) {
     let internal_temperature_watch = ITW.init(Watch::new());
     let bat_1_watch = B1W.init(Watch::new());
     let bat_2_watch = B2W.init(Watch::new());
+    let bat_1_current_watch = B1CW.init(Watch::new());
+    let bat_2_current_watch = B2CW.init(Watch::new());
     let aux_pwr_watch = APW.init(Watch::new());
+    let aux_pwr_current_watch = APCW.init(Watch::new());
+    let bus_voltage_watch = BVW.init(Watch::new());
+    let bat_1_soc_watch = B1SW.init(Watch::new());
+    let bat_2_soc_watch = B2SW.init(Watch::new());
+    let sink_current_watches: &'static [_; SINKS.len()] =
+        SCW.init(core::array::from_fn(|_| Watch::new()));
 
     let bat_1_channel = AdcCtrlChannel::new(
         p.PA4.degrade_adc(),
         bat_1_watch.dyn_sender(),
+        &B1ST,
         adc::conversion::calculate_voltage_10mv,
     );
     let bat_2_channel = AdcCtrlChannel::new(
         p.PA3.degrade_adc(),
         bat_2_watch.dyn_sender(),
+        &B2ST,
         adc::conversion::calculate_voltage_10mv,
     );
+    let bat_1_current_channel = AdcCtrlChannel::new(
+        p.PB0.degrade_adc(),
+        bat_1_current_watch.dyn_sender(),
+        &B1CST,
+        adc::conversion::calculate_current_ma::<BatteryCurrentSense>,
+    );
+    let bat_2_current_channel = AdcCtrlChannel::new(
+        p.PB1.degrade_adc(),
+        bat_2_current_watch.dyn_sender(),
+        &B2CST,
+        adc::conversion::calculate_current_ma::<BatteryCurrentSense>,
+    );
     let aux_pwr_channel = AdcCtrlChannel::new(
         p.PA2.degrade_adc(),
         aux_pwr_watch.dyn_sender(),
+        &APST,
         adc::conversion::calculate_voltage_10mv,
     );
+    let aux_pwr_current_channel = AdcCtrlChannel::new(
+        p.PA1.degrade_adc(),
+        aux_pwr_current_watch.dyn_sender(),
+        &APCST,
+        adc::conversion::calculate_current_ma::<AuxPwrCurrentSense>,
+    );
+    // power path after the source flip flop, feeding all sinks
+    let bus_voltage_channel = AdcCtrlChannel::new(
+        p.PB2.degrade_adc(),
+        bus_voltage_watch.dyn_sender(),
+        &BVST,
+        adc::conversion::calculate_voltage_10mv,
+    );
+    // sink currents in SINKS order
+    let [lst_watch, sens_watch, gps_watch, rhd_watch] = sink_current_watches.each_ref();
+    let [lst_stats, sens_stats, gps_stats, rhd_stats] = SCST.each_ref();
+    let sink_current_channel = |pin,
+                                watch: &'static Watch<ThreadModeRawMutex, i16, 1>,
+                                stats: &'static SharedStatistics| {
+        AdcCtrlChannel::new(
+            pin,
+            watch.dyn_sender(),
+            stats,
+            adc::conversion::calculate_current_ma::<SinkCurrentSense>,
+        )
+    };
+    let lst_current = sink_current_channel(p.PB10.degrade_adc(), lst_watch, lst_stats);
+    let sens_current = sink_current_channel(p.PB11.degrade_adc(), sens_watch, sens_stats);
+    let gps_current = sink_current_channel(p.PB12.degrade_adc(), gps_watch, gps_stats);
+    let rhd_current = sink_current_channel(p.PC4.degrade_adc(), rhd_watch, rhd_stats);
 
     let adc = AdcCtrl::new(
-        adc_periph,
-        p.DMA1_CH1,
+        Stm32Adc::new(adc_periph, p.DMA1_CH1),
         internal_temperature_watch.dyn_sender(),
-        [bat_1_channel, bat_2_channel, aux_pwr_channel],
+        &ITST,
+        [
+            bat_1_channel,
+            bat_2_channel,
+            bat_1_current_channel,
+            bat_2_current_channel,
+            aux_pwr_channel,
+            aux_pwr_current_channel,
+            bus_voltage_channel,
+            lst_current,
+            sens_current,
+            gps_current,
+            rhd_current,
+        ],
     );
 
-    // TM channel setup
-    let tm_channel = TMC.init(Channel::new());
+    // channel setup
     let cmd_channel = CMDC.init(Channel::new());
+    let protection_channel = PROTC.init(Channel::new());
+    let fuse_reset_channel = FUSEC.init(Channel::new());
+    let config_channel = CFGC.init(Channel::new());
+    let script_channel = SCRC.init(Channel::new());
+    let log_channel = LOGC.init(Channel::new());
+
+    // check which temperature sensors are on the bus
+    let temp_sensor_scan = scan_bus(temp_sensor_i2c).await;
+    for (name, addr) in [
+        ("bat 1", eps_config.bat_1_temp_sensor_addr()),
+        ("bat 2", eps_config.bat_2_temp_sensor_addr()),
+    ] {
+        if temp_sensor_scan & addr.scan_bit() == 0 {
+            warn!(
+                "{} temp sensor not answering, devices found: {:b}",
+                name, temp_sensor_scan
+            );
+        }
+    }
+    let container = EpsTMContainer::new(&tm::TempSensorScan, &temp_sensor_scan).unwrap();
+    tm_channel.send(container).await;
+
+    let reset_info = reset_counters.tm_value(reset_cause);
+    let container = EpsTMContainer::new(&tm::ResetInfo, &reset_info).unwrap();
+    tm_channel.send(container).await;
+    tm_channel.send(tc_auth.counter_container()).await;
 
     // first battery
-    let bat_1_tmp = Tmp100::new(temp_sensor_i2c, Resolution::BITS12, Addr0State::Floating)
-        .await
-        .inspect_err(|e| error!("could not establish connection to bat 1 temp sensor: {}", e));
+    let bat_1_tmp = TempSensor::new(
+        temp_sensor_i2c,
+        eps_config.bat_1_temp_sensor_addr(),
+        eps_config.temp_sensor_resolution(),
+    )
+    .await;
     let bat_1 = Battery::new(
-        bat_1_tmp.ok(),
+        FlipFlopInput::Bat1,
+        bat_1_tmp,
         bat_1_watch.dyn_receiver().unwrap(),
+        bat_1_current_watch.dyn_receiver().unwrap(),
+        &B1ST,
+        &B1CST,
+        SocEstimator::new(BATTERY_CAPACITY_MAH),
+        bat_1_soc_watch.dyn_sender(),
+        eps_config.battery_voltage_limits(),
+        eps_config.battery_temperature_limits(),
+        protection_channel.dyn_sender(),
         tm_channel.dyn_sender(),
-        &tm::Bat1Temperature,
-        &tm::Bat1Voltage,
+        BatteryTopics {
+            temperature: &tm::Bat1Temperature,
+            voltage: &tm::Bat1Voltage,
+            voltage_stats: &tm::Bat1VoltageStats,
+            temp_sensor_errors: &tm::Bat1TempSensorErrors,
+            temp_sensor_state: &tm::Bat1TempSensorState,
+            current: &tm::Bat1Current,
+            current_stats: &tm::Bat1CurrentStats,
+            power: &tm::Bat1Power,
+            state_of_charge: &tm::Bat1StateOfCharge,
+            remaining_capacity: &tm::Bat1RemainingCapacity,
+            time_to_empty: &tm::Bat1TimeToEmpty,
+        },
     )
     .await;
 
     // second battery
-    let bat_2_tmp = Tmp100::new(temp_sensor_i2c, Resolution::BITS12, Addr0State::Floating)
-        .await
-        .inspect_err(|e| error!("could not establish connection to bat 2 temp sensor: {}", e));
+    let bat_2_tmp = TempSensor::new(
+        temp_sensor_i2c,
+        eps_config.bat_2_temp_sensor_addr(),
+        eps_config.temp_sensor_resolution(),
+    )
+    .await;
     let bat_2 = Battery::new(
-        bat_2_tmp.ok(),
+        FlipFlopInput::Bat2,
+        bat_2_tmp,
         bat_2_watch.dyn_receiver().unwrap(),
+        bat_2_current_watch.dyn_receiver().unwrap(),
+        &B2ST,
+        &B2CST,
+        SocEstimator::new(BATTERY_CAPACITY_MAH),
+        bat_2_soc_watch.dyn_sender(),
+        eps_config.battery_voltage_limits(),
+        eps_config.battery_temperature_limits(),
+        protection_channel.dyn_sender(),
         tm_channel.dyn_sender(),
-        &tm::Bat2Temperature,
-        &tm::Bat2Voltage,
+        BatteryTopics {
+            temperature: &tm::Bat2Temperature,
+            voltage: &tm::Bat2Voltage,
+            voltage_stats: &tm::Bat2VoltageStats,
+            temp_sensor_errors: &tm::Bat2TempSensorErrors,
+            temp_sensor_state: &tm::Bat2TempSensorState,
+            current: &tm::Bat2Current,
+            current_stats: &tm::Bat2CurrentStats,
+            power: &tm::Bat2Power,
+            state_of_charge: &tm::Bat2StateOfCharge,
+            remaining_capacity: &tm::Bat2RemainingCapacity,
+            time_to_empty: &tm::Bat2TimeToEmpty,
+        },
     )
     .await;
 
     // aux power
     let aux_pwr = AuxPwr::new(
         aux_pwr_watch.dyn_receiver().unwrap(),
+        aux_pwr_current_watch.dyn_receiver().unwrap(),
+        &APST,
+        &APCST,
+        protection_channel.dyn_sender(),
         tm_channel.dyn_sender(),
     )
     .await;
 
-    // debug leds not used at the moment (might disrupt can)
-    let _led1 = Output::new(p.PB7, Level::Low, Speed::Low);
-    let _led2 = Output::new(p.PB8, Level::Low, Speed::Low);
-
-    // set can standby pin to low
-    let _can_standby = Output::new(p.PA10, Level::Low, Speed::Low);
-    //let _can_2_standby = Output::new(p.PB2, Level::High, Speed::Low);
+    // sinks
+    let sink_monitor = SinkMonitor::new(
+        bus_voltage_watch.dyn_receiver().unwrap(),
+        sink_current_watches
+            .each_ref()
+            .map(|watch| watch.dyn_receiver().unwrap()),
+        &BVST,
+        SCST.each_ref(),
+        eps_config.sink_fuse_limits(),
+        fuse_reset_channel.dyn_receiver(),
+        protection_channel.dyn_sender(),
+        tm_channel.dyn_sender(),
+    );
 
-    // -- CAN configuration
-    let mut can_configurator =
-        CanPeriphConfig::new(CanConfigurator::new(p.FDCAN1, p.PA11, p.PA12, Irqs));
+    // configuration telecommands
+    let config_service = ConfigService::new(
+        config_store,
+        eps_config,
+        config_channel.dyn_receiver(),
+        tm_channel.dyn_sender(),
+    );
 
-    can_configurator
-        .add_receive_topic(telecommands::Telecommand.id())
-        .unwrap();
+    // script uploads
+    let script_service = ScriptService::new(
+        script_store,
+        scripts.clone(),
+        script_channel.dyn_receiver(),
+        tm_channel.dyn_sender(),
+    );
 
-    let can_interface = can_configurator.activate(
-        TX_BUF.init(TxFdBuf::<TX_BUF_SIZE>::new()),
-        RX_BUF.init(RxFdBuf::<RX_BUF_SIZE>::new()),
+    // event log downloads
+    let event_log_service = EventLogService::new(
+        event_log_store,
+        log_channel.dyn_receiver(),
+        tm_channel.dyn_sender(),
     );
 
+    // burst capture downloads
+    let burst_service = BurstService::new(burst_tm_channel.dyn_sender());
+
     // Main control loop setup
     let control_loop = ControlLoop::spawn(
+        boot_reason,
+        eps_config,
+        scripts,
         source_flip_flop,
         sink_ctrl,
         cmd_channel.dyn_receiver(),
+        protection_channel.dyn_receiver(),
+        fuse_reset_channel.dyn_sender(),
+        config_channel.dyn_sender(),
+        script_channel.dyn_sender(),
+        log_channel.dyn_sender(),
+        PowerReadings {
+            bus_voltage: bus_voltage_watch.dyn_receiver().unwrap(),
+            soc: [
+                bat_1_soc_watch.dyn_receiver().unwrap(),
+                bat_2_soc_watch.dyn_receiver().unwrap(),
+            ],
+        },
         tm_channel.dyn_sender(),
-    );
+    )
+    .await;
 
-    spawner.must_spawn(petter(watchdog));
+    spawner.must_spawn(reset::stable_uptime_thread(backup_registers));
 
-    spawner.must_spawn(adc::adc_thread(adc));
+    spawner.must_spawn(adc::adc_thread(adc, eps_config.adc_loop_len()));
+    spawner.must_spawn(adc::burst::burst_thread(burst_service));
     spawner.must_spawn(control_loop::ctrl_thread(control_loop));
-
-    spawner.must_spawn(battery::battery_thread(bat_1));
-    spawner.must_spawn(battery::battery_thread(bat_2));
-    spawner.must_spawn(aux_pwr::aux_pwr_thread(aux_pwr));
+    spawner.must_spawn(config::config_thread(config_service));
+    spawner.must_spawn(script::script_thread(script_service));
+    spawner.must_spawn(event_log::event_log_thread(event_log_service));
+
+    let battery_loop_len = eps_config.battery_loop_len();
+    spawner.must_spawn(battery::battery_thread(bat_1, battery_loop_len));
+    spawner.must_spawn(battery::battery_thread(bat_2, battery_loop_len));
+    spawner.must_spawn(aux_pwr::aux_pwr_thread(
+        aux_pwr,
+        eps_config.aux_pwr_loop_len(),
+    ));
+    spawner.must_spawn(sink_monitor::sink_monitor_thread(
+        sink_monitor,
+        eps_config.sink_loop_len(),
+    ));
 
     spawner.must_spawn(internal_temp_thread(
         tm_channel.dyn_sender(),
         internal_temperature_watch.dyn_receiver().unwrap(),
+        &ITST,
+        eps_config.internal_temp_loop_len(),
+    ));
+    spawner.must_spawn(tc_thread(
+        can_interface.reader(),
+        cmd_channel.sender(),
+        tm_channel.dyn_sender(),
+        tc_auth,
     ));
-    spawner.must_spawn(tm_thread(can_interface.writer(), tm_channel.receiver()));
-    spawner.must_spawn(tc_thread(can_interface.reader(), cmd_channel.sender()));
 
     // wait until all other threads finished (never)
     core::future::pending::<()>().await;
@@ -0,0 +1,181 @@
+//! Mission time kept in sync with the time broadcast of the flight computer.
+//! The clock keeps the offset to the local uptime and estimates the drift of the
+//! local oscillator, so time tags and telemetry stamps stay accurate between
+//! syncs. Telemetry frames end in a stamp `[reference, time (u32 le)]` in the
+//! time tag format, with uptime as reference until the first sync. The payload
+//! is zero padded up to the next CAN FD frame length, so the stamp is always
+//! the last five bytes of the frame
+//!
+//! Time syncs are not authenticated. A jump would move the execution of the
+//! queued telecommands tagged with mission time, so jumps are refused while any
+//! are queued
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use embassy_time::{Duration, Instant};
+use heapless::Vec;
+use south_common::telemetry::eps as tm;
+
+use crate::{EpsTMContainer, telecommand::TimeTag};
+
+/// shortest time between the syncs a drift is measured from, shorter ones
+/// mostly measure the jitter of the broadcast
+const DRIFT_INTERVAL: Duration = Duration::from_secs(60);
+/// drift of an oscillator that works at all
+const MAX_DRIFT_PPM: i64 = 20_000;
+/// weight of the previous drift estimate against a new measurement
+const DRIFT_FILTER: i32 = 4;
+/// deviation at which the mission time is taken to have jumped, e.g. after
+/// the flight computer restarted, instead of drifted
+const JUMP_MS: i32 = 1_000;
+/// payload lengths a CAN FD frame can have
+pub const FRAME_LENS: [usize; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 12, 16, 20, 24, 32, 48, 64];
+/// maximum payload of a CAN FD frame
+pub const MAX_FRAME_LEN: usize = FRAME_LENS[FRAME_LENS.len() - 1];
+
+/// Deviation of a sync from the predicted mission time and the drift estimate after it
+#[derive(Clone, Copy)]
+pub struct SyncReport {
+    pub error_ms: i32,
+    pub drift_ppm: i32,
+}
+
+impl SyncReport {
+    /// sync telemetry, packed as `[error ms (i16 le), drift ppm (i16 le)]`, saturated
+    pub fn container(&self) -> EpsTMContainer {
+        let saturate = |value: i32| value.clamp(i16::MIN.into(), i16::MAX.into()) as i16;
+        let [error_low, error_high] = saturate(self.error_ms).to_le_bytes();
+        let [drift_low, drift_high] = saturate(self.drift_ppm).to_le_bytes();
+        let report = u32::from_le_bytes([error_low, error_high, drift_low, drift_high]);
+        EpsTMContainer::new(&tm::TimeSync, &report).unwrap()
+    }
+}
+
+/// Mapping between uptime and mission time in ms, derived from time syncs
+#[derive(Clone, Copy, Default)]
+pub struct MissionClock {
+    // uptime and mission time of the last sync
+    last_sync: Option<(Instant, u32)>,
+    // sync the next drift measurement is taken from
+    drift_ref: Option<(Instant, u32)>,
+    // how much faster mission time runs than the local clock
+    drift_ppm: i32,
+    // jumps are refused while set
+    jumps_held: bool,
+}
+
+impl MissionClock {
+    pub const fn new() -> Self {
+        Self {
+            last_sync: None,
+            drift_ref: None,
+            drift_ppm: 0,
+            jumps_held: false,
+        }
+    }
+    pub fn drift_ppm(&self) -> i32 {
+        self.drift_ppm
+    }
+    /// mission time at the local instant `at`, `None` before the first sync
+    pub fn mission_ms(&self, at: Instant) -> Option<u32> {
+        let (synced_at, synced_ms) = self.last_sync?;
+        let elapsed_us = at.as_micros() as i64 - synced_at.as_micros() as i64;
+        let mission_us = elapsed_us + elapsed_us * self.drift_ppm as i64 / 1_000_000;
+        Some((synced_ms as i64 + mission_us.div_euclid(1_000)) as u32)
+    }
+    /// local instant of a mission time, `None` before the first sync
+    pub fn instant(&self, mission_ms: u32) -> Option<Instant> {
+        let (synced_at, synced_ms) = self.last_sync?;
+        let mission_us = mission_ms.wrapping_sub(synced_ms) as i32 as i64 * 1_000;
+        let local_us = mission_us * 1_000_000 / (1_000_000 + self.drift_ppm as i64);
+        let at_us = synced_at.as_micros() as i64 + local_us;
+        Some(Instant::from_micros(at_us.max(0) as u64))
+    }
+    /// refuse syncs that jump the mission time while `held`
+    pub fn hold_jumps(&mut self, held: bool) {
+        self.jumps_held = held;
+    }
+    /// take over the mission time broadcast at `now`. A refused jump is reported
+    /// with its error and leaves the clock as it is
+    pub fn sync(&mut self, now: Instant, mission_ms: u32) -> SyncReport {
+        let error_ms = self
+            .mission_ms(now)
+            .map_or(0, |predicted| mission_ms.wrapping_sub(predicted) as i32);
+        if self.jumps_held && error_ms.abs() > JUMP_MS {
+            return SyncReport {
+                error_ms,
+                drift_ppm: self.drift_ppm,
+            };
+        }
+        match self.drift_ref {
+            Some(_) if error_ms.abs() > JUMP_MS => self.drift_ref = Some((now, mission_ms)),
+            Some((ref_at, ref_ms)) if now - ref_at >= DRIFT_INTERVAL => {
+                let local_us = (now - ref_at).as_micros() as i64;
+                let mission_us = mission_ms.wrapping_sub(ref_ms) as i64 * 1_000;
+                let measured = ((mission_us - local_us) * 1_000_000 / local_us)
+                    .clamp(-MAX_DRIFT_PPM, MAX_DRIFT_PPM) as i32;
+                self.drift_ppm += (measured - self.drift_ppm) / DRIFT_FILTER;
+                self.drift_ref = Some((now, mission_ms));
+            }
+            Some(_) => (),
+            None => self.drift_ref = Some((now, mission_ms)),
+        }
+        self.last_sync = Some((now, mission_ms));
+        SyncReport {
+            error_ms,
+            drift_ppm: self.drift_ppm,
+        }
+    }
+}
+
+// shared by the telecommand, telemetry and control loop tasks
+static CLOCK: Mutex<CriticalSectionRawMutex, Cell<MissionClock>> =
+    Mutex::new(Cell::new(MissionClock::new()));
+
+fn clock() -> MissionClock {
+    CLOCK.lock(Cell::get)
+}
+
+/// take over a time sync frame of the flight computer, `[mission time ms (u32 le)]`
+pub fn sync(frame: &[u8]) -> Option<SyncReport> {
+    let mission_ms = u32::from_le_bytes(frame.get(..4)?.try_into().unwrap());
+    Some(CLOCK.lock(|cell| {
+        let mut clock = cell.get();
+        let report = clock.sync(Instant::now(), mission_ms);
+        cell.set(clock);
+        report
+    }))
+}
+/// refuse syncs that jump the mission time while `held`, set while telecommands
+/// tagged with mission time are queued
+pub fn hold_jumps(held: bool) {
+    CLOCK.lock(|cell| {
+        let mut clock = cell.get();
+        clock.hold_jumps(held);
+        cell.set(clock);
+    });
+}
+/// local instant of a mission time, `None` before the first sync
+pub fn instant(mission_ms: u32) -> Option<Instant> {
+    clock().instant(mission_ms)
+}
+/// current mission time, uptime until the first sync
+pub fn now() -> TimeTag {
+    let now = Instant::now();
+    match clock().mission_ms(now) {
+        Some(mission_ms) => TimeTag::Mission(mission_ms),
+        None => TimeTag::Boot(now.as_millis() as u32),
+    }
+}
+/// telemetry payload padded to a valid frame length with the current time
+/// stamp at the end, `None` if the payload leaves no room for the stamp
+pub fn stamped(payload: &[u8]) -> Option<Vec<u8, MAX_FRAME_LEN>> {
+    let stamp = now().write();
+    let len = FRAME_LENS
+        .into_iter()
+        .find(|len| *len >= payload.len() + stamp.len())?;
+    let mut frame = Vec::from_slice(payload).ok()?;
+    frame.resize(len - stamp.len(), 0).ok()?;
+    frame.extend_from_slice(&stamp).ok()?;
+    Some(frame)
+}
@@ -0,0 +1,6 @@
+//! Hardware mocks used to run the EPS logic on the build machine
+
+pub mod adc;
+pub mod flash;
+pub mod gpio;
+pub mod i2c;
@@ -0,0 +1,39 @@
+use core::cell::Cell;
+
+use crate::adc::AdcSampler;
+
+/// Index of the analog input read by a mocked channel
+pub struct MockAdcChannel(pub u8);
+
+/// ADC returning the raw values currently set on its inputs.
+/// The last input is wired to the internal temperature sensor
+pub struct MockAdc<'a> {
+    inputs: &'a [Cell<u16>],
+}
+impl<'a> MockAdc<'a> {
+    pub fn new(inputs: &'a [Cell<u16>]) -> Self {
+        Self { inputs }
+    }
+}
+
+impl AdcSampler for MockAdc<'_> {
+    type Channel = MockAdcChannel;
+
+    fn temperature_channel(&mut self) -> Self::Channel {
+        MockAdcChannel(self.inputs.len() as u8 - 1)
+    }
+    fn hw_channel(channel: &Self::Channel) -> u8 {
+        channel.0
+    }
+    async fn read<'c>(
+        &mut self,
+        sequence: impl ExactSizeIterator<Item = &'c mut Self::Channel>,
+        readings: &mut [u16],
+    ) where
+        Self::Channel: 'c,
+    {
+        sequence
+            .zip(readings)
+            .for_each(|(c, r)| *r = self.inputs[c.0 as usize].get());
+    }
+}
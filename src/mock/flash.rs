@@ -0,0 +1,68 @@
+use core::cell::Cell;
+
+use embedded_storage::nor_flash::{
+    ErrorType, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash, check_erase, check_read,
+    check_write,
+};
+
+/// Flash page size of the STM32G0B1
+pub const MOCK_PAGE_SIZE: usize = 2048;
+
+#[derive(Debug)]
+pub struct MockFlashError(NorFlashErrorKind);
+impl NorFlashError for MockFlashError {
+    fn kind(&self) -> NorFlashErrorKind {
+        self.0
+    }
+}
+
+/// NOR flash on top of shared memory cells. Like the real flash, writes can
+/// only clear bits and erasing sets whole pages back to `0xFF`
+pub struct MockFlash<'a> {
+    memory: &'a [Cell<u8>],
+}
+impl<'a> MockFlash<'a> {
+    pub fn new(memory: &'a [Cell<u8>]) -> Self {
+        Self { memory }
+    }
+}
+impl ErrorType for MockFlash<'_> {
+    type Error = MockFlashError;
+}
+impl ReadNorFlash for MockFlash<'_> {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        check_read(self, offset, bytes.len()).map_err(MockFlashError)?;
+        let memory = &self.memory[offset as usize..];
+        bytes
+            .iter_mut()
+            .zip(memory)
+            .for_each(|(b, cell)| *b = cell.get());
+        Ok(())
+    }
+    fn capacity(&self) -> usize {
+        self.memory.len()
+    }
+}
+impl NorFlash for MockFlash<'_> {
+    const WRITE_SIZE: usize = 8;
+    const ERASE_SIZE: usize = MOCK_PAGE_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        check_erase(self, from, to).map_err(MockFlashError)?;
+        self.memory[from as usize..to as usize]
+            .iter()
+            .for_each(|cell| cell.set(0xFF));
+        Ok(())
+    }
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        check_write(self, offset, bytes.len()).map_err(MockFlashError)?;
+        let memory = &self.memory[offset as usize..];
+        bytes
+            .iter()
+            .zip(memory)
+            .for_each(|(b, cell)| cell.set(cell.get() & b));
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use core::{cell::Cell, convert::Infallible};
+
+use embedded_hal::digital::{ErrorType, InputPin, OutputPin, StatefulOutputPin};
+
+/// Logic level of a single wire shared between mocked pins
+#[derive(Default)]
+pub struct MockNet {
+    level: Cell<bool>,
+}
+impl MockNet {
+    pub const fn new(level: bool) -> Self {
+        Self {
+            level: Cell::new(level),
+        }
+    }
+    pub fn is_high(&self) -> bool {
+        self.level.get()
+    }
+    pub fn set(&self, level: bool) {
+        self.level.set(level)
+    }
+}
+
+/// Push pull output driving a [`MockNet`]
+pub struct MockOutputPin<'a> {
+    net: &'a MockNet,
+    clocks: Option<&'a MockFlipFlopChip>,
+}
+impl<'a> MockOutputPin<'a> {
+    pub fn new(net: &'a MockNet) -> Self {
+        Self { net, clocks: None }
+    }
+    /// output connected to the clock input of a flip flop chip
+    pub fn clock(chip: &'a MockFlipFlopChip) -> Self {
+        Self {
+            net: &chip.clk,
+            clocks: Some(chip),
+        }
+    }
+}
+impl ErrorType for MockOutputPin<'_> {
+    type Error = Infallible;
+}
+impl OutputPin for MockOutputPin<'_> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.net.set(false);
+        Ok(())
+    }
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        if let Some(chip) = self.clocks
+            && !self.net.is_high()
+        {
+            chip.latch();
+        }
+        self.net.set(true);
+        Ok(())
+    }
+}
+impl StatefulOutputPin for MockOutputPin<'_> {
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.net.is_high())
+    }
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.net.is_high())
+    }
+}
+
+/// Input sampling a [`MockNet`]
+pub struct MockInputPin<'a> {
+    net: &'a MockNet,
+}
+impl<'a> MockInputPin<'a> {
+    pub fn new(net: &'a MockNet) -> Self {
+        Self { net }
+    }
+}
+impl ErrorType for MockInputPin<'_> {
+    type Error = Infallible;
+}
+impl InputPin for MockInputPin<'_> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.net.is_high())
+    }
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.net.is_high())
+    }
+}
+
+/// Triple D flip flop of the source power path.
+/// The d inputs are latched on the rising clock edge, the state outputs are inverted
+/// so a low d input enables the source. All sources are enabled at power up
+pub struct MockFlipFlopChip {
+    pub d: [MockNet; 3],
+    pub state: [MockNet; 3],
+    clk: MockNet,
+}
+impl MockFlipFlopChip {
+    pub const fn new() -> Self {
+        Self {
+            d: [
+                MockNet::new(false),
+                MockNet::new(false),
+                MockNet::new(false),
+            ],
+            state: [MockNet::new(true), MockNet::new(true), MockNet::new(true)],
+            clk: MockNet::new(false),
+        }
+    }
+    fn latch(&self) {
+        self.d
+            .iter()
+            .zip(&self.state)
+            .for_each(|(d, state)| state.set(!d.is_high()));
+    }
+    /// (d, state) pin pairs for bat 1, bat 2 and aux pwr followed by the clock pin
+    #[allow(clippy::type_complexity)]
+    pub fn pins(
+        &self,
+    ) -> (
+        (MockOutputPin<'_>, MockInputPin<'_>),
+        (MockOutputPin<'_>, MockInputPin<'_>),
+        (MockOutputPin<'_>, MockInputPin<'_>),
+        MockOutputPin<'_>,
+    ) {
+        let pair = |i: usize| {
+            (
+                MockOutputPin::new(&self.d[i]),
+                MockInputPin::new(&self.state[i]),
+            )
+        };
+        (pair(0), pair(1), pair(2), MockOutputPin::clock(self))
+    }
+}
@@ -0,0 +1,109 @@
+use core::cell::Cell;
+
+use embedded_hal_async::i2c::{ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation};
+
+use crate::i2c_bus::BusRecovery;
+
+/// Register model of a TMP100 answering on a fixed address
+pub struct MockTmp100 {
+    addr: u8,
+    present: Cell<bool>,
+    pointer: Cell<u8>,
+    registers: [Cell<u16>; 4],
+}
+impl MockTmp100 {
+    pub const fn new(addr: u8) -> Self {
+        Self {
+            addr,
+            present: Cell::new(true),
+            pointer: Cell::new(0),
+            registers: [
+                Cell::new(0),
+                Cell::new(0),
+                Cell::new(75 << 8),
+                Cell::new(80 << 8),
+            ],
+        }
+    }
+    /// set the temperature register in tenth degrees celsius
+    pub fn set_temperature_tenth_deg(&self, temp: i16) {
+        let raw = (temp as i32 * 256 / 10) as i16;
+        self.registers[0].set(raw as u16)
+    }
+    /// unplug or reconnect the device, a missing device does not acknowledge
+    pub fn set_present(&self, present: bool) {
+        self.present.set(present)
+    }
+    pub fn register(&self, pointer: u8) -> u16 {
+        self.registers[pointer as usize & 0b11].get()
+    }
+    fn write(&self, bytes: &[u8]) {
+        let Some((pointer, data)) = bytes.split_first() else {
+            return;
+        };
+        self.pointer.set(pointer & 0b11);
+        match data {
+            // config register is a single byte wide
+            [config] if self.pointer.get() == 1 => self.registers[1].set((*config as u16) << 8),
+            [msb, lsb, ..] if self.pointer.get() > 1 => {
+                self.registers[self.pointer.get() as usize].set(u16::from_be_bytes([*msb, *lsb]))
+            }
+            _ => (),
+        }
+    }
+    fn read(&self, buffer: &mut [u8]) {
+        let value = self.registers[self.pointer.get() as usize]
+            .get()
+            .to_be_bytes();
+        buffer
+            .iter_mut()
+            .zip(value.iter().cycle())
+            .for_each(|(b, v)| *b = *v);
+    }
+}
+
+/// I2C bus with mocked TMP100 devices, transfers to any other address are not acknowledged
+pub struct MockI2c<'a> {
+    devices: &'a [MockTmp100],
+    recoveries: u32,
+}
+impl<'a> MockI2c<'a> {
+    pub fn new(devices: &'a [MockTmp100]) -> Self {
+        Self {
+            devices,
+            recoveries: 0,
+        }
+    }
+    /// number of bus recoveries performed
+    pub fn recoveries(&self) -> u32 {
+        self.recoveries
+    }
+}
+impl BusRecovery for MockI2c<'_> {
+    async fn recover(&mut self) {
+        self.recoveries += 1;
+    }
+}
+impl ErrorType for MockI2c<'_> {
+    type Error = ErrorKind;
+}
+impl I2c for MockI2c<'_> {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let device = self
+            .devices
+            .iter()
+            .find(|d| d.addr == address && d.present.get())
+            .ok_or(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address))?;
+        for operation in operations {
+            match operation {
+                Operation::Read(buffer) => device.read(buffer),
+                Operation::Write(bytes) => device.write(bytes),
+            }
+        }
+        Ok(())
+    }
+}
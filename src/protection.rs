@@ -0,0 +1,96 @@
+//! Autonomous protection, limits are checked by the measuring tasks which report
+//! status changes to the control loop owning the power path
+pub mod overcurrent;
+pub mod temperature;
+pub mod voltage;
+
+use defmt::Format;
+use south_common::types::Sink;
+
+use crate::pwr_src::d_flip_flop::FlipFlopInput;
+use overcurrent::FuseStatus;
+use temperature::{FaultResponse, TemperatureStatus};
+use voltage::VoltageStatus;
+
+#[derive(Clone, Copy)]
+pub enum ProtectionEvent {
+    /// battery voltage left or reentered its allowed window
+    BatteryVoltage(FlipFlopInput, VoltageStatus),
+    /// battery temperature left or reentered its allowed window,
+    /// with the response configured for the new status
+    BatteryTemperature(FlipFlopInput, TemperatureStatus, FaultResponse),
+    /// electronic fuse of a sink tripped, latched or closed again
+    SinkFuse(Sink, FuseStatus),
+    /// aux power appeared within or left its voltage window
+    AuxVoltage(VoltageStatus),
+}
+
+impl ProtectionEvent {
+    /// source the event refers to, `None` for sink events
+    pub fn input(&self) -> Option<FlipFlopInput> {
+        match self {
+            Self::BatteryVoltage(input, _) | Self::BatteryTemperature(input, _, _) => Some(*input),
+            Self::AuxVoltage(_) => Some(FlipFlopInput::AuxPwr),
+            Self::SinkFuse(_, _) => None,
+        }
+    }
+    /// true if the event takes a battery or sink out of the power path, aux
+    /// power is expected to go away at launch
+    pub fn is_fault(&self) -> bool {
+        !matches!(
+            self.reason(),
+            SwitchReason::BatteryRecovered
+                | SwitchReason::FuseClosed
+                | SwitchReason::AuxPowerLost
+                | SwitchReason::AuxPowerRestored
+        )
+    }
+    pub fn reason(&self) -> SwitchReason {
+        match self {
+            Self::BatteryVoltage(_, VoltageStatus::Nominal)
+            | Self::BatteryTemperature(_, TemperatureStatus::Nominal, _) => {
+                SwitchReason::BatteryRecovered
+            }
+            Self::BatteryVoltage(_, VoltageStatus::Undervoltage) => {
+                SwitchReason::BatteryUndervoltage
+            }
+            Self::BatteryVoltage(_, VoltageStatus::Overvoltage) => SwitchReason::BatteryOvervoltage,
+            Self::BatteryTemperature(_, TemperatureStatus::Undertemperature, _) => {
+                SwitchReason::BatteryUndertemperature
+            }
+            Self::BatteryTemperature(_, TemperatureStatus::Overtemperature, _) => {
+                SwitchReason::BatteryOvertemperature
+            }
+            Self::BatteryTemperature(_, TemperatureStatus::ProbeFailure, _) => {
+                SwitchReason::TemperatureProbeFailure
+            }
+            Self::SinkFuse(_, FuseStatus::Closed) => SwitchReason::FuseClosed,
+            Self::SinkFuse(_, FuseStatus::Tripped) => SwitchReason::FuseTripped,
+            Self::SinkFuse(_, FuseStatus::Latched) => SwitchReason::FuseLatched,
+            Self::AuxVoltage(VoltageStatus::Nominal) => SwitchReason::AuxPowerRestored,
+            Self::AuxVoltage(_) => SwitchReason::AuxPowerLost,
+        }
+    }
+}
+
+/// Cause of an autonomous power path change, reported in event telemetry
+#[repr(u8)]
+#[derive(Format, Clone, Copy)]
+pub enum SwitchReason {
+    BatteryUndervoltage,
+    BatteryOvervoltage,
+    BatteryRecovered,
+    BatteryUndertemperature,
+    BatteryOvertemperature,
+    TemperatureProbeFailure,
+    FuseTripped,
+    FuseLatched,
+    /// fuse retried or reset by telecommand
+    FuseClosed,
+    /// bus voltage or battery charge below a load shedding threshold
+    PowerDegraded,
+    PowerRecovered,
+    /// aux power left its voltage window, e.g. at umbilical separation
+    AuxPowerLost,
+    AuxPowerRestored,
+}
@@ -0,0 +1,167 @@
+use defmt::Format;
+use embassy_time::{Duration, Instant};
+
+use crate::pwr_src::sink_ctrl::SINKS;
+
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum FuseStatus {
+    Closed,
+    /// tripped, closed again once the retry delay expired
+    Tripped,
+    /// tripped too often, stays open until reset by telecommand
+    Latched,
+}
+
+/// Behaviour of a fuse after a trip
+#[derive(Clone, Copy)]
+pub enum RetryPolicy {
+    /// latch on the first trip
+    LatchOff,
+    /// retry after a fixed delay, latch after `max_retries` consecutive trips
+    Retry { max_retries: u8, delay: Duration },
+    /// retry after a delay doubling with every consecutive trip
+    Backoff {
+        max_retries: u8,
+        initial_delay: Duration,
+        max_delay: Duration,
+    },
+}
+
+/// Current limit of a rail, currents in mA
+#[derive(Clone, Copy)]
+pub struct FuseLimits {
+    pub limit_ma: i16,
+    /// time the current has to stay above the limit before the fuse trips
+    pub trip_delay: Duration,
+    pub retry: RetryPolicy,
+}
+
+const SINK_FUSE_RETRY: RetryPolicy = RetryPolicy::Backoff {
+    max_retries: 4,
+    initial_delay: Duration::from_secs(1),
+    max_delay: Duration::from_secs(16),
+};
+
+/// limits of the sink rails in [`SINKS`] order
+pub const SINK_FUSE_LIMITS: [FuseLimits; SINKS.len()] = [
+    // RocketLST
+    FuseLimits {
+        limit_ma: 1_500,
+        trip_delay: Duration::from_millis(200),
+        retry: SINK_FUSE_RETRY,
+    },
+    // SensorUpper
+    FuseLimits {
+        limit_ma: 1_000,
+        trip_delay: Duration::from_millis(200),
+        retry: SINK_FUSE_RETRY,
+    },
+    // GPS
+    FuseLimits {
+        limit_ma: 500,
+        trip_delay: Duration::from_millis(200),
+        retry: SINK_FUSE_RETRY,
+    },
+    // RocketHD
+    FuseLimits {
+        limit_ma: 2_000,
+        trip_delay: Duration::from_millis(200),
+        retry: SINK_FUSE_RETRY,
+    },
+];
+
+// a fuse closed this long without tripping forgets its previous trips
+const FUSE_HEALTHY_TIME: Duration = Duration::from_secs(60);
+
+/// Software electronic fuse of a single rail
+pub struct EFuse {
+    limits: FuseLimits,
+    status: FuseStatus,
+    over_limit_since: Option<Instant>,
+    closed_since: Instant,
+    retry_at: Instant,
+    retries: u8,
+}
+
+impl EFuse {
+    pub fn new(limits: FuseLimits) -> Self {
+        Self {
+            limits,
+            status: FuseStatus::Closed,
+            over_limit_since: None,
+            closed_since: Instant::now(),
+            retry_at: Instant::MAX,
+            retries: 0,
+        }
+    }
+    pub fn status(&self) -> FuseStatus {
+        self.status
+    }
+    /// consecutive trips since the fuse was last healthy or reset
+    pub fn retries(&self) -> u8 {
+        self.retries
+    }
+    fn retry_delay(&self) -> Option<Duration> {
+        match self.limits.retry {
+            RetryPolicy::LatchOff => None,
+            RetryPolicy::Retry { max_retries, delay } => {
+                (self.retries < max_retries).then_some(delay)
+            }
+            RetryPolicy::Backoff {
+                max_retries,
+                initial_delay,
+                max_delay,
+            } => (self.retries < max_retries).then(|| {
+                // saturates instead of overflowing for large configured retry counts
+                let factor = 1u32.checked_shl(self.retries as u32).unwrap_or(u32::MAX);
+                initial_delay
+                    .checked_mul(factor)
+                    .map_or(max_delay, |delay| delay.min(max_delay))
+            }),
+        }
+    }
+    fn close(&mut self, now: Instant) -> FuseStatus {
+        self.status = FuseStatus::Closed;
+        self.over_limit_since = None;
+        self.closed_since = now;
+        self.status
+    }
+    fn trip(&mut self, now: Instant) -> FuseStatus {
+        self.status = match self.retry_delay() {
+            Some(delay) => {
+                self.retries += 1;
+                self.retry_at = now + delay;
+                FuseStatus::Tripped
+            }
+            None => FuseStatus::Latched,
+        };
+        self.status
+    }
+    /// feed a new current sample, returns the new status on a change
+    pub fn update(&mut self, current_ma: i16) -> Option<FuseStatus> {
+        let now = Instant::now();
+        match self.status {
+            FuseStatus::Closed if current_ma > self.limits.limit_ma => {
+                let since = *self.over_limit_since.get_or_insert(now);
+                (now - since >= self.limits.trip_delay).then(|| self.trip(now))
+            }
+            FuseStatus::Closed => {
+                self.over_limit_since = None;
+                if now - self.closed_since >= FUSE_HEALTHY_TIME {
+                    self.retries = 0;
+                }
+                None
+            }
+            FuseStatus::Tripped if now >= self.retry_at => Some(self.close(now)),
+            FuseStatus::Tripped | FuseStatus::Latched => None,
+        }
+    }
+    /// close a tripped or latched fuse, returns the new status on a change
+    pub fn reset(&mut self) -> Option<FuseStatus> {
+        if self.status == FuseStatus::Closed {
+            return None;
+        }
+        self.retries = 0;
+        Some(self.close(Instant::now()))
+    }
+}
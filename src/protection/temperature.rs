@@ -0,0 +1,120 @@
+use defmt::Format;
+
+use crate::pwr_src::sink_ctrl::SinkSet;
+
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum TemperatureStatus {
+    Nominal,
+    Undertemperature,
+    Overtemperature,
+    /// probe missing or not answering
+    ProbeFailure,
+}
+
+/// Reaction of the control loop to a battery fault
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum FaultResponse {
+    /// report only
+    Ignore,
+    /// drop the battery from the power path
+    Disconnect,
+    /// disable the given sinks until the fault clears
+    ShedSinks(SinkSet),
+}
+
+/// Temperature window with hysteresis, all temperatures in tenth degrees celsius
+#[derive(Clone, Copy)]
+pub struct TemperatureLimits {
+    pub min_tenth_deg: i16,
+    pub max_tenth_deg: i16,
+    /// distance to the limit needed to leave a fault
+    pub hysteresis_tenth_deg: i16,
+    /// consecutive samples needed before a status change is reported
+    pub debounce_samples: u8,
+    pub limit_response: FaultResponse,
+    /// fail safe policy for missing probes and read errors
+    pub probe_failure_response: FaultResponse,
+}
+
+/// discharge window of li-ion cells
+pub const BATTERY_TEMPERATURE_LIMITS: TemperatureLimits = TemperatureLimits {
+    min_tenth_deg: -20_0,
+    max_tenth_deg: 60_0,
+    hysteresis_tenth_deg: 5_0,
+    debounce_samples: 4,
+    limit_response: FaultResponse::Disconnect,
+    probe_failure_response: FaultResponse::Ignore,
+};
+
+/// Debounced temperature window and probe health check
+pub struct TemperatureMonitor {
+    limits: TemperatureLimits,
+    status: TemperatureStatus,
+    candidate: TemperatureStatus,
+    candidate_samples: u8,
+}
+
+impl TemperatureMonitor {
+    pub fn new(limits: TemperatureLimits) -> Self {
+        Self {
+            limits,
+            status: TemperatureStatus::Nominal,
+            candidate: TemperatureStatus::Nominal,
+            candidate_samples: 0,
+        }
+    }
+    pub fn status(&self) -> TemperatureStatus {
+        self.status
+    }
+    /// configured response to a status, nominal needs no response
+    pub fn response(&self, status: TemperatureStatus) -> FaultResponse {
+        match status {
+            TemperatureStatus::Nominal => FaultResponse::Ignore,
+            TemperatureStatus::Undertemperature | TemperatureStatus::Overtemperature => {
+                self.limits.limit_response
+            }
+            TemperatureStatus::ProbeFailure => self.limits.probe_failure_response,
+        }
+    }
+    fn classify(&self, temperature: Option<i16>) -> TemperatureStatus {
+        let limits = &self.limits;
+        let Some(temp) = temperature else {
+            return TemperatureStatus::ProbeFailure;
+        };
+        match self.status {
+            _ if temp < limits.min_tenth_deg => TemperatureStatus::Undertemperature,
+            _ if temp > limits.max_tenth_deg => TemperatureStatus::Overtemperature,
+            TemperatureStatus::Undertemperature
+                if temp < limits.min_tenth_deg + limits.hysteresis_tenth_deg =>
+            {
+                TemperatureStatus::Undertemperature
+            }
+            TemperatureStatus::Overtemperature
+                if temp > limits.max_tenth_deg - limits.hysteresis_tenth_deg =>
+            {
+                TemperatureStatus::Overtemperature
+            }
+            _ => TemperatureStatus::Nominal,
+        }
+    }
+    /// feed a new reading, `None` if the probe failed.
+    /// returns the new status once a change is debounced
+    pub fn update(&mut self, temperature: Option<i16>) -> Option<TemperatureStatus> {
+        let measured = self.classify(temperature);
+        if measured == self.status {
+            self.candidate_samples = 0;
+            return None;
+        }
+        if measured != self.candidate {
+            self.candidate = measured;
+            self.candidate_samples = 0;
+        }
+        self.candidate_samples += 1;
+        if self.candidate_samples < self.limits.debounce_samples {
+            return None;
+        }
+        self.candidate_samples = 0;
+        self.status = measured;
+        Some(measured)
+    }
+}
@@ -0,0 +1,101 @@
+use defmt::Format;
+
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum VoltageStatus {
+    Nominal,
+    Undervoltage,
+    Overvoltage,
+}
+
+/// Voltage window with hysteresis, all voltages in 10mV
+#[derive(Clone, Copy)]
+pub struct VoltageLimits {
+    pub under_10mv: i16,
+    pub under_recover_10mv: i16,
+    pub over_10mv: i16,
+    pub over_recover_10mv: i16,
+    /// consecutive samples needed before a status change is reported
+    pub debounce_samples: u8,
+}
+
+/// limits of a 2S li-ion battery pack
+pub const BATTERY_VOLTAGE_LIMITS: VoltageLimits = VoltageLimits {
+    under_10mv: 6_00,
+    under_recover_10mv: 6_40,
+    over_10mv: 8_50,
+    over_recover_10mv: 8_30,
+    debounce_samples: 4,
+};
+
+/// window of the ground supply fed in through the umbilical
+pub const AUX_PWR_VOLTAGE_LIMITS: VoltageLimits = VoltageLimits {
+    under_10mv: 6_00,
+    under_recover_10mv: 6_40,
+    over_10mv: 9_00,
+    over_recover_10mv: 8_80,
+    debounce_samples: 4,
+};
+
+/// Debounced voltage window check
+pub struct VoltageMonitor {
+    limits: VoltageLimits,
+    status: VoltageStatus,
+    candidate: VoltageStatus,
+    candidate_samples: u8,
+}
+
+impl VoltageMonitor {
+    pub fn new(limits: VoltageLimits) -> Self {
+        Self {
+            limits,
+            status: VoltageStatus::Nominal,
+            candidate: VoltageStatus::Nominal,
+            candidate_samples: 0,
+        }
+    }
+    /// monitor of a source that is not taken as present until it is measured
+    /// within its limits
+    pub fn absent(limits: VoltageLimits) -> Self {
+        Self {
+            status: VoltageStatus::Undervoltage,
+            candidate: VoltageStatus::Undervoltage,
+            ..Self::new(limits)
+        }
+    }
+    pub fn status(&self) -> VoltageStatus {
+        self.status
+    }
+    fn classify(&self, voltage_10mv: i16) -> VoltageStatus {
+        let limits = &self.limits;
+        match self.status {
+            _ if voltage_10mv < limits.under_10mv => VoltageStatus::Undervoltage,
+            _ if voltage_10mv > limits.over_10mv => VoltageStatus::Overvoltage,
+            VoltageStatus::Undervoltage if voltage_10mv < limits.under_recover_10mv => {
+                VoltageStatus::Undervoltage
+            }
+            VoltageStatus::Overvoltage if voltage_10mv > limits.over_recover_10mv => {
+                VoltageStatus::Overvoltage
+            }
+            _ => VoltageStatus::Nominal,
+        }
+    }
+    /// feed a new sample, returns the new status once a change is debounced
+    pub fn update(&mut self, voltage_10mv: i16) -> Option<VoltageStatus> {
+        let measured = self.classify(voltage_10mv);
+        if measured == self.status {
+            self.candidate_samples = 0;
+            return None;
+        }
+        if measured != self.candidate {
+            self.candidate = measured;
+            self.candidate_samples = 0;
+        }
+        self.candidate_samples += 1;
+        if self.candidate_samples < self.limits.debounce_samples {
+            return None;
+        }
+        self.candidate_samples = 0;
+        self.status = measured;
+        Some(measured)
+    }
+}
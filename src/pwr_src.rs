@@ -2,3 +2,4 @@ pub mod aux_pwr;
 pub mod battery;
 pub mod d_flip_flop;
 pub mod sink_ctrl;
+pub mod sink_monitor;
@@ -3,41 +3,85 @@ use embassy_sync::{channel::DynamicSender, watch::DynReceiver};
 use embassy_time::{Duration, Instant, Timer};
 use south_common::telemetry::eps as tm;
 
-use crate::EpsTMContainer;
+use crate::{
+    EpsTMContainer,
+    adc::{conversion::calculate_power_10mw, stats::SharedStatistics},
+    protection::{
+        ProtectionEvent,
+        voltage::{AUX_PWR_VOLTAGE_LIMITS, VoltageMonitor},
+    },
+};
 
 // Aux pwr task
 #[embassy_executor::task]
-pub async fn aux_pwr_thread(mut aux_pwr: AuxPwr<'static>) {
-    const AUX_LOOP_LEN: Duration = Duration::from_millis(500);
+pub async fn aux_pwr_thread(mut aux_pwr: AuxPwr<'static>, loop_len: Duration) {
     let mut loop_time = Instant::now();
     loop {
         aux_pwr.run().await;
-        loop_time += AUX_LOOP_LEN;
+        loop_time += loop_len;
         Timer::at(loop_time).await;
     }
 }
 
 pub struct AuxPwr<'a> {
     adc_recv: DynReceiver<'a, i16>,
+    current_recv: DynReceiver<'a, i16>,
+    voltage_stats: &'a SharedStatistics,
+    current_stats: &'a SharedStatistics,
+    voltage_monitor: VoltageMonitor,
+    protection_sender: DynamicSender<'a, ProtectionEvent>,
     tm_sender: DynamicSender<'a, EpsTMContainer>,
 }
 
 impl<'a> AuxPwr<'a> {
     pub async fn new(
         adc_recv: DynReceiver<'a, i16>,
+        current_recv: DynReceiver<'a, i16>,
+        voltage_stats: &'a SharedStatistics,
+        current_stats: &'a SharedStatistics,
+        protection_sender: DynamicSender<'a, ProtectionEvent>,
         tm_sender: DynamicSender<'a, EpsTMContainer>,
     ) -> Self {
         Self {
             adc_recv,
+            current_recv,
+            voltage_stats,
+            current_stats,
+            voltage_monitor: VoltageMonitor::absent(AUX_PWR_VOLTAGE_LIMITS),
+            protection_sender,
             tm_sender,
         }
     }
     async fn get_voltage(&mut self) -> i16 {
         self.adc_recv.get().await
     }
+    async fn get_current(&mut self) -> i16 {
+        self.current_recv.get().await
+    }
     pub async fn run(&mut self) {
-        let container =
-            EpsTMContainer::new(&tm::AuxPowerVoltage, &self.get_voltage().await).unwrap();
+        let voltage = self.get_voltage().await;
+        if let Some(status) = self.voltage_monitor.update(voltage) {
+            self.protection_sender
+                .send(ProtectionEvent::AuxVoltage(status))
+                .await;
+        }
+        let container = EpsTMContainer::new(&tm::AuxPowerVoltage, &voltage).unwrap();
+        self.tm_sender.send(container).await;
+
+        let current = self.get_current().await;
+        let container = EpsTMContainer::new(&tm::AuxPowerCurrent, &current).unwrap();
+        self.tm_sender.send(container).await;
+        let power = calculate_power_10mw(voltage, current);
+        let container = EpsTMContainer::new(&tm::AuxPowerPower, &power).unwrap();
         self.tm_sender.send(container).await;
+
+        let stats = self.voltage_stats.take();
+        self.tm_sender
+            .send(stats.container(&tm::AuxPowerVoltageStats))
+            .await;
+        let stats = self.current_stats.take();
+        self.tm_sender
+            .send(stats.container(&tm::AuxPowerCurrentStats))
+            .await;
     }
 }
@@ -1,65 +1,179 @@
+pub mod soc;
+pub mod temp_sensor;
 pub mod tmp100_drv;
-use embassy_stm32::{
-    i2c::{I2c, Master},
-    mode::Async,
+use embassy_sync::{
+    channel::DynamicSender,
+    watch::{DynReceiver, DynSender},
 };
-use embassy_sync::{channel::DynamicSender, watch::DynReceiver};
+#[cfg(feature = "stm32")]
 use embassy_time::{Duration, Instant, Timer};
-use tmp100_drv::Tmp100;
+use embedded_hal_async::i2c::I2c;
+use soc::SocEstimator;
+use temp_sensor::TempSensor;
 
 use south_common::TelemetryDefinition;
 
-use crate::EpsTMContainer;
+use crate::{
+    EpsTMContainer,
+    adc::{conversion::calculate_power_10mw, stats::SharedStatistics},
+    event_log::{self, Event},
+    i2c_bus::BusRecovery,
+    protection::{
+        ProtectionEvent,
+        temperature::{TemperatureLimits, TemperatureMonitor},
+        voltage::{VoltageLimits, VoltageMonitor},
+    },
+    pwr_src::d_flip_flop::FlipFlopInput,
+};
+
+#[cfg(feature = "stm32")]
+pub type BoardI2c = crate::i2c_bus::Stm32I2cBus;
 
 // Battery task
+#[cfg(feature = "stm32")]
 #[embassy_executor::task(pool_size = 2)]
-pub async fn battery_thread(mut battery: Battery<'static, 'static>) {
-    const BTRY_LOOP_LEN: Duration = Duration::from_millis(500);
+pub async fn battery_thread(mut battery: Battery<'static, BoardI2c>, loop_len: Duration) {
     let mut loop_time = Instant::now();
     loop {
         battery.run().await;
-        loop_time += BTRY_LOOP_LEN;
+        loop_time += loop_len;
         Timer::at(loop_time).await;
     }
 }
 
-pub struct Battery<'a, 'd> {
-    temp_probe: Option<Tmp100<'a, I2c<'d, Async, Master>>>,
+/// Telemetry definitions of a battery
+pub struct BatteryTopics {
+    pub temperature: &'static dyn TelemetryDefinition,
+    pub voltage: &'static dyn TelemetryDefinition,
+    pub voltage_stats: &'static dyn TelemetryDefinition,
+    pub temp_sensor_errors: &'static dyn TelemetryDefinition,
+    pub temp_sensor_state: &'static dyn TelemetryDefinition,
+    pub current: &'static dyn TelemetryDefinition,
+    pub current_stats: &'static dyn TelemetryDefinition,
+    pub power: &'static dyn TelemetryDefinition,
+    pub state_of_charge: &'static dyn TelemetryDefinition,
+    pub remaining_capacity: &'static dyn TelemetryDefinition,
+    pub time_to_empty: &'static dyn TelemetryDefinition,
+}
+
+pub struct Battery<'a, I2C: I2c + BusRecovery> {
+    input: FlipFlopInput,
+    temp_sensor: TempSensor<'a, I2C>,
     adc_recv: DynReceiver<'a, i16>,
+    current_recv: DynReceiver<'a, i16>,
+    voltage_stats: &'a SharedStatistics,
+    current_stats: &'a SharedStatistics,
+    soc: SocEstimator,
+    soc_sender: DynSender<'a, u16>,
+    voltage_monitor: VoltageMonitor,
+    temperature_monitor: TemperatureMonitor,
+    protection_sender: DynamicSender<'a, ProtectionEvent>,
     tm_sender: DynamicSender<'a, EpsTMContainer>,
-    temp_topic: &'static dyn TelemetryDefinition,
-    voltage_topic: &'static dyn TelemetryDefinition,
+    topics: BatteryTopics,
 }
 
-impl<'a, 'd> Battery<'a, 'd> {
+impl<'a, I2C: I2c + BusRecovery> Battery<'a, I2C> {
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
-        temp_probe: Option<Tmp100<'a, I2c<'d, Async, Master>>>,
+        input: FlipFlopInput,
+        temp_sensor: TempSensor<'a, I2C>,
         adc_recv: DynReceiver<'a, i16>,
+        current_recv: DynReceiver<'a, i16>,
+        voltage_stats: &'a SharedStatistics,
+        current_stats: &'a SharedStatistics,
+        soc: SocEstimator,
+        soc_sender: DynSender<'a, u16>,
+        voltage_limits: VoltageLimits,
+        temperature_limits: TemperatureLimits,
+        protection_sender: DynamicSender<'a, ProtectionEvent>,
         tm_sender: DynamicSender<'a, EpsTMContainer>,
-        temp_topic: &'static dyn TelemetryDefinition,
-        voltage_topic: &'static dyn TelemetryDefinition,
+        topics: BatteryTopics,
     ) -> Self {
         Self {
-            temp_probe,
+            input,
+            temp_sensor,
             adc_recv,
+            current_recv,
+            voltage_stats,
+            current_stats,
+            soc,
+            soc_sender,
+            voltage_monitor: VoltageMonitor::new(voltage_limits),
+            temperature_monitor: TemperatureMonitor::new(temperature_limits),
+            protection_sender,
             tm_sender,
-            temp_topic,
-            voltage_topic,
+            topics,
         }
     }
     async fn get_temperature(&mut self) -> Option<i16> {
-        Some(self.temp_probe.as_mut()?.read_temp().await.ok()?)
+        self.temp_sensor.read().await
     }
     async fn get_voltage(&mut self) -> i16 {
         self.adc_recv.get().await
     }
+    async fn get_current(&mut self) -> i16 {
+        self.current_recv.get().await
+    }
     pub async fn run(&mut self) {
-        if let Some(temperature) = self.get_temperature().await {
-            let container = EpsTMContainer::new(self.temp_topic, &temperature).unwrap();
+        let temperature = self.get_temperature().await;
+        if let Some(status) = self.temperature_monitor.update(temperature) {
+            let response = self.temperature_monitor.response(status);
+            self.protection_sender
+                .send(ProtectionEvent::BatteryTemperature(
+                    self.input, status, response,
+                ))
+                .await;
+        }
+        if let Some(temperature) = temperature {
+            let container = EpsTMContainer::new(self.topics.temperature, &temperature).unwrap();
+            self.tm_sender.send(container).await;
+        }
+        if let Some(state) = self.temp_sensor.take_state_change() {
+            event_log::log(Event::TempSensor, [self.input as u8, state as u8, 0]);
+            let container =
+                EpsTMContainer::new(self.topics.temp_sensor_state, &(state as u8)).unwrap();
             self.tm_sender.send(container).await;
         }
+        let container =
+            EpsTMContainer::new(self.topics.temp_sensor_errors, &self.temp_sensor.errors())
+                .unwrap();
+        self.tm_sender.send(container).await;
+
+        let voltage = self.get_voltage().await;
+        if let Some(status) = self.voltage_monitor.update(voltage) {
+            self.protection_sender
+                .send(ProtectionEvent::BatteryVoltage(self.input, status))
+                .await;
+        }
 
-        let container = EpsTMContainer::new(self.voltage_topic, &self.get_voltage().await).unwrap();
+        let container = EpsTMContainer::new(self.topics.voltage, &voltage).unwrap();
+        self.tm_sender.send(container).await;
+
+        let current = self.get_current().await;
+        let container = EpsTMContainer::new(self.topics.current, &current).unwrap();
+        self.tm_sender.send(container).await;
+        let power = calculate_power_10mw(voltage, current);
+        let container = EpsTMContainer::new(self.topics.power, &power).unwrap();
         self.tm_sender.send(container).await;
+        for (stats, topic) in [
+            (self.voltage_stats, self.topics.voltage_stats),
+            (self.current_stats, self.topics.current_stats),
+        ] {
+            self.tm_sender.send(stats.take().container(topic)).await;
+        }
+
+        let estimate = self.soc.update(voltage, current, temperature);
+        self.soc_sender.send(estimate.soc_permille);
+        for (topic, value) in [
+            (self.topics.state_of_charge, estimate.soc_permille),
+            (
+                self.topics.remaining_capacity,
+                estimate.remaining_capacity_mah,
+            ),
+            (self.topics.time_to_empty, estimate.time_to_empty_min),
+        ] {
+            let container = EpsTMContainer::new(topic, &value).unwrap();
+            self.tm_sender.send(container).await;
+        }
     }
 }
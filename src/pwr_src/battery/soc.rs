@@ -0,0 +1,121 @@
+use embassy_time::{Duration, Instant};
+
+// cells of the battery pack in series
+const CELLS_IN_SERIES: i32 = 2;
+
+/// open circuit voltage of a li-ion cell in mV against state of charge in permille
+const OCV_TABLE: [(i32, i32); 12] = [
+    (3000, 0),
+    (3300, 50),
+    (3450, 100),
+    (3550, 200),
+    (3600, 300),
+    (3650, 400),
+    (3700, 500),
+    (3780, 600),
+    (3870, 700),
+    (3950, 800),
+    (4050, 900),
+    (4200, 1000),
+];
+
+/// usable share of the capacity in percent against temperature in tenth degrees celsius
+const TEMPERATURE_CAPACITY_TABLE: [(i32, i32); 4] =
+    [(-20_0, 60), (0_0, 80), (10_0, 90), (25_0, 100)];
+
+// below this current the battery is considered at rest
+const REST_CURRENT_MA: i32 = 20;
+// after resting this long the terminal voltage is close to the open circuit voltage
+const REST_TIME: Duration = Duration::from_secs(30 * 60);
+
+/// linear interpolation in a table sorted by x, clamped to the table range
+fn interpolate(table: &[(i32, i32)], x: i32) -> i32 {
+    let upper = table.iter().position(|(tx, _)| *tx > x);
+    match upper {
+        Some(0) => table[0].1,
+        Some(i) => {
+            let (x0, y0) = table[i - 1];
+            let (x1, y1) = table[i];
+            y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+        }
+        None => table[table.len() - 1].1,
+    }
+}
+
+pub struct SocEstimate {
+    pub soc_permille: u16,
+    pub remaining_capacity_mah: u16,
+    /// minutes until empty at the present current, `u16::MAX` if not discharging
+    pub time_to_empty_min: u16,
+}
+
+/// State of charge from coulomb counting, recalibrated on the open circuit voltage at rest
+pub struct SocEstimator {
+    // charges in mA * ms to not lose short low current samples to rounding
+    capacity_ma_ms: i64,
+    // remaining charge, unknown until the first sample
+    charge_ma_ms: Option<i64>,
+    last_update: Instant,
+    resting_since: Option<Instant>,
+}
+
+impl SocEstimator {
+    pub fn new(capacity_mah: u16) -> Self {
+        Self {
+            capacity_ma_ms: capacity_mah as i64 * 3_600_000,
+            charge_ma_ms: None,
+            last_update: Instant::now(),
+            resting_since: None,
+        }
+    }
+    fn charge_from_ocv(&self, voltage_10mv: i16) -> i64 {
+        let cell_mv = voltage_10mv as i32 * 10 / CELLS_IN_SERIES;
+        let soc_permille = interpolate(&OCV_TABLE, cell_mv);
+        self.capacity_ma_ms * soc_permille as i64 / 1000
+    }
+    /// feed a new sample, positive currents discharge the battery
+    pub fn update(
+        &mut self,
+        voltage_10mv: i16,
+        current_ma: i16,
+        temperature_tenth_deg: Option<i16>,
+    ) -> SocEstimate {
+        let now = Instant::now();
+        let elapsed_ms = (now - self.last_update).as_millis() as i64;
+        self.last_update = now;
+        let current_ma = current_ma as i32;
+
+        let resting_since = match self.resting_since {
+            _ if current_ma.abs() >= REST_CURRENT_MA => None,
+            Some(since) => Some(since),
+            None => Some(now),
+        };
+        self.resting_since = resting_since;
+        let rested = resting_since.is_some_and(|since| now - since >= REST_TIME);
+
+        let charge = match self.charge_ma_ms {
+            Some(charge) if !rested => charge - current_ma as i64 * elapsed_ms,
+            _ => self.charge_from_ocv(voltage_10mv),
+        };
+        let charge = charge.clamp(0, self.capacity_ma_ms);
+        self.charge_ma_ms = Some(charge);
+
+        // cold cells deliver less of their charge, assume room temperature without a probe
+        let usable_percent = temperature_tenth_deg
+            .map(|temp| interpolate(&TEMPERATURE_CAPACITY_TABLE, temp as i32))
+            .unwrap_or(100);
+        let usable_ma_ms = charge * usable_percent as i64 / 100;
+
+        let time_to_empty_min = match current_ma {
+            i if i > 0 => {
+                (usable_ma_ms / current_ma as i64 / 60_000).min(u16::MAX as i64 - 1) as u16
+            }
+            _ => u16::MAX,
+        };
+        SocEstimate {
+            soc_permille: (charge * 1000 / self.capacity_ma_ms) as u16,
+            remaining_capacity_mah: (usable_ma_ms / 3_600_000) as u16,
+            time_to_empty_min,
+        }
+    }
+}
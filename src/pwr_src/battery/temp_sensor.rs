@@ -0,0 +1,118 @@
+use defmt::{Format, info, warn};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::{Duration, Instant};
+use embedded_hal_async::i2c::I2c;
+
+use super::tmp100_drv::{Addr0State, Resolution, Tmp100};
+use crate::i2c_bus::BusRecovery;
+
+// failed reads in a row before the sensor is considered lost
+const MAX_CONSECUTIVE_ERRORS: u8 = 3;
+const MIN_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(64);
+
+#[repr(u8)]
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum TempSensorState {
+    Connected,
+    Disconnected,
+}
+
+/// TMP100 that is reconnected with exponential backoff after failing
+pub struct TempSensor<'a, I2C: I2c + BusRecovery> {
+    interface: &'a Mutex<ThreadModeRawMutex, I2C>,
+    addr_state: Addr0State,
+    resolution: Resolution,
+    probe: Option<Tmp100<'a, I2C>>,
+    // failed sensor accesses since boot, never reset
+    errors: u16,
+    consecutive_errors: u8,
+    backoff: Duration,
+    next_retry: Instant,
+    state_changed: bool,
+}
+
+impl<'a, I2C: I2c + BusRecovery> TempSensor<'a, I2C> {
+    pub async fn new(
+        interface: &'a Mutex<ThreadModeRawMutex, I2C>,
+        addr_state: Addr0State,
+        resolution: Resolution,
+    ) -> Self {
+        let mut sensor = Self {
+            interface,
+            addr_state,
+            resolution,
+            probe: None,
+            errors: 0,
+            consecutive_errors: 0,
+            backoff: MIN_RETRY_BACKOFF,
+            next_retry: Instant::now(),
+            state_changed: true,
+        };
+        sensor.connect().await;
+        sensor
+    }
+    pub fn state(&self) -> TempSensorState {
+        match self.probe {
+            Some(_) => TempSensorState::Connected,
+            None => TempSensorState::Disconnected,
+        }
+    }
+    pub fn errors(&self) -> u16 {
+        self.errors
+    }
+    /// new state if it changed since the last call
+    pub fn take_state_change(&mut self) -> Option<TempSensorState> {
+        core::mem::take(&mut self.state_changed).then(|| self.state())
+    }
+    fn count_error(&mut self) {
+        self.errors = self.errors.saturating_add(1);
+    }
+    async fn connect(&mut self) {
+        match Tmp100::new(self.interface, self.resolution, self.addr_state).await {
+            Ok(probe) => {
+                info!("temp sensor {:x} connected", self.addr_state.get_addr());
+                self.probe = Some(probe);
+                self.consecutive_errors = 0;
+                self.backoff = MIN_RETRY_BACKOFF;
+                self.state_changed = true;
+            }
+            Err(_) => {
+                self.count_error();
+                self.next_retry = Instant::now() + self.backoff;
+                self.backoff = (self.backoff * 2).min(MAX_RETRY_BACKOFF);
+            }
+        }
+    }
+    fn disconnect(&mut self) {
+        warn!("temp sensor {:x} disconnected", self.addr_state.get_addr());
+        self.probe = None;
+        self.next_retry = Instant::now();
+        self.state_changed = true;
+    }
+    /// read the temperature, retrying the connection if it is due
+    pub async fn read(&mut self) -> Option<i16> {
+        if self.probe.is_none() {
+            if Instant::now() < self.next_retry {
+                return None;
+            }
+            // a slave stuck in a transfer blocks the bus until it is cleared
+            self.interface.lock().await.recover().await;
+            self.connect().await;
+        }
+        match self.probe.as_mut()?.read_temp().await {
+            Ok(temperature) => {
+                self.consecutive_errors = 0;
+                Some(temperature)
+            }
+            Err(_) => {
+                self.count_error();
+                self.consecutive_errors += 1;
+                if self.consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
+                    self.disconnect();
+                }
+                None
+            }
+        }
+    }
+}
@@ -1,13 +1,27 @@
 use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::{Duration, Timer};
 use embedded_hal_async::i2c::{ErrorType, I2c};
 
 const TEMP_RANGE_TENTH_DEG: i32 = 128_0;
+// limit registers are always in 12 bit format, left aligned
+const LIMIT_RAW_PER_DEG: i32 = 256;
+
+// first of the eight addresses selectable by ADD0 and ADD1
+const BASE_ADDR: u8 = 0b1001000;
 
 const TEMP_POINTER_REG: u8 = 0b00;
 const CONFIG_POINTER_REG: u8 = 0b01;
-//const T_LOW_POINTER_REG: u8 = 0b10;
-//const T_HIGH_POINTER_REG: u8 = 0b11;
+const T_LOW_POINTER_REG: u8 = 0b10;
+const T_HIGH_POINTER_REG: u8 = 0b11;
+
+// config register bits
+const SHUTDOWN_BIT: u8 = 1 << 0;
+const THERMOSTAT_BIT: u8 = 1 << 1;
+const POLARITY_BIT: u8 = 1 << 2;
+const ONE_SHOT_ALERT_BIT: u8 = 1 << 7;
 
+#[repr(u8)]
+#[derive(Clone, Copy)]
 pub enum Resolution {
     BITS9,
     BITS10,
@@ -15,6 +29,15 @@ pub enum Resolution {
     BITS12,
 }
 impl Resolution {
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::BITS9),
+            1 => Some(Self::BITS10),
+            2 => Some(Self::BITS11),
+            3 => Some(Self::BITS12),
+            _ => None,
+        }
+    }
     pub fn get_temp_range(&self) -> i32 {
         match self {
             Self::BITS9 => 256,
@@ -31,6 +54,15 @@ impl Resolution {
             Self::BITS12 => 4,
         }
     }
+    /// maximum conversion time according to the datasheet
+    pub fn get_conversion_time(&self) -> Duration {
+        Duration::from_millis(match self {
+            Self::BITS9 => 75,
+            Self::BITS10 => 150,
+            Self::BITS11 => 300,
+            Self::BITS12 => 600,
+        })
+    }
     fn set_reg_bits(&self, config_reg: &mut u8) {
         *config_reg &= !(0b11 << 5);
         *config_reg |= (match self {
@@ -41,12 +73,68 @@ impl Resolution {
         } << 5);
     }
 }
+/// Behaviour of the alert flag
+pub enum ThermostatMode {
+    /// alert while above T_HIGH until falling below T_LOW
+    Comparator,
+    /// alert on crossing T_HIGH or T_LOW until the next register read
+    Interrupt,
+}
+impl ThermostatMode {
+    fn set_reg_bits(&self, config_reg: &mut u8) {
+        match self {
+            Self::Comparator => *config_reg &= !THERMOSTAT_BIT,
+            Self::Interrupt => *config_reg |= THERMOSTAT_BIT,
+        }
+    }
+}
+/// Level of the alert flag while a fault is active
+pub enum Polarity {
+    ActiveLow,
+    ActiveHigh,
+}
+impl Polarity {
+    fn set_reg_bits(&self, config_reg: &mut u8) {
+        match self {
+            Self::ActiveLow => *config_reg &= !POLARITY_BIT,
+            Self::ActiveHigh => *config_reg |= POLARITY_BIT,
+        }
+    }
+}
+/// Consecutive faulty conversions needed to raise an alert
+pub enum FaultQueue {
+    Faults1,
+    Faults2,
+    Faults4,
+    Faults6,
+}
+impl FaultQueue {
+    fn set_reg_bits(&self, config_reg: &mut u8) {
+        *config_reg &= !(0b11 << 3);
+        *config_reg |= (match self {
+            Self::Faults1 => 0b00,
+            Self::Faults2 => 0b01,
+            Self::Faults4 => 0b10,
+            Self::Faults6 => 0b11,
+        } << 3);
+    }
+}
+#[repr(u8)]
+#[derive(Clone, Copy)]
 pub enum Addr0State {
     Floating,
     High,
     Low,
 }
 impl Addr0State {
+    pub const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::Floating),
+            1 => Some(Self::High),
+            2 => Some(Self::Low),
+            _ => None,
+        }
+    }
     pub fn get_addr(&self) -> u8 {
         match self {
             Self::Floating => 0b1001001,
@@ -54,12 +142,34 @@ impl Addr0State {
             Self::Low => 0b1001000,
         }
     }
+    /// bit of this address in a [`scan_bus`] result
+    pub fn scan_bit(&self) -> u8 {
+        1 << (self.get_addr() - BASE_ADDR)
+    }
+}
+
+/// Probe all addresses a TMP100 can be strapped to.
+/// Bit n of the result is set if the device at `0b1001000 + n` acknowledged
+pub async fn scan_bus<I2C: I2c>(interface: &Mutex<ThreadModeRawMutex, I2C>) -> u8 {
+    let mut answered = 0;
+    for offset in 0..8 {
+        let mut buffer = [0u8; 1];
+        let ack = interface
+            .lock()
+            .await
+            .read(BASE_ADDR + offset, &mut buffer)
+            .await
+            .is_ok();
+        answered |= (ack as u8) << offset;
+    }
+    answered
 }
 
 pub struct Tmp100<'a, I2C: I2c + ErrorType> {
     interface: &'a Mutex<ThreadModeRawMutex, I2C>,
     resolution: Resolution,
     addr_state: Addr0State,
+    config_reg: u8,
 }
 
 impl<'a, I2C: I2c + ErrorType> Tmp100<'a, I2C> {
@@ -70,21 +180,116 @@ impl<'a, I2C: I2c + ErrorType> Tmp100<'a, I2C> {
     ) -> Result<Self, I2C::Error> {
         let mut config_reg = 0;
         resolution.set_reg_bits(&mut config_reg);
+        let mut tmp100 = Self {
+            interface,
+            resolution,
+            addr_state,
+            config_reg,
+        };
+        tmp100.write_config().await?;
+        Ok(tmp100)
+    }
+    /// write a register and point back to the temperature register for following reads
+    async fn write_register(&mut self, bytes: &[u8]) -> Result<(), I2C::Error> {
+        let mut interface = self.interface.lock().await;
+        interface.write(self.addr_state.get_addr(), bytes).await?;
         interface
-            .lock()
+            .write(self.addr_state.get_addr(), &[TEMP_POINTER_REG])
             .await
-            .write(addr_state.get_addr(), &[CONFIG_POINTER_REG, config_reg])
+    }
+    /// read a register and point back to the temperature register for following reads
+    async fn read_register(&mut self, pointer: u8, buffer: &mut [u8]) -> Result<(), I2C::Error> {
+        let mut interface = self.interface.lock().await;
+        interface
+            .write_read(self.addr_state.get_addr(), &[pointer], buffer)
             .await?;
         interface
-            .lock()
+            .write(self.addr_state.get_addr(), &[TEMP_POINTER_REG])
+            .await
+    }
+    async fn write_config(&mut self) -> Result<(), I2C::Error> {
+        self.write_register(&[CONFIG_POINTER_REG, self.config_reg])
             .await
-            .write(addr_state.get_addr(), &[TEMP_POINTER_REG])
+    }
+    /// raw content of the config register
+    pub async fn read_config(&mut self) -> Result<u8, I2C::Error> {
+        let mut buffer = [0u8; 1];
+        self.read_register(CONFIG_POINTER_REG, &mut buffer).await?;
+        Ok(buffer[0])
+    }
+    /// state of the thermostat alert flag, independent of the configured polarity
+    pub async fn read_alert(&mut self) -> Result<bool, I2C::Error> {
+        let alert_bit = self.read_config().await? & ONE_SHOT_ALERT_BIT != 0;
+        let active_high = self.config_reg & POLARITY_BIT != 0;
+        Ok(alert_bit == active_high)
+    }
+    pub async fn set_resolution(&mut self, resolution: Resolution) -> Result<(), I2C::Error> {
+        resolution.set_reg_bits(&mut self.config_reg);
+        self.resolution = resolution;
+        self.write_config().await
+    }
+    pub async fn set_thermostat_mode(&mut self, mode: ThermostatMode) -> Result<(), I2C::Error> {
+        mode.set_reg_bits(&mut self.config_reg);
+        self.write_config().await
+    }
+    pub async fn set_polarity(&mut self, polarity: Polarity) -> Result<(), I2C::Error> {
+        polarity.set_reg_bits(&mut self.config_reg);
+        self.write_config().await
+    }
+    pub async fn set_fault_queue(&mut self, fault_queue: FaultQueue) -> Result<(), I2C::Error> {
+        fault_queue.set_reg_bits(&mut self.config_reg);
+        self.write_config().await
+    }
+    /// stop continuous conversions, temperatures are then only measured by one shot conversions
+    pub async fn set_shutdown(&mut self, shutdown: bool) -> Result<(), I2C::Error> {
+        if shutdown {
+            self.config_reg |= SHUTDOWN_BIT;
+        } else {
+            self.config_reg &= !SHUTDOWN_BIT;
+        }
+        self.write_config().await
+    }
+    fn tenth_deg_to_limit(temp_tenth_deg: i16) -> [u8; 2] {
+        let raw = temp_tenth_deg as i32 * LIMIT_RAW_PER_DEG / 10;
+        (raw as i16).to_be_bytes()
+    }
+    fn limit_to_tenth_deg(raw: [u8; 2]) -> i16 {
+        (i16::from_be_bytes(raw) as i32 * 10 / LIMIT_RAW_PER_DEG) as i16
+    }
+    /// program the thermostat limits in tenth degrees celsius
+    pub async fn set_limits(
+        &mut self,
+        low_tenth_deg: i16,
+        high_tenth_deg: i16,
+    ) -> Result<(), I2C::Error> {
+        let [low_msb, low_lsb] = Self::tenth_deg_to_limit(low_tenth_deg);
+        self.write_register(&[T_LOW_POINTER_REG, low_msb, low_lsb])
             .await?;
-        Ok(Self {
-            interface,
-            resolution,
-            addr_state,
-        })
+        let [high_msb, high_lsb] = Self::tenth_deg_to_limit(high_tenth_deg);
+        self.write_register(&[T_HIGH_POINTER_REG, high_msb, high_lsb])
+            .await
+    }
+    /// read back the thermostat limits (low, high) in tenth degrees celsius
+    pub async fn read_limits(&mut self) -> Result<(i16, i16), I2C::Error> {
+        let mut low = [0u8; 2];
+        self.read_register(T_LOW_POINTER_REG, &mut low).await?;
+        let mut high = [0u8; 2];
+        self.read_register(T_HIGH_POINTER_REG, &mut high).await?;
+        Ok((
+            Self::limit_to_tenth_deg(low),
+            Self::limit_to_tenth_deg(high),
+        ))
+    }
+    /// start a single conversion while in shutdown
+    pub async fn start_one_shot(&mut self) -> Result<(), I2C::Error> {
+        self.write_register(&[CONFIG_POINTER_REG, self.config_reg | ONE_SHOT_ALERT_BIT])
+            .await
+    }
+    /// measure once while in shutdown and wait for the result
+    pub async fn read_temp_one_shot(&mut self) -> Result<i16, I2C::Error> {
+        self.start_one_shot().await?;
+        Timer::after(self.resolution.get_conversion_time()).await;
+        self.read_temp().await
     }
     pub fn raw_temp_range(&self) -> i32 {
         self.resolution.get_temp_range()
@@ -96,9 +301,9 @@ impl<'a, I2C: I2c + ErrorType> Tmp100<'a, I2C> {
             .await
             .read(self.addr_state.get_addr(), &mut buffer)
             .await?;
+        // two's complement, left aligned
         let bitshift = self.resolution.get_bit_shift();
-        let tmp_raw = (buffer[0] as i32) << bitshift | (buffer[1] as i32) >> (8 - bitshift);
-        Ok(tmp_raw)
+        Ok((i16::from_be_bytes(buffer) >> (8 - bitshift)) as i32)
     }
     pub async fn read_temp(&mut self) -> Result<i16, I2C::Error> {
         Ok(((self.read_temp_raw().await? * TEMP_RANGE_TENTH_DEG) / self.raw_temp_range()) as i16)
@@ -1,32 +1,95 @@
+use core::convert::Infallible;
+
 use defmt::Format;
+#[cfg(feature = "stm32")]
 use embassy_stm32::{
     Peri,
     gpio::{Input, Level, Output, Pin, Pull, Speed},
 };
 use embassy_time::Timer;
+use embedded_hal::digital::{InputPin, OutputPin, PinState};
 use south_common::types::FlipFlopState;
 
 #[repr(u8)]
-#[derive(Format, Clone, Copy)]
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
 pub enum FlipFlopInput {
     Bat1,
     Bat2,
     AuxPwr,
 }
 
-pub struct DFlipFlop<'d> {
-    state: FlipFlopState,
-    bat_1: RawFlipFlop<'d>,
-    bat_2: RawFlipFlop<'d>,
-    aux_pwr: RawFlipFlop<'d>,
-    clk: Output<'d>,
+/// numeric id of a flip flop state used in telemetry
+pub fn state_id(state: FlipFlopState) -> u8 {
+    match state {
+        FlipFlopState::On => 0,
+        FlipFlopState::Bat1 => 1,
+        FlipFlopState::Bat2 => 2,
+        FlipFlopState::AuxPwr => 3,
+    }
 }
-struct RawFlipFlop<'d> {
-    d_pin: Output<'d>,
-    state_pin: Input<'d>,
+
+/// flip flop state of a numeric id, inverse of [`state_id`]
+pub fn state_from_id(id: u8) -> Option<FlipFlopState> {
+    match id {
+        0 => Some(FlipFlopState::On),
+        1 => Some(FlipFlopState::Bat1),
+        2 => Some(FlipFlopState::Bat2),
+        3 => Some(FlipFlopState::AuxPwr),
+        _ => None,
+    }
 }
 
-impl<'d> DFlipFlop<'d> {
+/// Set of flip flop inputs in the power path, stored as bitmask over the
+/// [`FlipFlopInput`] ids
+#[derive(Format, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SourceSet(u8);
+
+impl SourceSet {
+    pub const EMPTY: Self = Self(0);
+    pub const BATTERIES: Self = Self::EMPTY
+        .with(FlipFlopInput::Bat1)
+        .with(FlipFlopInput::Bat2);
+
+    /// inputs enabled in a flip flop state
+    pub const fn of(state: FlipFlopState) -> Self {
+        match state {
+            FlipFlopState::On => Self::BATTERIES.with(FlipFlopInput::AuxPwr),
+            FlipFlopState::Bat1 => Self::EMPTY.with(FlipFlopInput::Bat1),
+            FlipFlopState::Bat2 => Self::EMPTY.with(FlipFlopInput::Bat2),
+            FlipFlopState::AuxPwr => Self::EMPTY.with(FlipFlopInput::AuxPwr),
+        }
+    }
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+    pub const fn with(self, input: FlipFlopInput) -> Self {
+        Self(self.0 | 1 << input as u8)
+    }
+    pub const fn without(self, input: FlipFlopInput) -> Self {
+        Self(self.0 & !(1 << input as u8))
+    }
+    pub const fn contains(self, input: FlipFlopInput) -> bool {
+        self.0 & 1 << input as u8 != 0
+    }
+    pub const fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+pub struct DFlipFlop<O, I> {
+    sources: SourceSet,
+    bat_1: RawFlipFlop<O, I>,
+    bat_2: RawFlipFlop<O, I>,
+    aux_pwr: RawFlipFlop<O, I>,
+    clk: O,
+}
+struct RawFlipFlop<O, I> {
+    d_pin: O,
+    state_pin: I,
+}
+
+#[cfg(feature = "stm32")]
+impl<'d> DFlipFlop<Output<'d>, Input<'d>> {
     pub fn new(
         d_bat_1: Peri<'d, impl Pin>,
         state_bat_1: Peri<'d, impl Pin>,
@@ -36,7 +99,6 @@ impl<'d> DFlipFlop<'d> {
         state_aux_pwr: Peri<'d, impl Pin>,
         clk: Peri<'d, impl Pin>,
     ) -> Self {
-        let state = FlipFlopState::On;
         let d_bat_1 = Output::new(d_bat_1, Level::Low, Speed::High);
         let state_bat_1 = Input::new(state_bat_1, Pull::None);
         let d_bat_2 = Output::new(d_bat_2, Level::Low, Speed::High);
@@ -45,60 +107,77 @@ impl<'d> DFlipFlop<'d> {
         let state_aux_pwr = Input::new(state_aux_pwr, Pull::None);
 
         let clk = Output::new(clk, Level::Low, Speed::High);
+        Self::from_pins(
+            (d_bat_1, state_bat_1),
+            (d_bat_2, state_bat_2),
+            (d_aux_pwr, state_aux_pwr),
+            clk,
+        )
+    }
+}
+
+impl<O, I> DFlipFlop<O, I>
+where
+    O: OutputPin<Error = Infallible>,
+    I: InputPin<Error = Infallible>,
+{
+    /// create the flip flop from already configured (d, state) pin pairs.
+    /// d and clk pins are expected to be driven low initially
+    pub fn from_pins(bat_1: (O, I), bat_2: (O, I), aux_pwr: (O, I), clk: O) -> Self {
         Self {
-            state,
+            sources: SourceSet::of(FlipFlopState::On),
             bat_1: RawFlipFlop {
-                d_pin: d_bat_1,
-                state_pin: state_bat_1,
+                d_pin: bat_1.0,
+                state_pin: bat_1.1,
             },
             bat_2: RawFlipFlop {
-                d_pin: d_bat_2,
-                state_pin: state_bat_2,
+                d_pin: bat_2.0,
+                state_pin: bat_2.1,
             },
             aux_pwr: RawFlipFlop {
-                d_pin: d_aux_pwr,
-                state_pin: state_aux_pwr,
+                d_pin: aux_pwr.0,
+                state_pin: aux_pwr.1,
             },
             clk,
         }
     }
-    pub fn is_enabled(&self, input: FlipFlopInput) -> bool {
-        match input {
+    pub fn is_enabled(&mut self, input: FlipFlopInput) -> bool {
+        let Ok(high) = match input {
             FlipFlopInput::Bat1 => self.bat_1.state_pin.is_high(),
             FlipFlopInput::Bat2 => self.bat_2.state_pin.is_high(),
             FlipFlopInput::AuxPwr => self.aux_pwr.state_pin.is_high(),
-        }
+        };
+        high
     }
-    pub fn get_state(&self) -> FlipFlopState {
-        self.state
+    /// inputs the flip flop was last set to
+    pub fn sources(&self) -> SourceSet {
+        self.sources
     }
     pub async fn clock(&mut self) {
-        self.clk.set_high();
+        let Ok(()) = self.clk.set_high();
         Timer::after_micros(10).await;
-        self.clk.set_low();
-    }
-    pub fn map_state(&self) -> (bool, bool, bool) {
-        match self.state {
-            FlipFlopState::On => (true, true, true),
-            FlipFlopState::Bat1 => (true, false, false),
-            FlipFlopState::Bat2 => (false, true, false),
-            FlipFlopState::AuxPwr => (false, false, true),
-        }
+        let Ok(()) = self.clk.set_low();
     }
     pub async fn update(&mut self) {
-        let (bat_1_on, bat_2_on, aux_pwr_on) = self.map_state();
+        let bat_1_on = self.sources.contains(FlipFlopInput::Bat1);
+        let bat_2_on = self.sources.contains(FlipFlopInput::Bat2);
+        let aux_pwr_on = self.sources.contains(FlipFlopInput::AuxPwr);
         if bat_1_on != self.is_enabled(FlipFlopInput::Bat1)
             || bat_2_on != self.is_enabled(FlipFlopInput::Bat2)
             || aux_pwr_on != self.is_enabled(FlipFlopInput::AuxPwr)
         {
-            self.bat_1.d_pin.set_level((!bat_1_on).into());
-            self.bat_2.d_pin.set_level((!bat_2_on).into());
-            self.aux_pwr.d_pin.set_level((!aux_pwr_on).into());
+            let Ok(()) = self.bat_1.d_pin.set_state(PinState::from(!bat_1_on));
+            let Ok(()) = self.bat_2.d_pin.set_state(PinState::from(!bat_2_on));
+            let Ok(()) = self.aux_pwr.d_pin.set_state(PinState::from(!aux_pwr_on));
             self.clock().await
         }
     }
     pub async fn set(&mut self, state: FlipFlopState) {
-        self.state = state;
+        self.set_sources(SourceSet::of(state)).await
+    }
+    /// enable exactly the given inputs, e.g. a battery together with aux power
+    pub async fn set_sources(&mut self, sources: SourceSet) {
+        self.sources = sources;
         self.update().await
     }
 }
@@ -1,27 +1,97 @@
+use core::convert::Infallible;
+
+use defmt::Format;
+#[cfg(feature = "stm32")]
 use embassy_stm32::{
     Peri,
     gpio::{Level, Output, Pin, Speed},
 };
+use embedded_hal::digital::StatefulOutputPin;
 use south_common::types::Sink;
 
-pub struct SinkCtrl<'d> {
-    lst_enable: Output<'d>,
-    sens_enable: Output<'d>,
-    gps_enable: Output<'d>,
-    rhd_enable: Output<'d>,
+pub const SINKS: [Sink; 4] = [
+    Sink::RocketLST,
+    Sink::SensorUpper,
+    Sink::GPS,
+    Sink::RocketHD,
+];
+
+/// position of a sink in [`SINKS`]
+pub const fn sink_index(sink: Sink) -> usize {
+    match sink {
+        Sink::RocketLST => 0,
+        Sink::SensorUpper => 1,
+        Sink::GPS => 2,
+        Sink::RocketHD => 3,
+    }
+}
+
+/// Set of sinks, stored as bitmask over the [`SINKS`] indices
+#[derive(Format, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SinkSet(u8);
+
+impl SinkSet {
+    pub const EMPTY: Self = Self(0);
+    pub const ALL: Self = Self((1 << SINKS.len()) - 1);
+
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits & Self::ALL.0)
+    }
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+    pub const fn with(self, sink: Sink) -> Self {
+        Self(self.0 | 1 << sink_index(sink))
+    }
+    pub const fn without(self, sink: Sink) -> Self {
+        Self(self.0 & !(1 << sink_index(sink)))
+    }
+    pub const fn contains(self, sink: Sink) -> bool {
+        self.0 & 1 << sink_index(sink) != 0
+    }
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+    pub const fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+    pub const fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+    pub fn iter(self) -> impl Iterator<Item = Sink> {
+        SINKS.into_iter().filter(move |sink| self.contains(*sink))
+    }
+}
+
+pub struct SinkCtrl<O> {
+    lst_enable: O,
+    sens_enable: O,
+    gps_enable: O,
+    rhd_enable: O,
 }
 
-impl<'d> SinkCtrl<'d> {
+#[cfg(feature = "stm32")]
+impl<'d> SinkCtrl<Output<'d>> {
+    /// configure the enable pins with the sinks in `enabled` switched on
     pub fn new(
         lst_enable: Peri<'d, impl Pin>,
         sens_enable: Peri<'d, impl Pin>,
         gps_enable: Peri<'d, impl Pin>,
         rhd_enable: Peri<'d, impl Pin>,
+        enabled: SinkSet,
     ) -> Self {
-        let lst_enable = Output::new(lst_enable, Level::High, Speed::High);
-        let sens_enable = Output::new(sens_enable, Level::High, Speed::High);
-        let gps_enable = Output::new(gps_enable, Level::High, Speed::High);
-        let rhd_enable = Output::new(rhd_enable, Level::High, Speed::High);
+        let level = |sink| Level::from(enabled.contains(sink));
+        let lst_enable = Output::new(lst_enable, level(Sink::RocketLST), Speed::High);
+        let sens_enable = Output::new(sens_enable, level(Sink::SensorUpper), Speed::High);
+        let gps_enable = Output::new(gps_enable, level(Sink::GPS), Speed::High);
+        let rhd_enable = Output::new(rhd_enable, level(Sink::RocketHD), Speed::High);
+        Self::from_pins(lst_enable, sens_enable, gps_enable, rhd_enable)
+    }
+}
+
+impl<O: StatefulOutputPin<Error = Infallible>> SinkCtrl<O> {
+    /// create the sink control from already configured enable pins
+    pub fn from_pins(lst_enable: O, sens_enable: O, gps_enable: O, rhd_enable: O) -> Self {
         Self {
             lst_enable,
             sens_enable,
@@ -29,7 +99,14 @@ impl<'d> SinkCtrl<'d> {
             rhd_enable,
         }
     }
-    fn get(&mut self, sink: Sink) -> &mut Output<'d> {
+    /// sinks currently enabled
+    pub fn enabled(&mut self) -> SinkSet {
+        SINKS
+            .into_iter()
+            .filter(|sink| self.is_enabled(*sink))
+            .fold(SinkSet::EMPTY, SinkSet::with)
+    }
+    fn get(&mut self, sink: Sink) -> &mut O {
         match sink {
             Sink::RocketLST => &mut self.lst_enable,
             Sink::SensorUpper => &mut self.sens_enable,
@@ -38,12 +115,13 @@ impl<'d> SinkCtrl<'d> {
         }
     }
     pub fn enable(&mut self, sink: Sink) {
-        self.get(sink).set_high()
+        let Ok(()) = self.get(sink).set_high();
     }
     pub fn disable(&mut self, sink: Sink) {
-        self.get(sink).set_low()
+        let Ok(()) = self.get(sink).set_low();
     }
     pub fn is_enabled(&mut self, sink: Sink) -> bool {
-        self.get(sink).is_set_high()
+        let Ok(high) = self.get(sink).is_set_high();
+        high
     }
 }
@@ -0,0 +1,147 @@
+use embassy_sync::{
+    channel::{DynamicReceiver, DynamicSender},
+    watch::DynReceiver,
+};
+
+use embassy_time::{Duration, Instant, Timer};
+use south_common::{TelemetryDefinition, telemetry::eps as tm, types::Sink};
+
+use crate::{
+    EpsTMContainer,
+    adc::{conversion::calculate_power_10mw, stats::SharedStatistics},
+    protection::{
+        ProtectionEvent,
+        overcurrent::{EFuse, FuseLimits},
+    },
+    pwr_src::sink_ctrl::{SINKS, sink_index},
+};
+
+/// current and power telemetry of each sink, in [`SINKS`] order
+const SINK_TOPICS: [(&dyn TelemetryDefinition, &dyn TelemetryDefinition); SINKS.len()] = [
+    (&tm::RocketLSTCurrent, &tm::RocketLSTPower),
+    (&tm::SensorUpperCurrent, &tm::SensorUpperPower),
+    (&tm::GPSCurrent, &tm::GPSPower),
+    (&tm::RocketHDCurrent, &tm::RocketHDPower),
+];
+/// current statistics telemetry of each sink, in [`SINKS`] order
+const SINK_STATS_TOPICS: [&dyn TelemetryDefinition; SINKS.len()] = [
+    &tm::RocketLSTCurrentStats,
+    &tm::SensorUpperCurrentStats,
+    &tm::GPSCurrentStats,
+    &tm::RocketHDCurrentStats,
+];
+
+const SINK_TM_INTERVAL: Duration = Duration::from_millis(500);
+
+// Sink monitoring task, runs at the adc rate for a short fuse reaction time
+#[embassy_executor::task]
+pub async fn sink_monitor_thread(mut sink_monitor: SinkMonitor<'static>, loop_len: Duration) {
+    let mut loop_time = Instant::now();
+    loop {
+        sink_monitor.run().await;
+        loop_time += loop_len;
+        Timer::at(loop_time).await;
+    }
+}
+
+/// Current and power of the sink rails, which are all fed from the power path,
+/// with an electronic fuse per sink
+pub struct SinkMonitor<'a> {
+    bus_voltage_recv: DynReceiver<'a, i16>,
+    current_recvs: [DynReceiver<'a, i16>; SINKS.len()],
+    bus_voltage_stats: &'a SharedStatistics,
+    current_stats: [&'a SharedStatistics; SINKS.len()],
+    fuses: [EFuse; SINKS.len()],
+    next_tm: Instant,
+    dropped_tm: u32,
+    fuse_reset_receiver: DynamicReceiver<'a, Sink>,
+    protection_sender: DynamicSender<'a, ProtectionEvent>,
+    tm_sender: DynamicSender<'a, EpsTMContainer>,
+}
+
+impl<'a> SinkMonitor<'a> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        bus_voltage_recv: DynReceiver<'a, i16>,
+        current_recvs: [DynReceiver<'a, i16>; SINKS.len()],
+        bus_voltage_stats: &'a SharedStatistics,
+        current_stats: [&'a SharedStatistics; SINKS.len()],
+        fuse_limits: [FuseLimits; SINKS.len()],
+        fuse_reset_receiver: DynamicReceiver<'a, Sink>,
+        protection_sender: DynamicSender<'a, ProtectionEvent>,
+        tm_sender: DynamicSender<'a, EpsTMContainer>,
+    ) -> Self {
+        Self {
+            bus_voltage_recv,
+            current_recvs,
+            bus_voltage_stats,
+            current_stats,
+            fuses: fuse_limits.map(EFuse::new),
+            next_tm: Instant::now(),
+            dropped_tm: 0,
+            fuse_reset_receiver,
+            protection_sender,
+            tm_sender,
+        }
+    }
+    /// queue the telemetry without waiting, frames are dropped while the channel is
+    /// full so the fuses keep being evaluated at the adc rate. The dropped frames
+    /// are counted in the telemetry
+    fn send_tm(&mut self, bus_voltage: i16, currents: [i16; SINKS.len()]) {
+        let dropped = self.dropped_tm;
+        let mut send = |container| {
+            if self.tm_sender.try_send(container).is_err() {
+                self.dropped_tm = self.dropped_tm.saturating_add(1);
+            }
+        };
+        send(EpsTMContainer::new(&tm::BusVoltage, &bus_voltage).unwrap());
+        send(
+            self.bus_voltage_stats
+                .take()
+                .container(&tm::BusVoltageStats),
+        );
+
+        for (current, (current_topic, power_topic)) in currents.into_iter().zip(SINK_TOPICS) {
+            let power = calculate_power_10mw(bus_voltage, current);
+            send(EpsTMContainer::new(current_topic, &current).unwrap());
+            send(EpsTMContainer::new(power_topic, &power).unwrap());
+        }
+
+        for (stats, topic) in self.current_stats.into_iter().zip(SINK_STATS_TOPICS) {
+            send(stats.take().container(topic));
+        }
+
+        let retries = u32::from_le_bytes(self.fuses.each_ref().map(EFuse::retries));
+        send(EpsTMContainer::new(&tm::SinkFuseRetries, &retries).unwrap());
+        send(EpsTMContainer::new(&tm::SinkTmDropped, &dropped).unwrap());
+    }
+    /// telemetry frames dropped on a full channel since boot
+    pub fn dropped_tm(&self) -> u32 {
+        self.dropped_tm
+    }
+    pub async fn run(&mut self) {
+        while let Ok(sink) = self.fuse_reset_receiver.try_receive() {
+            if let Some(status) = self.fuses[sink_index(sink)].reset() {
+                self.protection_sender
+                    .send(ProtectionEvent::SinkFuse(sink, status))
+                    .await;
+            }
+        }
+
+        let bus_voltage = self.bus_voltage_recv.get().await;
+        let mut currents = [0; SINKS.len()];
+        for (i, sink) in SINKS.into_iter().enumerate() {
+            currents[i] = self.current_recvs[i].get().await;
+            if let Some(status) = self.fuses[i].update(currents[i]) {
+                self.protection_sender
+                    .send(ProtectionEvent::SinkFuse(sink, status))
+                    .await;
+            }
+        }
+
+        if Instant::now() >= self.next_tm {
+            self.send_tm(bus_voltage, currents);
+            self.next_tm += SINK_TM_INTERVAL;
+        }
+    }
+}
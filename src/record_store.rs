@@ -0,0 +1,213 @@
+//! Records kept in two flash pages used as a ring, the storage behind the
+//! configuration, the power scripts, the event log and the telecommand key.
+//! Records are appended to the active page. Once it is full the other page is
+//! erased and becomes the active one, so the records of the full page survive a
+//! power loss while the new page is written. Records are packed as
+//! `[magic (u16 le), layout version, padding, sequence number (u32 le),
+//! payload.., crc (u32 le)]`, padded to the flash double word
+use defmt::Format;
+use embedded_storage::nor_flash::NorFlash;
+
+// magic, layout version, padding and sequence number
+const HEADER_SIZE: usize = 8;
+const CRC_SIZE: usize = 4;
+const ERASED: u8 = 0xFF;
+
+/// size of a record slot holding `payload` bytes
+pub const fn record_size(payload: usize) -> usize {
+    (HEADER_SIZE + payload + CRC_SIZE).next_multiple_of(8)
+}
+
+/// flash not readable, writable or erasable
+#[derive(Format, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlashError;
+
+/// A valid record read from flash
+pub struct Record<const SIZE: usize>([u8; SIZE]);
+
+impl<const SIZE: usize> Record<SIZE> {
+    /// payload including the padding behind it
+    pub fn payload(&self) -> &[u8] {
+        &self.0[HEADER_SIZE..SIZE - CRC_SIZE]
+    }
+}
+
+/// Records of `SIZE` bytes in the two erase pages starting at `offset`
+pub struct RecordStore<F: NorFlash, const SIZE: usize> {
+    flash: F,
+    offset: u32,
+    magic: u16,
+    version: u8,
+    sequence: u32,
+    page: u32,
+    // next slot of the active page
+    slot: u32,
+    // used slots of the other page, which holds the older records
+    older: u32,
+}
+
+impl<F: NorFlash, const SIZE: usize> RecordStore<F, SIZE> {
+    /// record slots per page
+    pub const SLOTS: u32 = F::ERASE_SIZE as u32 / SIZE as u32;
+
+    /// records of other stores or layouts are told apart by `magic` and `version`
+    pub fn new(flash: F, offset: u32, magic: u16, version: u8) -> Self {
+        const { assert!(SIZE % F::WRITE_SIZE == 0 && SIZE <= F::ERASE_SIZE) };
+        Self {
+            flash,
+            offset,
+            magic,
+            version,
+            sequence: 0,
+            page: 0,
+            slot: 0,
+            older: 0,
+        }
+    }
+    fn slot_offset(&self, page: u32, slot: u32) -> u32 {
+        self.offset + page * F::ERASE_SIZE as u32 + slot * SIZE as u32
+    }
+    fn read_record(&mut self, page: u32, slot: u32) -> Result<[u8; SIZE], FlashError> {
+        let mut record = [0; SIZE];
+        self.flash
+            .read(self.slot_offset(page, slot), &mut record)
+            .map_err(|_| FlashError)?;
+        Ok(record)
+    }
+    fn erase(&mut self, page: u32) -> Result<(), FlashError> {
+        let start = self.slot_offset(page, 0);
+        self.flash
+            .erase(start, start + F::ERASE_SIZE as u32)
+            .map_err(|_| FlashError)
+    }
+    /// find the active page and the end of the records. Pages without any valid
+    /// record, e.g. of an older layout, are erased
+    pub fn load(&mut self) -> Result<(), FlashError> {
+        let mut latest = None;
+        let mut page_ends = [0; 2];
+        for (page, end) in page_ends.iter_mut().enumerate() {
+            let page = page as u32;
+            for slot in 0..Self::SLOTS {
+                let record = self.read_record(page, slot)?;
+                // records are appended, the first erased slot ends the page
+                if record.iter().all(|b| *b == ERASED) {
+                    break;
+                }
+                *end = slot + 1;
+                let Some(sequence) = self.check(&record) else {
+                    continue;
+                };
+                if latest.is_none_or(|(s, _)| sequence > s) {
+                    latest = Some((sequence, page));
+                }
+            }
+        }
+        let Some((sequence, page)) = latest else {
+            self.page = 0;
+            self.slot = 0;
+            self.older = 0;
+            return match page_ends {
+                [0, 0] => Ok(()),
+                _ => self.clear(),
+            };
+        };
+        self.sequence = sequence;
+        self.page = page;
+        self.slot = page_ends[page as usize];
+        self.older = page_ends[1 - page as usize];
+        Ok(())
+    }
+    /// used slots, including the ones of interrupted writes
+    pub fn len(&self) -> usize {
+        (self.older + self.slot) as usize
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// true if the next record starts a new page and drops the older records
+    pub fn page_full(&self) -> bool {
+        self.slot == Self::SLOTS
+    }
+    /// record at `position` counted from the oldest one, `None` for slots
+    /// without a valid record
+    pub fn read(&mut self, position: usize) -> Result<Option<Record<SIZE>>, FlashError> {
+        let position = position as u32;
+        let (page, slot) = match position.checked_sub(self.older) {
+            None => (1 - self.page, position),
+            Some(slot) if slot < self.slot => (self.page, slot),
+            Some(_) => return Ok(None),
+        };
+        let record = self.read_record(page, slot)?;
+        Ok(self.check(&record).map(|_| Record(record)))
+    }
+    /// newest valid record
+    pub fn latest(&mut self) -> Result<Option<Record<SIZE>>, FlashError> {
+        for position in (0..self.len()).rev() {
+            if let Some(record) = self.read(position)? {
+                return Ok(Some(record));
+            }
+        }
+        Ok(None)
+    }
+    /// append a record holding `payload`, the store has to be loaded first
+    pub fn append(&mut self, payload: &[u8]) -> Result<(), FlashError> {
+        if self.page_full() {
+            let page = 1 - self.page;
+            self.erase(page)?;
+            self.page = page;
+            self.slot = 0;
+            self.older = Self::SLOTS;
+        }
+        let sequence = self.sequence.wrapping_add(1);
+        let record = self.encode(sequence, payload);
+        let slot = self.slot;
+        // a failed write leaves a used slot, skip it on the next append
+        self.slot += 1;
+        self.flash
+            .write(self.slot_offset(self.page, slot), &record)
+            .map_err(|_| FlashError)?;
+        if self.read_record(self.page, slot)? != record {
+            return Err(FlashError);
+        }
+        self.sequence = sequence;
+        Ok(())
+    }
+    /// erase all records, sequence numbers continue
+    pub fn clear(&mut self) -> Result<(), FlashError> {
+        self.slot = 0;
+        self.older = 0;
+        self.erase(1 - self.page)?;
+        self.erase(self.page)
+    }
+    fn encode(&self, sequence: u32, payload: &[u8]) -> [u8; SIZE] {
+        let mut record = [ERASED; SIZE];
+        record[0..2].copy_from_slice(&self.magic.to_le_bytes());
+        record[2] = self.version;
+        record[4..8].copy_from_slice(&sequence.to_le_bytes());
+        record[HEADER_SIZE..HEADER_SIZE + payload.len()].copy_from_slice(payload);
+        let crc_start = SIZE - CRC_SIZE;
+        let crc = crc32(&record[..crc_start]);
+        record[crc_start..].copy_from_slice(&crc.to_le_bytes());
+        record
+    }
+    /// sequence number of a valid record
+    fn check(&self, record: &[u8; SIZE]) -> Option<u32> {
+        let crc_start = SIZE - CRC_SIZE;
+        let crc = u32::from_le_bytes(record[crc_start..].try_into().unwrap());
+        let magic = u16::from_le_bytes([record[0], record[1]]);
+        if crc != crc32(&record[..crc_start]) || magic != self.magic || record[2] != self.version {
+            return None;
+        }
+        Some(u32::from_le_bytes(record[4..8].try_into().unwrap()))
+    }
+}
+
+/// CRC-32 (IEEE 802.3)
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| match crc & 1 {
+            1 => (crc >> 1) ^ 0xEDB8_8320,
+            _ => crc >> 1,
+        })
+    })
+}
@@ -0,0 +1,61 @@
+//! Reset cause detection and reset counters kept across resets
+#[cfg(feature = "stm32")]
+mod stm32;
+
+use defmt::Format;
+use embassy_time::Duration;
+
+use crate::control_loop::mode::ModeReason;
+
+#[cfg(feature = "stm32")]
+pub use stm32::{BackupRegisters, read_reset_cause, stable_uptime_thread};
+
+#[repr(u8)]
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum ResetCause {
+    /// power on or brownout, the reset flags do not tell them apart
+    PowerOn,
+    Pin,
+    Software,
+    Watchdog,
+    WindowWatchdog,
+    LowPower,
+    OptionByteLoad,
+}
+
+/// boots without a stable uptime in between tolerated before safe mode is entered
+pub const MAX_RAPID_RESETS: u8 = 3;
+/// uptime after which a boot counts as stable
+pub const STABLE_UPTIME: Duration = Duration::from_secs(60);
+
+/// Reset counters kept across resets
+#[derive(Format, Clone, Copy)]
+pub struct ResetCounters {
+    /// boots since the last stable uptime
+    pub rapid: u8,
+    pub total: u16,
+}
+
+impl ResetCounters {
+    /// count the current boot
+    pub fn count_boot(self) -> Self {
+        Self {
+            rapid: self.rapid.saturating_add(1),
+            total: self.total.wrapping_add(1),
+        }
+    }
+    /// packed for telemetry together with the reset cause
+    pub fn tm_value(&self, cause: ResetCause) -> u32 {
+        let [total_low, total_high] = self.total.to_le_bytes();
+        u32::from_le_bytes([cause as u8, self.rapid, total_low, total_high])
+    }
+}
+
+/// reason of the mode entered after this boot
+pub fn boot_reason(cause: ResetCause, counters: ResetCounters) -> ModeReason {
+    match cause {
+        _ if counters.rapid > MAX_RAPID_RESETS => ModeReason::RepeatedResets,
+        ResetCause::Watchdog | ResetCause::WindowWatchdog => ModeReason::WatchdogReset,
+        _ => ModeReason::PowerOn,
+    }
+}
@@ -0,0 +1,66 @@
+use embassy_stm32::pac;
+use embassy_time::Timer;
+
+use super::{ResetCause, ResetCounters, STABLE_UPTIME};
+
+/// cause of the last reset, the sticky reset flags are cleared afterwards
+pub fn read_reset_cause() -> ResetCause {
+    let csr = pac::RCC.csr().read();
+    // an internal reset also drives the reset pin, so the pin flag comes last
+    let cause = if csr.iwdgrstf() {
+        ResetCause::Watchdog
+    } else if csr.wwdgrstf() {
+        ResetCause::WindowWatchdog
+    } else if csr.sftrstf() {
+        ResetCause::Software
+    } else if csr.lpwrrstf() {
+        ResetCause::LowPower
+    } else if csr.pwrrstf() {
+        ResetCause::PowerOn
+    } else if csr.oblrstf() {
+        ResetCause::OptionByteLoad
+    } else {
+        ResetCause::Pin
+    };
+    pac::RCC.csr().modify(|w| w.set_rmvf(true));
+    cause
+}
+
+/// Reset counters in the TAMP backup registers, which keep their content over
+/// system resets as long as the backup domain stays powered
+pub struct BackupRegisters;
+
+impl BackupRegisters {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        pac::RCC.apbenr1().modify(|w| {
+            w.set_pwren(true);
+            w.set_rtcapben(true);
+        });
+        // unlock backup domain writes
+        pac::PWR.cr1().modify(|w| w.set_dbp(true));
+        Self
+    }
+    pub fn load(&self) -> ResetCounters {
+        let raw = pac::TAMP.bkpr(0).read().bkp();
+        ResetCounters {
+            rapid: raw as u8,
+            total: (raw >> 16) as u16,
+        }
+    }
+    pub fn store(&mut self, counters: ResetCounters) {
+        let raw = counters.rapid as u32 | (counters.total as u32) << 16;
+        pac::TAMP.bkpr(0).write(|w| w.set_bkp(raw));
+    }
+}
+
+// clears the rapid reset counter once the boot turned out stable
+#[embassy_executor::task]
+pub async fn stable_uptime_thread(mut registers: BackupRegisters) {
+    Timer::after(STABLE_UPTIME).await;
+    let counters = registers.load();
+    registers.store(ResetCounters {
+        rapid: 0,
+        ..counters
+    });
+}
@@ -0,0 +1,185 @@
+//! Power sequencing scripts. A script is a short list of switching steps with
+//! waits in between, uploaded step by step via telecommand, stored in flash and
+//! executed by the control loop. Steps are encoded in 4 bytes as
+//! `[opcode, argument, parameter (u16 le)]`
+pub mod store;
+
+use defmt::Format;
+use embassy_sync::channel::{DynamicReceiver, DynamicSender};
+use embassy_time::Duration;
+use embedded_storage::nor_flash::NorFlash;
+use heapless::Vec;
+use south_common::{
+    telemetry::eps as tm,
+    types::{EPSCommand, FlipFlopState, Sink},
+};
+
+#[cfg(feature = "stm32")]
+use crate::shared_flash::BoardFlash;
+use crate::{
+    EpsTMContainer,
+    pwr_src::{
+        d_flip_flop::{state_from_id, state_id},
+        sink_ctrl::{SINKS, sink_index},
+    },
+    telecommand::TcFailure,
+};
+use store::{ScriptStore, ScriptStoreError};
+
+pub const SCRIPT_COUNT: usize = 8;
+pub const MAX_STEPS: usize = 16;
+pub const STEP_SIZE: usize = 4;
+
+// Script service task
+#[cfg(feature = "stm32")]
+#[embassy_executor::task]
+pub async fn script_thread(mut service: ScriptService<'static, BoardFlash>) {
+    loop {
+        service.run().await;
+    }
+}
+
+/// Single step of a script
+#[derive(Clone, Copy)]
+pub enum Step {
+    /// pause the script for the given ms
+    Wait(u16),
+    EnableSink(Sink),
+    DisableSink(Sink),
+    SetSource(FlipFlopState),
+}
+
+impl Step {
+    pub fn new(op: u8, arg: u8, param: u16) -> Option<Self> {
+        let sink = || SINKS.get(arg as usize).copied();
+        match op {
+            0 => Some(Self::Wait(param)),
+            1 => Some(Self::EnableSink(sink()?)),
+            2 => Some(Self::DisableSink(sink()?)),
+            3 => Some(Self::SetSource(state_from_id(arg)?)),
+            _ => None,
+        }
+    }
+    pub fn decode(bytes: [u8; STEP_SIZE]) -> Option<Self> {
+        Self::new(bytes[0], bytes[1], u16::from_le_bytes([bytes[2], bytes[3]]))
+    }
+    pub fn encode(&self) -> [u8; STEP_SIZE] {
+        let (op, arg, param) = match self {
+            Self::Wait(ms) => (0, 0, *ms),
+            Self::EnableSink(sink) => (1, sink_index(*sink) as u8, 0),
+            Self::DisableSink(sink) => (2, sink_index(*sink) as u8, 0),
+            Self::SetSource(state) => (3, state_id(*state), 0),
+        };
+        let [param_low, param_high] = param.to_le_bytes();
+        [op, arg, param_low, param_high]
+    }
+    /// switching command executed by the step, `None` for waits
+    pub fn command(&self) -> Option<EPSCommand> {
+        match self {
+            Self::Wait(_) => None,
+            Self::EnableSink(sink) => Some(EPSCommand::EnableSink(*sink, None)),
+            Self::DisableSink(sink) => Some(EPSCommand::DisableSink(*sink, None)),
+            Self::SetSource(state) => Some(EPSCommand::SetSource(*state, None)),
+        }
+    }
+    /// time until the next step is executed
+    pub fn delay(&self) -> Duration {
+        match self {
+            Self::Wait(ms) => Duration::from_millis((*ms).into()),
+            _ => Duration::from_ticks(0),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Script {
+    steps: Vec<Step, MAX_STEPS>,
+}
+
+impl Script {
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+    /// replace a step or append it if `index` is the script length
+    pub fn set_step(&mut self, index: u8, step: Step) -> Result<(), TcFailure> {
+        let index = index as usize;
+        match self.steps.get_mut(index) {
+            Some(old) => *old = step,
+            None if index == self.steps.len() => {
+                self.steps.push(step).map_err(|_| TcFailure::InvalidStep)?
+            }
+            None => return Err(TcFailure::InvalidStep),
+        }
+        Ok(())
+    }
+    pub fn clear(&mut self) {
+        self.steps.clear();
+    }
+}
+
+/// All scripts, indexed by their id
+pub type Scripts = [Script; SCRIPT_COUNT];
+
+/// Outcome of a script, reported in progress telemetry
+#[repr(u8)]
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptState {
+    Running,
+    Completed,
+    AbortedByTelecommand,
+    /// a protection fault changed the power path
+    AbortedByProtection,
+    AbortedByModeChange,
+    StepFailed,
+}
+
+/// progress telemetry, packed as `[script id, executed steps, script len, state]`
+pub fn progress_container(
+    id: u8,
+    executed: usize,
+    len: usize,
+    state: ScriptState,
+) -> EpsTMContainer {
+    let progress = u32::from_le_bytes([id, executed as u8, len as u8, state as u8]);
+    EpsTMContainer::new(&tm::ScriptProgress, &progress).unwrap()
+}
+
+/// Writes scripts to flash on request of the control loop, which keeps the
+/// scripts it executes in memory
+pub struct ScriptService<'a, F: NorFlash> {
+    store: ScriptStore<F>,
+    scripts: Scripts,
+    script_receiver: DynamicReceiver<'a, (u8, Script)>,
+    tm_sender: DynamicSender<'a, EpsTMContainer>,
+}
+
+impl<'a, F: NorFlash> ScriptService<'a, F> {
+    /// `scripts` are the scripts loaded from `store` at boot
+    pub fn new(
+        store: ScriptStore<F>,
+        scripts: Scripts,
+        script_receiver: DynamicReceiver<'a, (u8, Script)>,
+        tm_sender: DynamicSender<'a, EpsTMContainer>,
+    ) -> Self {
+        Self {
+            store,
+            scripts,
+            script_receiver,
+            tm_sender,
+        }
+    }
+    pub async fn run(&mut self) {
+        let (id, script) = self.script_receiver.receive().await;
+        let result = match self.scripts.get_mut(id as usize) {
+            Some(stored) => {
+                *stored = script;
+                self.store.commit(&self.scripts, id)
+            }
+            None => Err(ScriptStoreError::UnknownScript),
+        };
+        let status = result.err().map_or(0, |e| e as u8);
+        let stored = u16::from_le_bytes([id, status]);
+        let container = EpsTMContainer::new(&tm::ScriptStored, &stored).unwrap();
+        self.tm_sender.send(container).await;
+    }
+}
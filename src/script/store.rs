@@ -0,0 +1,104 @@
+use defmt::Format;
+use embedded_storage::nor_flash::NorFlash;
+
+use super::{MAX_STEPS, SCRIPT_COUNT, STEP_SIZE, Script, Scripts, Step};
+use crate::record_store::{RecordStore, record_size};
+
+#[repr(u8)]
+#[derive(Format, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptStoreError {
+    Flash = 1,
+    UnknownScript,
+}
+
+const MAGIC: u16 = 0x5C21;
+/// bumped whenever the record layout changes, records of other layouts are ignored
+const LAYOUT_VERSION: u8 = 2;
+
+// script id and step count
+const PAYLOAD_HEADER_SIZE: usize = 2;
+const PAYLOAD_SIZE: usize = PAYLOAD_HEADER_SIZE + STEP_SIZE * MAX_STEPS;
+/// size of a record slot, padded to the flash double word
+pub const RECORD_SIZE: usize = record_size(PAYLOAD_SIZE);
+
+/// Script records in a [`RecordStore`], one record per stored script. Once the
+/// active page is full all scripts are rewritten to the other page before it
+/// drops the older records. The latest record of a script wins, so an
+/// interrupted rewrite falls back to the old page
+pub struct ScriptStore<F: NorFlash> {
+    records: RecordStore<F, RECORD_SIZE>,
+}
+
+impl<F: NorFlash> ScriptStore<F> {
+    /// `offset` is the start of two erase pages reserved for the scripts
+    pub fn new(flash: F, offset: u32) -> Self {
+        const { assert!(SCRIPT_COUNT * RECORD_SIZE < F::ERASE_SIZE) };
+        Self {
+            records: RecordStore::new(flash, offset, MAGIC, LAYOUT_VERSION),
+        }
+    }
+    /// latest stored version of every script, scripts never stored are empty
+    pub fn load(&mut self) -> Result<Scripts, ScriptStoreError> {
+        self.records.load().map_err(|_| ScriptStoreError::Flash)?;
+        let mut scripts = Scripts::default();
+        // records are read oldest first
+        for position in 0..self.records.len() {
+            let record = self
+                .records
+                .read(position)
+                .map_err(|_| ScriptStoreError::Flash)?;
+            if let Some((id, script)) = record.and_then(|r| decode(r.payload())) {
+                scripts[id as usize] = script;
+            }
+        }
+        Ok(scripts)
+    }
+    /// store the script `id` of `scripts`, the store has to be loaded first
+    pub fn commit(&mut self, scripts: &Scripts, id: u8) -> Result<(), ScriptStoreError> {
+        if id as usize >= SCRIPT_COUNT {
+            return Err(ScriptStoreError::UnknownScript);
+        }
+        if !self.records.is_empty() && !self.records.page_full() {
+            return self.append(scripts, id);
+        }
+        // empty scripts are written as well, so older versions on the other page are hidden
+        for id in 0..SCRIPT_COUNT as u8 {
+            self.append(scripts, id)?;
+        }
+        Ok(())
+    }
+    fn append(&mut self, scripts: &Scripts, id: u8) -> Result<(), ScriptStoreError> {
+        self.records
+            .append(&encode(id, &scripts[id as usize]))
+            .map_err(|_| ScriptStoreError::Flash)
+    }
+}
+
+fn encode(id: u8, script: &Script) -> [u8; PAYLOAD_SIZE] {
+    let mut payload = [0; PAYLOAD_SIZE];
+    payload[0] = id;
+    payload[1] = script.steps().len() as u8;
+    let steps = payload[PAYLOAD_HEADER_SIZE..].chunks_exact_mut(STEP_SIZE);
+    for (bytes, step) in steps.zip(script.steps()) {
+        bytes.copy_from_slice(&step.encode());
+    }
+    payload
+}
+
+/// script id and script of a valid record
+fn decode(payload: &[u8]) -> Option<(u8, Script)> {
+    let id = payload[0];
+    let len = payload[1] as usize;
+    if id as usize >= SCRIPT_COUNT || len > MAX_STEPS {
+        return None;
+    }
+    let mut script = Script::default();
+    let steps = payload[PAYLOAD_HEADER_SIZE..PAYLOAD_SIZE]
+        .chunks_exact(STEP_SIZE)
+        .take(len);
+    for (index, bytes) in steps.enumerate() {
+        let step = Step::decode(bytes.try_into().unwrap())?;
+        script.set_step(index as u8, step).ok()?;
+    }
+    Some((id, script))
+}
@@ -0,0 +1,48 @@
+//! Flash shared by the configuration, script, event log and telecommand key stores
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::{Mutex, raw::RawMutex};
+use embedded_storage::nor_flash::{ErrorType, NorFlash, ReadNorFlash};
+
+#[cfg(feature = "stm32")]
+pub type BoardFlash = SharedFlash<
+    'static,
+    embassy_sync::blocking_mutex::raw::ThreadModeRawMutex,
+    embassy_stm32::flash::Flash<'static, embassy_stm32::flash::Blocking>,
+>;
+
+/// Handle to a flash used by several stores, every access locks the flash
+pub struct SharedFlash<'a, M: RawMutex, F> {
+    flash: &'a Mutex<M, RefCell<F>>,
+}
+impl<'a, M: RawMutex, F> SharedFlash<'a, M, F> {
+    pub fn new(flash: &'a Mutex<M, RefCell<F>>) -> Self {
+        Self { flash }
+    }
+}
+impl<M: RawMutex, F: ErrorType> ErrorType for SharedFlash<'_, M, F> {
+    type Error = F::Error;
+}
+impl<M: RawMutex, F: ReadNorFlash> ReadNorFlash for SharedFlash<'_, M, F> {
+    const READ_SIZE: usize = F::READ_SIZE;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        self.flash
+            .lock(|flash| flash.borrow_mut().read(offset, bytes))
+    }
+    fn capacity(&self) -> usize {
+        self.flash.lock(|flash| flash.borrow().capacity())
+    }
+}
+impl<M: RawMutex, F: NorFlash> NorFlash for SharedFlash<'_, M, F> {
+    const WRITE_SIZE: usize = F::WRITE_SIZE;
+    const ERASE_SIZE: usize = F::ERASE_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        self.flash.lock(|flash| flash.borrow_mut().erase(from, to))
+    }
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.flash
+            .lock(|flash| flash.borrow_mut().write(offset, bytes))
+    }
+}
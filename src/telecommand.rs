@@ -0,0 +1,175 @@
+//! Numbering and acknowledgement of received telecommands. Every frame addressed
+//! to the EPS gets a sequence number on reception which is repeated in all of its
+//! acknowledgements.
+//! Time tagged frames start with a [`TimeTag`] header of the reference and the
+//! execution time in ms, `[reference, time (u32 le), telecommand..]`
+pub mod auth;
+
+use defmt::Format;
+use embedded_storage::nor_flash::NorFlash;
+use south_common::{TMValue, telemetry::eps as tm, types::Telecommand};
+
+use crate::EpsTMContainer;
+use auth::TcAuth;
+
+/// Telecommand tagged with its reception sequence number
+pub struct ReceivedTc {
+    pub seq: u16,
+    pub cmd: Telecommand,
+    /// execution time of a queued telecommand, `None` executes right away
+    pub time_tag: Option<TimeTag>,
+}
+
+/// Execution time of a time tagged telecommand
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum TimeTag {
+    /// ms since boot
+    Boot(u32),
+    /// ms of synchronized mission time
+    Mission(u32),
+}
+
+pub const TIME_TAG_SIZE: usize = 5;
+
+impl TimeTag {
+    fn read(header: &[u8]) -> Option<Self> {
+        let time = u32::from_le_bytes(header.get(1..TIME_TAG_SIZE)?.try_into().unwrap());
+        match header[0] {
+            0 => Some(Self::Boot(time)),
+            1 => Some(Self::Mission(time)),
+            _ => None,
+        }
+    }
+    /// header as read by [`TcSequencer::receive_time_tagged`], also used to stamp telemetry
+    pub fn write(&self) -> [u8; TIME_TAG_SIZE] {
+        let (reference, time) = match self {
+            Self::Boot(time) => (0, time),
+            Self::Mission(time) => (1, time),
+        };
+        let [t0, t1, t2, t3] = time.to_le_bytes();
+        [reference, t0, t1, t2, t3]
+    }
+}
+
+/// Stage of the telecommand handling an acknowledgement reports on
+#[repr(u8)]
+#[derive(Format, Clone, Copy, PartialEq, Eq)]
+pub enum AckStage {
+    Acceptance,
+    Start,
+    Completion,
+}
+
+/// Failure code of an acknowledgement, 0 is reported on success
+#[repr(u8)]
+#[derive(Format, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcFailure {
+    /// frame could not be parsed
+    Malformed = 1,
+    /// addressed to another subsystem
+    NotEps,
+    /// not accepted in the current mode
+    RejectedInMode,
+    UnknownMode,
+    /// the mode can not be entered from the current one
+    IllegalTransition,
+    /// missing or wrong mac
+    Unauthenticated,
+    /// counter not above the last accepted one
+    Replayed,
+    /// critical command sent without arming it first
+    NotArmed,
+    /// arm command for a command that can not be armed
+    UnknownCommand,
+    /// execution time already passed
+    TimeInPast,
+    /// mission time is not synchronized
+    NoMissionTime,
+    QueueFull,
+    /// no queued telecommand with the sequence number
+    NotQueued,
+    UnknownScript,
+    /// unknown step or step index beyond the end of the script
+    InvalidStep,
+    /// a script is already running or the script is edited while running
+    ScriptRunning,
+    /// no burst capture armed or the trigger is not enabled
+    BurstNotArmed,
+    /// burst selection, period, history or threshold out of range
+    InvalidBurst,
+}
+
+/// acknowledgement telemetry, packed as `[stage, failure code, seq_lo, seq_hi]`
+pub fn ack_container(seq: u16, stage: AckStage, result: Result<(), TcFailure>) -> EpsTMContainer {
+    let code = result.err().map_or(0, |e| e as u8);
+    let [seq_low, seq_high] = seq.to_le_bytes();
+    let ack = u32::from_le_bytes([stage as u8, code, seq_low, seq_high]);
+    EpsTMContainer::new(&tm::TcAck, &ack).unwrap()
+}
+
+/// Numbers telecommand frames in order of reception, malformed ones included
+#[derive(Default)]
+pub struct TcSequencer {
+    next_seq: u16,
+}
+
+impl TcSequencer {
+    pub const fn new() -> Self {
+        Self { next_seq: 0 }
+    }
+    /// authenticate and parse a frame, a rejected one yields its acceptance
+    /// failure acknowledgement. Commands of other subsystems share the topic,
+    /// they are ignored without a sequence number and yield `None`
+    pub fn receive<F: NorFlash>(
+        &mut self,
+        frame: &[u8],
+        auth: &mut TcAuth<F>,
+    ) -> Result<Option<ReceivedTc>, EpsTMContainer> {
+        self.receive_frame(frame, false, auth)
+    }
+    /// like [`Self::receive`] for frames starting with a time tag
+    pub fn receive_time_tagged<F: NorFlash>(
+        &mut self,
+        frame: &[u8],
+        auth: &mut TcAuth<F>,
+    ) -> Result<Option<ReceivedTc>, EpsTMContainer> {
+        self.receive_frame(frame, true, auth)
+    }
+    fn receive_frame<F: NorFlash>(
+        &mut self,
+        frame: &[u8],
+        time_tagged: bool,
+        auth: &mut TcAuth<F>,
+    ) -> Result<Option<ReceivedTc>, EpsTMContainer> {
+        if for_other_subsystem(frame, time_tagged) {
+            return Ok(None);
+        }
+        let seq = self.next_seq;
+        self.next_seq = seq.wrapping_add(1);
+        let reject = |failure| ack_container(seq, AckStage::Acceptance, Err(failure));
+        let mut payload = auth.verify(frame).map_err(reject)?;
+        let time_tag = match time_tagged {
+            true => {
+                let tag = TimeTag::read(payload).ok_or_else(|| reject(TcFailure::Malformed))?;
+                payload = &payload[TIME_TAG_SIZE..];
+                Some(tag)
+            }
+            false => None,
+        };
+        match Telecommand::read(payload) {
+            Ok((_, cmd)) => Ok(Some(ReceivedTc { seq, cmd, time_tag })),
+            Err(_) => Err(reject(TcFailure::Malformed)),
+        }
+    }
+}
+
+/// true if the frame holds a command of another subsystem, checked before the
+/// authentication as only the EPS frames carry its trailer. Frames that do not
+/// parse are left to be rejected
+fn for_other_subsystem(frame: &[u8], time_tagged: bool) -> bool {
+    let start = if time_tagged { TIME_TAG_SIZE } else { 0 };
+    let Some(command) = frame.get(start..) else {
+        return false;
+    };
+    matches!(Telecommand::read(command), Ok((_, cmd)) if !matches!(cmd, Telecommand::EPS(_)))
+}
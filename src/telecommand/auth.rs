@@ -0,0 +1,240 @@
+//! Authentication of telecommand frames. An authenticated frame ends in a trailer
+//! of a monotonic counter and a truncated HMAC-SHA256 over counter and payload:
+//! `[payload.., counter (u32 le), mac (8 bytes)]`
+//!
+//! The key is loaded from flash. Builds with the `provision-key` feature store the
+//! key given in `EPS_TC_KEY=<64 hex digits>` on boot, they are flashed once to
+//! provision a board
+use defmt::{Format, warn};
+use embedded_storage::nor_flash::NorFlash;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use south_common::telemetry::eps as tm;
+
+use super::TcFailure;
+use crate::{
+    EpsTMContainer,
+    record_store::{RecordStore, record_size},
+};
+
+pub const KEY_SIZE: usize = 32;
+pub const MAC_SIZE: usize = 8;
+const COUNTER_SIZE: usize = 4;
+pub const TRAILER_SIZE: usize = COUNTER_SIZE + MAC_SIZE;
+/// counters reserved in flash at once. After a reset every counter up to the
+/// reserved one counts as replayed, the ground continues above it
+pub const COUNTER_RESERVE: u32 = 256;
+
+pub type Key = [u8; KEY_SIZE];
+
+/// key given at build time in `EPS_TC_KEY`
+#[cfg(feature = "provision-key")]
+pub const PROVISION_KEY: Key = match option_env!("EPS_TC_KEY") {
+    Some(hex) => parse_key(hex),
+    None => panic!("the provision-key feature needs EPS_TC_KEY=<64 hex digits>"),
+};
+
+#[cfg(feature = "provision-key")]
+const fn hex_digit(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        b'A'..=b'F' => digit - b'A' + 10,
+        _ => panic!("EPS_TC_KEY has to be 64 hex digits"),
+    }
+}
+#[cfg(feature = "provision-key")]
+const fn parse_key(hex: &str) -> Key {
+    let hex = hex.as_bytes();
+    assert!(
+        hex.len() == 2 * KEY_SIZE,
+        "EPS_TC_KEY has to be 64 hex digits"
+    );
+    let mut key = [0; KEY_SIZE];
+    let mut i = 0;
+    while i < KEY_SIZE {
+        key[i] = hex_digit(hex[2 * i]) << 4 | hex_digit(hex[2 * i + 1]);
+        i += 1;
+    }
+    key
+}
+
+fn mac(key: &Key, counter: u32, payload: &[u8]) -> Hmac<Sha256> {
+    // hmac takes keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(&counter.to_le_bytes());
+    mac.update(payload);
+    mac
+}
+
+/// truncated mac of a telecommand payload as expected in the trailer
+pub fn sign(key: &Key, counter: u32, payload: &[u8]) -> [u8; MAC_SIZE] {
+    let tag = mac(key, counter, payload).finalize().into_bytes();
+    tag[..MAC_SIZE].try_into().unwrap()
+}
+
+#[repr(u8)]
+#[derive(Format, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyStoreError {
+    Flash = 1,
+    /// no valid key record in flash
+    NoKey,
+}
+
+const MAGIC: u16 = 0x7C4B;
+/// bumped whenever the record layout changes, keys of other layouts are ignored
+const LAYOUT_VERSION: u8 = 2;
+// key and highest reserved counter
+const PAYLOAD_SIZE: usize = KEY_SIZE + COUNTER_SIZE;
+const RECORD_SIZE: usize = record_size(PAYLOAD_SIZE);
+
+/// Telecommand key and its highest reserved counter in a [`RecordStore`]. Every
+/// reservation appends a record holding the key again, the latest record wins
+pub struct KeyStore<F: NorFlash> {
+    records: RecordStore<F, RECORD_SIZE>,
+    key: Option<Key>,
+}
+
+impl<F: NorFlash> KeyStore<F> {
+    /// `offset` is the start of two erase pages reserved for the key
+    pub fn new(flash: F, offset: u32) -> Self {
+        Self {
+            records: RecordStore::new(flash, offset, MAGIC, LAYOUT_VERSION),
+            key: None,
+        }
+    }
+    fn append(&mut self, key: &Key, counter: u32) -> Result<(), KeyStoreError> {
+        let mut payload = [0; PAYLOAD_SIZE];
+        payload[..KEY_SIZE].copy_from_slice(key);
+        payload[KEY_SIZE..].copy_from_slice(&counter.to_le_bytes());
+        self.records
+            .append(&payload)
+            .map_err(|_| KeyStoreError::Flash)
+    }
+    /// store a new key, its counter starts over
+    pub fn provision(&mut self, key: &Key) -> Result<(), KeyStoreError> {
+        self.records.load().map_err(|_| KeyStoreError::Flash)?;
+        self.append(key, 0)?;
+        self.key = Some(*key);
+        Ok(())
+    }
+    /// provision `key` unless it is already stored, so its counters are kept
+    /// over reflashing the same key
+    pub fn ensure(&mut self, key: &Key) -> Result<(), KeyStoreError> {
+        match self.load() {
+            Ok((stored, _)) if stored == *key => Ok(()),
+            _ => self.provision(key),
+        }
+    }
+    /// key and highest reserved counter
+    pub fn load(&mut self) -> Result<(Key, u32), KeyStoreError> {
+        self.records.load().map_err(|_| KeyStoreError::Flash)?;
+        let record = self
+            .records
+            .latest()
+            .map_err(|_| KeyStoreError::Flash)?
+            .ok_or(KeyStoreError::NoKey)?;
+        let (key, counter) = record.payload()[..PAYLOAD_SIZE].split_at(KEY_SIZE);
+        let key: Key = key.try_into().unwrap();
+        self.key = Some(key);
+        Ok((key, u32::from_le_bytes(counter.try_into().unwrap())))
+    }
+    /// reserve all counters up to `counter`, the key has to be loaded first
+    pub fn reserve(&mut self, counter: u32) -> Result<(), KeyStoreError> {
+        let key = self.key.ok_or(KeyStoreError::NoKey)?;
+        self.append(&key, counter)
+    }
+}
+
+/// Frames rejected since boot
+#[derive(Format, Clone, Copy, Default)]
+pub struct RejectCounts {
+    /// missing or wrong mac
+    pub invalid: u16,
+    pub replayed: u16,
+}
+
+/// Checks and strips the trailer of authenticated telecommand frames. Builds
+/// with the `flatsat` feature accept plain frames without a trailer
+pub struct TcAuth<F: NorFlash> {
+    store: KeyStore<F>,
+    key: Option<Key>,
+    last_counter: u32,
+    reserved: u32,
+    rejects: RejectCounts,
+}
+
+impl<F: NorFlash> TcAuth<F> {
+    /// load the key, without a key every frame is rejected
+    pub fn new(mut store: KeyStore<F>) -> Self {
+        let (key, reserved) = match store.load() {
+            Ok((key, reserved)) => (Some(key), reserved),
+            Err(e) => {
+                warn!("no telecommand key: {}", e);
+                (None, 0)
+            }
+        };
+        Self {
+            store,
+            key,
+            last_counter: reserved,
+            reserved,
+            rejects: RejectCounts::default(),
+        }
+    }
+    pub fn rejects(&self) -> RejectCounts {
+        self.rejects
+    }
+    /// payload of an authentic frame that was not received before
+    pub fn verify<'f>(&mut self, frame: &'f [u8]) -> Result<&'f [u8], TcFailure> {
+        if cfg!(feature = "flatsat") {
+            return Ok(frame);
+        }
+        let result = self.check(frame);
+        match result {
+            Ok(_) => (),
+            Err(TcFailure::Replayed) => {
+                self.rejects.replayed = self.rejects.replayed.wrapping_add(1)
+            }
+            Err(_) => self.rejects.invalid = self.rejects.invalid.wrapping_add(1),
+        }
+        result
+    }
+    fn check<'f>(&mut self, frame: &'f [u8]) -> Result<&'f [u8], TcFailure> {
+        let key = self.key.as_ref().ok_or(TcFailure::Unauthenticated)?;
+        let payload_len = frame
+            .len()
+            .checked_sub(TRAILER_SIZE)
+            .ok_or(TcFailure::Unauthenticated)?;
+        let (payload, trailer) = frame.split_at(payload_len);
+        let (counter, tag) = trailer.split_at(COUNTER_SIZE);
+        let counter = u32::from_le_bytes(counter.try_into().unwrap());
+        mac(key, counter, payload)
+            .verify_truncated_left(tag)
+            .map_err(|_| TcFailure::Unauthenticated)?;
+        if counter <= self.last_counter {
+            return Err(TcFailure::Replayed);
+        }
+        if counter > self.reserved {
+            let reserved = counter.saturating_add(COUNTER_RESERVE);
+            match self.store.reserve(reserved) {
+                Ok(()) => self.reserved = reserved,
+                // still accepted, the next frame retries in the next slot
+                Err(e) => warn!("telecommand counter not reserved: {}", e),
+            }
+        }
+        self.last_counter = counter;
+        Ok(payload)
+    }
+    /// rejected frames packed as `[invalid_lo, invalid_hi, replayed_lo, replayed_hi]`
+    pub fn rejects_container(&self) -> EpsTMContainer {
+        let [invalid_low, invalid_high] = self.rejects.invalid.to_le_bytes();
+        let [replayed_low, replayed_high] = self.rejects.replayed.to_le_bytes();
+        let rejects = u32::from_le_bytes([invalid_low, invalid_high, replayed_low, replayed_high]);
+        EpsTMContainer::new(&tm::TcAuthRejects, &rejects).unwrap()
+    }
+    /// last accepted counter, the ground continues above it after a reset
+    pub fn counter_container(&self) -> EpsTMContainer {
+        EpsTMContainer::new(&tm::TcAuthCounter, &self.last_counter).unwrap()
+    }
+}
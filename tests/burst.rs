@@ -0,0 +1,240 @@
+use core::cell::Cell;
+
+use critical_section as _;
+
+use embassy_futures::block_on;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel, watch::Watch};
+use embassy_time::{Duration, Instant};
+use eps_software::{
+    EpsTMContainer,
+    adc::{
+        AdcCtrl, AdcCtrlChannel,
+        burst::{
+            self, BUFFER_LEN, BurstCapture, BurstConfig, BurstService, CaptureState, Edge,
+            FRAME_VALUES, Threshold, Trigger, TriggerSet,
+        },
+        stats::SharedStatistics,
+    },
+    mock::adc::{MockAdc, MockAdcChannel},
+    telecommand::TcFailure,
+};
+use south_common::{TelemetryContainer, TelemetryDefinition, telemetry::eps as tm};
+
+fn config(channels: u16, pre_trigger_percent: u8, triggers: TriggerSet) -> BurstConfig {
+    BurstConfig {
+        channels,
+        period_100us: 10,
+        pre_trigger_percent,
+        triggers,
+    }
+}
+
+/// all values of a complete capture, oldest first
+fn values(capture: &BurstCapture) -> Vec<i16> {
+    let mut values = vec![0; capture.len()];
+    assert!(capture.read(0, &mut values) == values.len());
+    values
+}
+
+#[test]
+fn history_before_the_trigger_is_kept() {
+    let mut capture = BurstCapture::new();
+    capture.arm(config(0b101, 25, TriggerSet::EMPTY)).unwrap();
+    let samples = BUFFER_LEN / 2;
+    let post_trigger = samples - samples / 4;
+
+    // the history wraps around while waiting for the trigger
+    for sample in 0..3_000 {
+        assert!(capture.record(&[sample, -sample]).is_none());
+    }
+    assert!(capture.trigger(Trigger::Command));
+    for sample in 3_000..3_000 + post_trigger as i16 - 1 {
+        assert!(capture.record(&[sample, -sample]).is_none());
+    }
+    assert!(capture.header().is_none());
+    let last = 3_000 + post_trigger as i16 - 1;
+    assert!(
+        capture.record(&[last, -last])
+            == Some(CaptureState::Complete {
+                trigger: Trigger::Command
+            })
+    );
+    // samples of a complete capture are not recorded anymore
+    assert!(capture.record(&[0, 0]).is_none());
+
+    let (_, header) = capture.header().unwrap();
+    let header = header.to_le_bytes();
+    assert!(u16::from_le_bytes([header[0], header[1]]) == 0b101);
+    assert!(u16::from_le_bytes([header[2], header[3]]) as usize == samples);
+    assert!(u16::from_le_bytes([header[4], header[5]]) as usize == samples / 4);
+    assert!(header[6] == Trigger::Command as u8);
+
+    let first = last + 1 - samples as i16;
+    let expected: Vec<_> = (first..=last).flat_map(|s| [s, -s]).collect();
+    assert!(values(&capture) == expected);
+}
+
+#[test]
+fn early_trigger_shortens_the_history() {
+    let mut capture = BurstCapture::new();
+    capture.arm(config(0b1, 50, TriggerSet::EMPTY)).unwrap();
+    for sample in 0..10 {
+        capture.record(&[sample]);
+    }
+    capture.trigger(Trigger::Command);
+    for sample in 10..10 + BUFFER_LEN as i16 / 2 {
+        capture.record(&[sample]);
+    }
+    let (_, header) = capture.header().unwrap();
+    let header = header.to_le_bytes();
+    assert!(u16::from_le_bytes([header[2], header[3]]) as usize == 10 + BUFFER_LEN / 2);
+    assert!(u16::from_le_bytes([header[4], header[5]]) == 10);
+    assert!(values(&capture)[..12] == [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+}
+
+#[test]
+fn only_enabled_triggers_start_the_capture() {
+    let mut capture = BurstCapture::new();
+    assert!(!capture.trigger(Trigger::Command));
+
+    let triggers = TriggerSet::EMPTY.with(Trigger::SinkEnable);
+    capture.arm(config(0b1, 50, triggers)).unwrap();
+    assert!(!capture.trigger(Trigger::SourceSwitch));
+    assert!(capture.state() == CaptureState::Armed);
+    assert!(capture.trigger(Trigger::SinkEnable));
+    // the first trigger wins
+    assert!(!capture.trigger(Trigger::Command));
+
+    capture.disarm();
+    assert!(capture.state() == CaptureState::Idle);
+    assert!(capture.sampling().is_none());
+}
+
+#[test]
+fn threshold_crossing_triggers_the_capture() {
+    let mut capture = BurstCapture::new();
+    capture
+        .set_threshold(Threshold {
+            channel: 2,
+            level: 6_00,
+            edge: Edge::Falling,
+        })
+        .unwrap();
+    let triggers = TriggerSet::EMPTY.with(Trigger::Threshold);
+    capture.arm(config(0b101, 50, triggers)).unwrap();
+
+    // channel 2 is the second value of a sample
+    for voltage in [7_40, 7_00, 6_50, 6_20] {
+        assert!(capture.record(&[0, voltage]).is_none());
+    }
+    assert!(matches!(
+        capture.record(&[0, 5_90]),
+        Some(CaptureState::Triggered {
+            trigger: Trigger::Threshold,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn threshold_changed_while_armed_has_to_be_captured() {
+    let mut capture = BurstCapture::new();
+    let on_channel = |channel| Threshold {
+        channel,
+        level: 6_00,
+        edge: Edge::Falling,
+    };
+    capture.set_threshold(on_channel(0)).unwrap();
+    let triggers = TriggerSet::EMPTY.with(Trigger::Threshold);
+    capture.arm(config(0b101, 50, triggers)).unwrap();
+    assert!(capture.record(&[7_00, 0]).is_none());
+
+    // channel 3 is not sampled, the armed capture keeps its threshold
+    assert!(capture.set_threshold(on_channel(3)) == Err(TcFailure::InvalidBurst));
+    assert!(capture.record(&[7_00, 0]).is_none());
+    // moving to channel 2 does not compare against the last value of channel 0
+    capture.set_threshold(on_channel(2)).unwrap();
+    assert!(capture.record(&[0, 5_00]).is_none());
+    assert!(capture.record(&[0, 7_00]).is_none());
+    assert!(capture.record(&[0, 5_00]).is_some());
+}
+
+#[test]
+fn invalid_bursts_are_rejected() {
+    let mut capture = BurstCapture::new();
+    let invalid = Err(TcFailure::InvalidBurst);
+    assert!(capture.arm(config(0, 50, TriggerSet::EMPTY)) == invalid);
+    assert!(capture.arm(config(1 << 12, 50, TriggerSet::EMPTY)) == invalid);
+    assert!(capture.arm(config(0b1, 100, TriggerSet::EMPTY)) == invalid);
+    let no_period = BurstConfig {
+        period_100us: 0,
+        ..config(0b1, 50, TriggerSet::EMPTY)
+    };
+    assert!(capture.arm(no_period) == invalid);
+
+    // the threshold channel has to be captured
+    let threshold = TriggerSet::EMPTY.with(Trigger::Threshold);
+    assert!(capture.arm(config(0b1, 50, threshold)) == invalid);
+    let on_channel_1 = Threshold {
+        channel: 1,
+        level: 0,
+        edge: Edge::Rising,
+    };
+    capture.set_threshold(on_channel_1).unwrap();
+    assert!(capture.arm(config(0b1, 50, threshold)) == invalid);
+    assert!(capture.arm(config(0b11, 50, threshold)).is_ok());
+    assert!(capture.state() == CaptureState::Armed);
+}
+
+#[test]
+fn armed_captures_are_sampled_and_streamed() {
+    // one external input and the internal temperature sensor
+    let inputs = [Cell::new(1_234), Cell::new(0)];
+    let watches: [Watch<CriticalSectionRawMutex, i16, 1>; 2] = [Watch::new(), Watch::new()];
+    let stats = [SharedStatistics::new(), SharedStatistics::new()];
+    let mut adc: AdcCtrl<'_, MockAdc<'_>, 2> = AdcCtrl::new(
+        MockAdc::new(&inputs),
+        watches[1].dyn_sender(),
+        &stats[1],
+        [AdcCtrlChannel::new(
+            MockAdcChannel(0),
+            watches[0].dyn_sender(),
+            &stats[0],
+            |raw| raw as i16,
+        )],
+    );
+    let tm: Channel<CriticalSectionRawMutex, EpsTMContainer, 64> = Channel::new();
+    let mut service = BurstService::new(tm.dyn_sender());
+
+    // a short capture, almost all of it is history
+    burst::arm(config(0b1, 99, TriggerSet::EMPTY)).unwrap();
+    let post_trigger = BUFFER_LEN - BUFFER_LEN * 99 / 100;
+    assert!(burst::trigger(Trigger::Command));
+    block_on(async {
+        adc.sample_bursts_until(Instant::now() + Duration::from_millis(10))
+            .await;
+        // the regular measurement is not mixed into the capture
+        inputs[0].set(999);
+        adc.run().await;
+        inputs[0].set(1_234);
+        let sampling_time = Duration::from_millis(post_trigger as u64 + 20);
+        adc.sample_bursts_until(Instant::now() + sampling_time)
+            .await;
+        assert!(burst::sampling().is_none());
+
+        service.run().await;
+        let header = tm.try_receive().unwrap();
+        assert!(header.id() == tm::BurstHeader.id());
+        assert!(header.bytes()[2..6] == [post_trigger as u8, 0, 0, 0]);
+        let frames: Vec<_> = core::iter::from_fn(|| tm.try_receive().ok()).collect();
+        assert!(frames.len() == post_trigger.div_ceil(FRAME_VALUES));
+        let frame = frames[1].bytes();
+        assert!(frame[0..2] == (FRAME_VALUES as u16).to_le_bytes());
+        assert!(frame[2..4] == 1_234i16.to_le_bytes());
+        assert!(frames.iter().all(|frame| {
+            frame.bytes()[2..]
+                .chunks_exact(2)
+                .all(|value| value != 999i16.to_le_bytes())
+        }));
+    });
+}
@@ -0,0 +1,18 @@
+//! Flash fixture shared by the tests of the stores built on the record store
+#![allow(dead_code)]
+
+use core::cell::Cell;
+
+use eps_software::mock::flash::MOCK_PAGE_SIZE;
+
+/// the two erased pages of a record store
+pub fn erased_memory() -> Vec<Cell<u8>> {
+    (0..2 * MOCK_PAGE_SIZE).map(|_| Cell::new(0xFF)).collect()
+}
+
+/// leave the last double word of the record at `offset` erased, as after an
+/// interrupted write
+pub fn interrupt_write(memory: &[Cell<u8>], offset: usize, record_size: usize) {
+    let end = offset + record_size;
+    memory[end - 8..end].iter().for_each(|cell| cell.set(0xFF));
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use embassy_time::Duration;
+
+use common::{erased_memory, interrupt_write};
+use eps_software::{
+    config::{
+        ConfigError, EpsConfig,
+        param::{PARAM_COUNT, Param, ParamKind},
+        store::{ConfigStore, RECORD_SIZE},
+    },
+    control_loop::mode::Mode,
+    mock::flash::MockFlash,
+};
+
+fn config_with_tm_interval(interval_ms: i32) -> EpsConfig {
+    let mut config = EpsConfig::DEFAULT;
+    config.set(Param::TmIntervalMs, interval_ms).unwrap();
+    config
+}
+
+#[test]
+fn erased_flash_has_no_record() {
+    let memory = erased_memory();
+    let mut store = ConfigStore::new(MockFlash::new(&memory), 0);
+    assert!(store.load() == Err(ConfigError::NoRecord));
+}
+
+#[test]
+fn committed_config_is_loaded_after_reset() {
+    let memory = erased_memory();
+    let mut store = ConfigStore::new(MockFlash::new(&memory), 0);
+    let _ = store.load();
+    store.commit(&config_with_tm_interval(1_000)).unwrap();
+    store.commit(&config_with_tm_interval(2_000)).unwrap();
+
+    let mut store = ConfigStore::new(MockFlash::new(&memory), 0);
+    assert!(store.load() == Ok(config_with_tm_interval(2_000)));
+}
+
+#[test]
+fn corrupted_record_falls_back_to_previous() {
+    let memory = erased_memory();
+    let mut store = ConfigStore::new(MockFlash::new(&memory), 0);
+    let _ = store.load();
+    store.commit(&config_with_tm_interval(1_000)).unwrap();
+    store.commit(&config_with_tm_interval(2_000)).unwrap();
+    // interrupted write of the second record
+    interrupt_write(&memory, RECORD_SIZE, RECORD_SIZE);
+
+    let mut store = ConfigStore::new(MockFlash::new(&memory), 0);
+    assert!(store.load() == Ok(config_with_tm_interval(1_000)));
+    // the broken slot is skipped
+    store.commit(&config_with_tm_interval(3_000)).unwrap();
+    let mut store = ConfigStore::new(MockFlash::new(&memory), 0);
+    assert!(store.load() == Ok(config_with_tm_interval(3_000)));
+}
+
+#[test]
+fn out_of_range_values_are_rejected() {
+    let mut config = EpsConfig::DEFAULT;
+    assert!(config.set(Param::AdcLoopMs, 5_000) == Err(ConfigError::OutOfRange));
+    assert!(config.set(Param::DividerR2Ohm, 0) == Err(ConfigError::OutOfRange));
+    assert!(config.set(Param::BatOvervoltage, 6_00) == Err(ConfigError::OutOfRange));
+    assert!(config == EpsConfig::DEFAULT);
+    assert!(Param::from_id(PARAM_COUNT as u8).is_none());
+}
+
+#[test]
+fn overlapping_voltage_windows_are_rejected() {
+    let mut config = EpsConfig::DEFAULT;
+    config.set(Param::BatUndervoltage, 7_50).unwrap();
+    // recovering from an undervoltage at 7.9 V would be an overvoltage until 7.6 V
+    assert!(config.set(Param::BatOvervoltage, 7_80) == Err(ConfigError::VoltageWindowsOverlap));
+    config.set(Param::BatOvervoltage, 8_20).unwrap();
+    let limits = config.battery_voltage_limits();
+    assert!(limits.under_recover_10mv < limits.over_recover_10mv);
+    assert!(config.set(Param::BatOvervoltage, 8_10) == Err(ConfigError::VoltageWindowsOverlap));
+    assert!(config.get(Param::BatOvervoltage) == 8_20);
+}
+
+#[test]
+fn parameter_table_defaults_are_valid() {
+    for param in Param::ALL {
+        let info = param.info();
+        assert!(info.contains(info.default), "{}", param as u8);
+        assert!(Param::from_id(param as u8) == Some(param));
+    }
+}
+
+#[test]
+fn signed_parameters_use_twos_complement() {
+    let mut config = EpsConfig::DEFAULT;
+    assert!(Param::BatMinTemperature.info().kind == ParamKind::Signed);
+    config
+        .set_raw(Param::BatMinTemperature, (-30_0i16) as u16)
+        .unwrap();
+    assert!(config.get(Param::BatMinTemperature) == -30_0);
+    assert!(config.battery_temperature_limits().min_tenth_deg == -30_0);
+    // out of the signed range although the raw value is small
+    assert!(config.set_raw(Param::BatMinTemperature, 20_0) == Err(ConfigError::OutOfRange));
+}
+
+#[test]
+fn comm_loss_only_times_out_on_the_pad() {
+    let config = EpsConfig::DEFAULT;
+    assert!(config.comm_loss_timeout(Mode::Standby).is_some());
+    // safe mode after landing would turn off the GPS
+    for mode in [Mode::Launch, Mode::Flight, Mode::Recovery, Mode::Safe] {
+        assert!(config.comm_loss_timeout(mode).is_none());
+    }
+    let mut config = EpsConfig::DEFAULT;
+    config.set(Param::RecoveryCommLossS, 3_600).unwrap();
+    assert!(config.comm_loss_timeout(Mode::Recovery) == Some(Duration::from_secs(3_600)));
+}
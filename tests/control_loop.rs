@@ -0,0 +1,1008 @@
+use core::cell::Cell;
+
+use critical_section as _;
+
+use embassy_futures::{block_on, select::select3};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel, watch::Watch};
+use embassy_time::{Duration, Instant, Timer};
+use eps_software::{
+    EpsTMContainer,
+    config::{ConfigCommand, EpsConfig, param::Param},
+    control_loop::{
+        ControlLoop, PowerReadings,
+        arming::CriticalCommand,
+        mode::{Mode, ModeReason},
+        power_budget::{LOAD_SHED_TABLE, PowerBudget},
+    },
+    event_log::LogCommand,
+    mission_time,
+    mock::gpio::{MockFlipFlopChip, MockInputPin, MockNet, MockOutputPin},
+    protection::{
+        ProtectionEvent,
+        overcurrent::FuseStatus,
+        temperature::{FaultResponse, TemperatureStatus},
+        voltage::VoltageStatus,
+    },
+    pwr_src::{
+        d_flip_flop::{DFlipFlop, FlipFlopInput},
+        sink_ctrl::{SinkCtrl, SinkSet, sink_index},
+    },
+    script::{Script, ScriptState, Scripts, Step, progress_container},
+    telecommand::{AckStage, ReceivedTc, TcFailure, TimeTag, ack_container},
+};
+use south_common::{
+    TelemetryContainer, TelemetryDefinition,
+    telemetry::eps as tm,
+    types::{EPSCommand, FlipFlopState, Sink, Telecommand},
+};
+
+type MockControlLoop<'a> = ControlLoop<'a, MockOutputPin<'a>, MockInputPin<'a>, MockOutputPin<'a>>;
+
+/// Channels and measurements connecting the control loop to the other tasks
+struct Links {
+    cmds: Channel<CriticalSectionRawMutex, ReceivedTc, 4>,
+    next_seq: Cell<u16>,
+    protection: Channel<CriticalSectionRawMutex, ProtectionEvent, 4>,
+    fuse_resets: Channel<CriticalSectionRawMutex, Sink, 2>,
+    config: Channel<CriticalSectionRawMutex, ConfigCommand, 2>,
+    scripts: Channel<CriticalSectionRawMutex, (u8, Script), 1>,
+    log: Channel<CriticalSectionRawMutex, LogCommand, 2>,
+    tm: Channel<CriticalSectionRawMutex, EpsTMContainer, 16>,
+    bus_voltage: Watch<CriticalSectionRawMutex, i16, 1>,
+    soc: [Watch<CriticalSectionRawMutex, u16, 1>; 2],
+}
+impl Links {
+    fn new() -> Self {
+        Self {
+            cmds: Channel::new(),
+            next_seq: Cell::new(0),
+            protection: Channel::new(),
+            fuse_resets: Channel::new(),
+            config: Channel::new(),
+            scripts: Channel::new(),
+            log: Channel::new(),
+            tm: Channel::new(),
+            bus_voltage: Watch::new(),
+            soc: [Watch::new(), Watch::new()],
+        }
+    }
+    /// send a telecommand as the tc thread would, returns its sequence number
+    async fn send_tc(&self, cmd: Telecommand, time_tag: Option<TimeTag>) -> u16 {
+        let seq = self.next_seq.get();
+        self.next_seq.set(seq.wrapping_add(1));
+        let tc = ReceivedTc { seq, cmd, time_tag };
+        self.cmds.send(tc).await;
+        seq
+    }
+    async fn command(&self, cmd: EPSCommand) -> u16 {
+        self.send_tc(Telecommand::EPS(cmd), None).await
+    }
+    /// queue a command for `delay` from now
+    async fn command_in(&self, delay: Duration, cmd: EPSCommand) -> u16 {
+        let at_ms = (Instant::now() + delay).as_millis() as u32;
+        self.send_tc(Telecommand::EPS(cmd), Some(TimeTag::Boot(at_ms)))
+            .await
+    }
+}
+
+struct Board {
+    flip_flop: MockFlipFlopChip,
+    sinks: [MockNet; 4],
+}
+impl Board {
+    fn new() -> Self {
+        Self {
+            flip_flop: MockFlipFlopChip::new(),
+            sinks: core::array::from_fn(|_| MockNet::new(true)),
+        }
+    }
+    async fn control_loop<'a>(
+        &'a self,
+        links: &'a Links,
+        boot_reason: ModeReason,
+        eps_config: EpsConfig,
+    ) -> MockControlLoop<'a> {
+        let (bat_1, bat_2, aux_pwr, clk) = self.flip_flop.pins();
+        let flip_flop = DFlipFlop::from_pins(bat_1, bat_2, aux_pwr, clk);
+        let sink_ctrl = SinkCtrl::from_pins(
+            MockOutputPin::new(&self.sinks[0]),
+            MockOutputPin::new(&self.sinks[1]),
+            MockOutputPin::new(&self.sinks[2]),
+            MockOutputPin::new(&self.sinks[3]),
+        );
+        ControlLoop::spawn(
+            boot_reason,
+            eps_config,
+            Scripts::default(),
+            flip_flop,
+            sink_ctrl,
+            links.cmds.dyn_receiver(),
+            links.protection.dyn_receiver(),
+            links.fuse_resets.dyn_sender(),
+            links.config.dyn_sender(),
+            links.scripts.dyn_sender(),
+            links.log.dyn_sender(),
+            PowerReadings {
+                bus_voltage: links.bus_voltage.dyn_receiver().unwrap(),
+                soc: links.soc.each_ref().map(|w| w.dyn_receiver().unwrap()),
+            },
+            links.tm.dyn_sender(),
+        )
+        .await
+    }
+    /// enabled state of bat 1, bat 2 and aux pwr
+    fn sources(&self) -> [bool; 3] {
+        self.flip_flop.state.each_ref().map(MockNet::is_high)
+    }
+    fn gps_enabled(&self) -> bool {
+        self.sinks[2].is_high()
+    }
+    fn rocket_hd_enabled(&self) -> bool {
+        self.sinks[3].is_high()
+    }
+}
+
+/// run the control loop, returns the telemetry sent meanwhile
+async fn run_for(
+    control_loop: &mut MockControlLoop<'_>,
+    links: &Links,
+    duration: Duration,
+) -> Vec<EpsTMContainer> {
+    let mut tm = Vec::new();
+    select3(
+        async {
+            loop {
+                control_loop.run().await
+            }
+        },
+        async {
+            loop {
+                tm.push(links.tm.receive().await)
+            }
+        },
+        Timer::after(duration),
+    )
+    .await;
+    tm
+}
+
+#[test]
+fn timed_overrides_do_not_block_and_overlap() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::PowerOn, EpsConfig::DEFAULT));
+
+    block_on(async {
+        links
+            .command(EPSCommand::DisableSink(Sink::GPS, Some(1)))
+            .await;
+        links
+            .command(EPSCommand::DisableSink(Sink::RocketHD, Some(2)))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(200)).await;
+        assert!(!board.gps_enabled());
+        assert!(!board.rocket_hd_enabled());
+
+        run_for(&mut control_loop, &links, Duration::from_millis(1000)).await;
+        assert!(board.gps_enabled());
+        assert!(!board.rocket_hd_enabled());
+
+        run_for(&mut control_loop, &links, Duration::from_millis(1000)).await;
+        assert!(board.rocket_hd_enabled());
+    });
+}
+
+#[test]
+fn untimed_command_cancels_pending_revert() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::PowerOn, EpsConfig::DEFAULT));
+
+    block_on(async {
+        links
+            .command(EPSCommand::DisableSink(Sink::GPS, Some(1)))
+            .await;
+        links
+            .command(EPSCommand::DisableSink(Sink::GPS, None))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(1200)).await;
+        assert!(!board.gps_enabled());
+    });
+}
+
+#[test]
+fn undervoltage_battery_is_dropped_from_power_path() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::PowerOn, EpsConfig::DEFAULT));
+
+    block_on(async {
+        let undervoltage = VoltageStatus::Undervoltage;
+        links
+            .protection
+            .send(ProtectionEvent::BatteryVoltage(
+                FlipFlopInput::Bat1,
+                undervoltage,
+            ))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert_eq!(board.sources(), [false, true, true]);
+
+        links
+            .protection
+            .send(ProtectionEvent::BatteryVoltage(
+                FlipFlopInput::Bat2,
+                undervoltage,
+            ))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        // aux power was never measured within its limits, keep a battery
+        assert_eq!(board.sources(), [true, false, false]);
+
+        links
+            .protection
+            .send(ProtectionEvent::AuxVoltage(VoltageStatus::Nominal))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert_eq!(board.sources(), [false, false, true]);
+
+        let nominal = VoltageStatus::Nominal;
+        links
+            .protection
+            .send(ProtectionEvent::BatteryVoltage(
+                FlipFlopInput::Bat1,
+                nominal,
+            ))
+            .await;
+        links
+            .protection
+            .send(ProtectionEvent::BatteryVoltage(
+                FlipFlopInput::Bat2,
+                nominal,
+            ))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert_eq!(board.sources(), [true, true, true]);
+    });
+}
+
+#[test]
+fn hot_battery_sheds_sinks_until_recovered() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::PowerOn, EpsConfig::DEFAULT));
+
+    block_on(async {
+        links
+            .command(EPSCommand::DisableSink(Sink::RocketHD, None))
+            .await;
+        let shed = SinkSet::EMPTY.with(Sink::GPS).with(Sink::RocketHD);
+        links
+            .protection
+            .send(ProtectionEvent::BatteryTemperature(
+                FlipFlopInput::Bat1,
+                TemperatureStatus::Overtemperature,
+                FaultResponse::ShedSinks(shed),
+            ))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert!(!board.gps_enabled());
+        assert_eq!(board.sources(), [true, true, true]);
+
+        links
+            .protection
+            .send(ProtectionEvent::BatteryTemperature(
+                FlipFlopInput::Bat1,
+                TemperatureStatus::Nominal,
+                FaultResponse::Ignore,
+            ))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert!(board.gps_enabled());
+        // only sinks that were on before shedding are restored
+        assert!(!board.rocket_hd_enabled());
+    });
+}
+
+#[test]
+fn failed_probe_keeps_temperature_fault_latched() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::PowerOn, EpsConfig::DEFAULT));
+
+    block_on(async {
+        let hot = TemperatureStatus::Overtemperature;
+        let shed = SinkSet::EMPTY.with(Sink::GPS);
+        links
+            .protection
+            .send(ProtectionEvent::BatteryTemperature(
+                FlipFlopInput::Bat1,
+                hot,
+                FaultResponse::Disconnect,
+            ))
+            .await;
+        links
+            .protection
+            .send(ProtectionEvent::BatteryTemperature(
+                FlipFlopInput::Bat2,
+                hot,
+                FaultResponse::ShedSinks(shed),
+            ))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert_eq!(board.sources(), [false, true, true]);
+        assert!(!board.gps_enabled());
+
+        // the probes fail while the batteries are still hot
+        for input in [FlipFlopInput::Bat1, FlipFlopInput::Bat2] {
+            links
+                .protection
+                .send(ProtectionEvent::BatteryTemperature(
+                    input,
+                    TemperatureStatus::ProbeFailure,
+                    FaultResponse::Ignore,
+                ))
+                .await;
+        }
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert_eq!(board.sources(), [false, true, true]);
+        assert!(!board.gps_enabled());
+
+        for input in [FlipFlopInput::Bat1, FlipFlopInput::Bat2] {
+            links
+                .protection
+                .send(ProtectionEvent::BatteryTemperature(
+                    input,
+                    TemperatureStatus::Nominal,
+                    FaultResponse::Ignore,
+                ))
+                .await;
+        }
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert_eq!(board.sources(), [true, true, true]);
+        assert!(board.gps_enabled());
+    });
+}
+
+#[test]
+fn tripped_fuse_keeps_sink_off_until_closed() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::PowerOn, EpsConfig::DEFAULT));
+
+    block_on(async {
+        links
+            .protection
+            .send(ProtectionEvent::SinkFuse(Sink::GPS, FuseStatus::Latched))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert!(!board.gps_enabled());
+
+        // resets are forwarded to the fuse, the sink stays off until it reports closed
+        links.command(EPSCommand::ResetFuse(Sink::GPS)).await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert!(!board.gps_enabled());
+        assert!(matches!(links.fuse_resets.try_receive(), Ok(Sink::GPS)));
+
+        links
+            .protection
+            .send(ProtectionEvent::SinkFuse(Sink::GPS, FuseStatus::Closed))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert!(board.gps_enabled());
+    });
+}
+
+#[test]
+fn degraded_bus_sheds_sinks_in_priority_order() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::PowerOn, EpsConfig::DEFAULT));
+    let soc = links.soc.each_ref().map(Watch::sender);
+
+    block_on(async {
+        soc.iter().for_each(|soc| soc.send(800));
+        // only below the SensorUpper threshold
+        links.bus_voltage.sender().send(6_70);
+        run_for(&mut control_loop, &links, Duration::from_millis(600)).await;
+        assert!(!board.sinks[1].is_high());
+        assert!(board.gps_enabled());
+        assert!(board.rocket_hd_enabled());
+
+        // a low battery sheds everything up to GPS, even with a good bus voltage
+        links.bus_voltage.sender().send(7_40);
+        soc[0].send(150);
+        run_for(&mut control_loop, &links, Duration::from_millis(500)).await;
+        assert!(!board.gps_enabled());
+        assert!(board.sinks[0].is_high());
+
+        // RocketHD outlasts RocketLST
+        soc[0].send(40);
+        run_for(&mut control_loop, &links, Duration::from_millis(500)).await;
+        assert!(!board.sinks[0].is_high());
+        assert!(board.rocket_hd_enabled());
+
+        // restored once the battery left the power path
+        links
+            .command(EPSCommand::SetSource(FlipFlopState::Bat2, None))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(500)).await;
+        assert!(board.gps_enabled());
+        assert!(board.rocket_hd_enabled());
+    });
+}
+
+#[test]
+fn rocket_hd_is_shed_last() {
+    let mut power_budget = PowerBudget::new(LOAD_SHED_TABLE);
+    let mut shed_order = Vec::new();
+    for bus_voltage in (5_00..=7_40).rev().step_by(10) {
+        power_budget.update(bus_voltage, None);
+        let shed = power_budget.shed();
+        for sink in shed.iter() {
+            if !shed_order.contains(&sink_index(sink)) {
+                shed_order.push(sink_index(sink));
+            }
+        }
+    }
+    let expected = [
+        Sink::SensorUpper,
+        Sink::GPS,
+        Sink::RocketLST,
+        Sink::RocketHD,
+    ];
+    assert!(shed_order == expected.map(sink_index));
+
+    // the same order for a draining battery
+    let mut power_budget = PowerBudget::new(LOAD_SHED_TABLE);
+    power_budget.update(7_40, Some(35));
+    assert!(!power_budget.shed().contains(Sink::RocketHD));
+    assert!(power_budget.shed().contains(Sink::RocketLST));
+    power_budget.update(7_40, Some(25));
+    assert!(power_budget.shed() == SinkSet::ALL);
+}
+
+#[test]
+fn watchdog_reset_boots_into_safe_mode() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::WatchdogReset, EpsConfig::DEFAULT));
+
+    block_on(async {
+        assert!(board.sinks[0].is_high());
+        assert!(!board.gps_enabled());
+
+        links.command(EPSCommand::EnableSink(Sink::GPS, None)).await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert!(!board.gps_enabled());
+
+        // leaving safe mode restores the standby power path
+        links
+            .command(EPSCommand::SetMode(Mode::Standby as u8))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert!(board.gps_enabled());
+        assert!(board.rocket_hd_enabled());
+    });
+}
+
+#[test]
+fn configured_standby_sinks_are_enabled_at_power_on() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut eps_config = EpsConfig::DEFAULT;
+    let standby_sinks = SinkSet::EMPTY.with(Sink::RocketLST).with(Sink::GPS);
+    eps_config
+        .set(Param::StandbySinks, standby_sinks.bits().into())
+        .unwrap();
+    let mut control_loop = block_on(board.control_loop(&links, ModeReason::PowerOn, eps_config));
+
+    block_on(async {
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert!(board.sinks[0].is_high());
+        assert!(!board.sinks[1].is_high());
+        assert!(board.gps_enabled());
+        assert!(!board.rocket_hd_enabled());
+
+        // configuration telecommands are answered by the config service
+        links.command(EPSCommand::CommitConfig).await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert!(matches!(
+            links.config.try_receive(),
+            Ok(ConfigCommand::Commit)
+        ));
+
+        // and event log downloads by the event log service
+        links.command(EPSCommand::DumpEventLog(2)).await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert!(matches!(links.log.try_receive(), Ok(LogCommand::Dump(2))));
+    });
+}
+
+#[test]
+fn repeated_resets_boot_into_safe_mode() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::RepeatedResets, EpsConfig::DEFAULT));
+
+    block_on(async {
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert!(board.sinks[0].is_high());
+        assert!(!board.sinks[1].is_high());
+        assert!(!board.gps_enabled());
+        assert!(!board.rocket_hd_enabled());
+    });
+}
+
+#[test]
+fn launch_locks_power_path_and_battery_loss_enters_safe_mode() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::PowerOn, EpsConfig::DEFAULT));
+
+    block_on(async {
+        for mode in [Mode::Launch, Mode::Flight] {
+            links.command(EPSCommand::SetMode(mode as u8)).await;
+        }
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        links.command(EPSCommand::SetMode(Mode::Launch as u8)).await;
+        links
+            .command(EPSCommand::DisableSink(Sink::GPS, None))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        // flight accepts sink commands, going back to launch is not allowed
+        assert!(!board.gps_enabled());
+
+        for input in [FlipFlopInput::Bat1, FlipFlopInput::Bat2] {
+            links
+                .protection
+                .send(ProtectionEvent::BatteryVoltage(
+                    input,
+                    VoltageStatus::Undervoltage,
+                ))
+                .await;
+        }
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        // there is no aux power in flight, the power path stays on a battery
+        assert_eq!(board.sources(), [true, false, false]);
+        assert!(board.sinks[0].is_high());
+        assert!(!board.rocket_hd_enabled());
+    });
+}
+
+/// acknowledgement frames in the order they were sent
+fn acks(telemetry: &[EpsTMContainer]) -> Vec<Vec<u8>> {
+    telemetry
+        .iter()
+        .filter(|container| container.id() == tm::TcAck.id())
+        .map(|container| container.bytes().to_vec())
+        .collect()
+}
+
+/// acceptance, start and completion of an executed telecommand
+fn executed(seq: u16, result: Result<(), TcFailure>) -> [Vec<u8>; 3] {
+    [
+        (AckStage::Acceptance, Ok(())),
+        (AckStage::Start, Ok(())),
+        (AckStage::Completion, result),
+    ]
+    .map(|(stage, result)| ack_container(seq, stage, result).bytes().to_vec())
+}
+
+#[test]
+fn telecommands_are_acknowledged() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::PowerOn, EpsConfig::DEFAULT));
+
+    block_on(async {
+        let disable = links
+            .command(EPSCommand::DisableSink(Sink::GPS, None))
+            .await;
+        // a command that changes nothing still completes
+        let no_op = links
+            .command(EPSCommand::DisableSink(Sink::GPS, None))
+            .await;
+        let unknown_mode = links.command(EPSCommand::SetMode(u8::MAX)).await;
+        let launch = links.command(EPSCommand::SetMode(Mode::Launch as u8)).await;
+        let telemetry = run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        let expected = [
+            executed(disable, Ok(())),
+            executed(no_op, Ok(())),
+            executed(unknown_mode, Err(TcFailure::UnknownMode)),
+            executed(launch, Ok(())),
+        ]
+        .concat();
+        assert!(acks(&telemetry) == expected);
+
+        let rejected = links.command(EPSCommand::EnableSink(Sink::GPS, None)).await;
+        let telemetry = run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        let expected = ack_container(
+            rejected,
+            AckStage::Acceptance,
+            Err(TcFailure::RejectedInMode),
+        );
+        assert!(acks(&telemetry) == [expected.bytes().to_vec()]);
+        assert!(!board.gps_enabled());
+    });
+}
+
+#[test]
+fn critical_commands_need_arming_in_flight() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut eps_config = EpsConfig::DEFAULT;
+    eps_config.set(Param::ArmWindowS, 1).unwrap();
+    let mut control_loop = block_on(board.control_loop(&links, ModeReason::PowerOn, eps_config));
+
+    block_on(async {
+        for mode in [Mode::Launch, Mode::Flight] {
+            links.command(EPSCommand::SetMode(mode as u8)).await;
+        }
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+
+        let unarmed = links
+            .command(EPSCommand::DisableSink(Sink::RocketHD, None))
+            .await;
+        let telemetry = run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        let expected = ack_container(unarmed, AckStage::Acceptance, Err(TcFailure::NotArmed));
+        assert!(acks(&telemetry) == [expected.bytes().to_vec()]);
+        assert!(board.rocket_hd_enabled());
+
+        // arming another command does not help
+        links
+            .command(EPSCommand::Arm(CriticalCommand::SetSource as u8))
+            .await;
+        links
+            .command(EPSCommand::DisableSink(Sink::RocketHD, None))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert!(board.rocket_hd_enabled());
+
+        // the window runs out
+        links
+            .command(EPSCommand::Arm(CriticalCommand::DisableRocketHD as u8))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(1_500)).await;
+        links
+            .command(EPSCommand::DisableSink(Sink::RocketHD, None))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert!(board.rocket_hd_enabled());
+
+        links
+            .command(EPSCommand::Arm(CriticalCommand::DisableRocketHD as u8))
+            .await;
+        links
+            .command(EPSCommand::DisableSink(Sink::RocketHD, None))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert!(!board.rocket_hd_enabled());
+    });
+}
+
+#[test]
+fn queued_commands_execute_at_their_time() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::PowerOn, EpsConfig::DEFAULT));
+
+    block_on(async {
+        let gps_off = links
+            .command_in(
+                Duration::from_millis(400),
+                EPSCommand::DisableSink(Sink::GPS, None),
+            )
+            .await;
+        let rocket_hd_off = links
+            .command_in(
+                Duration::from_millis(200),
+                EPSCommand::DisableSink(Sink::RocketHD, None),
+            )
+            .await;
+        let telemetry = run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        let accepted = [gps_off, rocket_hd_off].map(|seq| {
+            ack_container(seq, AckStage::Acceptance, Ok(()))
+                .bytes()
+                .to_vec()
+        });
+        assert!(acks(&telemetry) == accepted);
+        assert!(board.gps_enabled());
+        assert!(board.rocket_hd_enabled());
+
+        let telemetry = run_for(&mut control_loop, &links, Duration::from_millis(200)).await;
+        assert!(acks(&telemetry) == executed(rocket_hd_off, Ok(()))[1..]);
+        assert!(board.gps_enabled());
+        assert!(!board.rocket_hd_enabled());
+
+        run_for(&mut control_loop, &links, Duration::from_millis(200)).await;
+        assert!(!board.gps_enabled());
+    });
+}
+
+#[test]
+fn queued_commands_can_be_deleted() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::PowerOn, EpsConfig::DEFAULT));
+
+    block_on(async {
+        let gps_off = links
+            .command_in(
+                Duration::from_millis(200),
+                EPSCommand::DisableSink(Sink::GPS, None),
+            )
+            .await;
+        links
+            .command_in(
+                Duration::from_millis(200),
+                EPSCommand::DisableSink(Sink::RocketHD, None),
+            )
+            .await;
+        let past = links
+            .send_tc(
+                Telecommand::EPS(EPSCommand::DisableSink(Sink::RocketLST, None)),
+                Some(TimeTag::Boot(0)),
+            )
+            .await;
+        let telemetry = run_for(&mut control_loop, &links, Duration::from_millis(50)).await;
+        let past_ack = ack_container(past, AckStage::Acceptance, Err(TcFailure::TimeInPast));
+        assert!(acks(&telemetry).contains(&past_ack.bytes().to_vec()));
+
+        let delete = links.command(EPSCommand::DeleteQueued(gps_off)).await;
+        let delete_again = links.command(EPSCommand::DeleteQueued(gps_off)).await;
+        let clear = links.command(EPSCommand::ClearQueue).await;
+        let telemetry = run_for(&mut control_loop, &links, Duration::from_millis(300)).await;
+        assert!(board.sinks[0].is_high());
+        assert!(board.gps_enabled());
+        assert!(board.rocket_hd_enabled());
+
+        let expected = [
+            executed(delete, Ok(())),
+            executed(delete_again, Err(TcFailure::NotQueued)),
+            executed(clear, Ok(())),
+        ]
+        .concat();
+        assert!(acks(&telemetry) == expected);
+    });
+}
+
+#[test]
+fn queued_power_commands_run_in_launch() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::PowerOn, EpsConfig::DEFAULT));
+
+    block_on(async {
+        let gps_off = links
+            .command_in(
+                Duration::from_millis(300),
+                EPSCommand::DisableSink(Sink::GPS, None),
+            )
+            .await;
+        links.command(EPSCommand::SetMode(Mode::Launch as u8)).await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+
+        // the timeline was uploaded ahead, live commands stay locked
+        let live = links
+            .command(EPSCommand::DisableSink(Sink::SensorUpper, None))
+            .await;
+        let telemetry = run_for(&mut control_loop, &links, Duration::from_millis(300)).await;
+        assert!(!board.gps_enabled());
+        assert!(board.sinks[1].is_high());
+        let rejected = ack_container(live, AckStage::Acceptance, Err(TcFailure::RejectedInMode));
+        let expected = [
+            vec![rejected.bytes().to_vec()],
+            executed(gps_off, Ok(()))[1..].to_vec(),
+        ]
+        .concat();
+        assert!(acks(&telemetry) == expected);
+    });
+}
+
+#[test]
+fn queued_critical_commands_keep_their_arming() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::PowerOn, EpsConfig::DEFAULT));
+
+    block_on(async {
+        // queued in standby, where switching sources needs no arming
+        let unarmed = links
+            .command_in(
+                Duration::from_millis(300),
+                EPSCommand::SetSource(FlipFlopState::Bat2, None),
+            )
+            .await;
+        links
+            .command(EPSCommand::Arm(CriticalCommand::SetSource as u8))
+            .await;
+        let armed = links
+            .command_in(
+                Duration::from_millis(400),
+                EPSCommand::SetSource(FlipFlopState::Bat1, None),
+            )
+            .await;
+        links.command(EPSCommand::SetMode(Mode::Launch as u8)).await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+
+        // executed in launch, where it has to be armed
+        let telemetry = run_for(&mut control_loop, &links, Duration::from_millis(250)).await;
+        let not_armed = ack_container(unarmed, AckStage::Start, Err(TcFailure::NotArmed));
+        assert!(acks(&telemetry) == [not_armed.bytes().to_vec()]);
+        assert_eq!(board.sources(), [true, true, true]);
+
+        let telemetry = run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert!(acks(&telemetry) == executed(armed, Ok(()))[1..]);
+        assert_eq!(board.sources(), [true, false, false]);
+    });
+}
+
+// the only test synchronizing the shared mission clock
+#[test]
+fn mission_time_tags_execute_after_sync() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::PowerOn, EpsConfig::DEFAULT));
+
+    block_on(async {
+        let gps_off = || Telecommand::EPS(EPSCommand::DisableSink(Sink::GPS, None));
+        let unsynced = links
+            .send_tc(gps_off(), Some(TimeTag::Mission(100_200)))
+            .await;
+        let telemetry = run_for(&mut control_loop, &links, Duration::from_millis(10)).await;
+        let expected = ack_container(
+            unsynced,
+            AckStage::Acceptance,
+            Err(TcFailure::NoMissionTime),
+        );
+        assert!(acks(&telemetry) == [expected.bytes().to_vec()]);
+
+        mission_time::sync(&100_000u32.to_le_bytes()).unwrap();
+        links
+            .send_tc(gps_off(), Some(TimeTag::Mission(100_200)))
+            .await;
+        run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert!(board.gps_enabled());
+        // the queued telecommand holds the mission time
+        let report = mission_time::sync(&0u32.to_le_bytes()).unwrap();
+        assert!(report.error_ms <= -100_000);
+        assert!(matches!(mission_time::now(), TimeTag::Mission(ms) if ms >= 100_000));
+        run_for(&mut control_loop, &links, Duration::from_millis(200)).await;
+        assert!(!board.gps_enabled());
+    });
+}
+
+/// upload a script step by step, a few steps at a time so the command channel never blocks
+async fn upload(control_loop: &mut MockControlLoop<'_>, links: &Links, id: u8, steps: &[Step]) {
+    for (index, step) in steps.iter().enumerate() {
+        let [op, arg, param_low, param_high] = step.encode();
+        let param = u16::from_le_bytes([param_low, param_high]);
+        links
+            .command(EPSCommand::SetScriptStep(id, index as u8, op, arg, param))
+            .await;
+        if index % 4 == 3 {
+            run_for(control_loop, links, Duration::from_millis(10)).await;
+        }
+    }
+    run_for(control_loop, links, Duration::from_millis(10)).await;
+}
+
+/// script progress frames in the order they were sent
+fn progress(telemetry: &[EpsTMContainer]) -> Vec<Vec<u8>> {
+    telemetry
+        .iter()
+        .filter(|container| container.id() == tm::ScriptProgress.id())
+        .map(|container| container.bytes().to_vec())
+        .collect()
+}
+
+#[test]
+fn scripts_run_step_by_step() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::PowerOn, EpsConfig::DEFAULT));
+
+    block_on(async {
+        let steps = [
+            Step::DisableSink(Sink::GPS),
+            Step::Wait(300),
+            Step::DisableSink(Sink::RocketHD),
+            Step::SetSource(FlipFlopState::Bat2),
+        ];
+        upload(&mut control_loop, &links, 0, &steps).await;
+        links.command(EPSCommand::RunScript(0)).await;
+        let telemetry = run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        assert!(!board.gps_enabled());
+        assert!(board.rocket_hd_enabled());
+        let expected = [0, 1, 2].map(|executed| {
+            progress_container(0, executed, steps.len(), ScriptState::Running)
+                .bytes()
+                .to_vec()
+        });
+        assert!(progress(&telemetry) == expected);
+
+        // the running script can not be edited or started again
+        let edit = links.command(EPSCommand::ClearScript(0)).await;
+        let again = links.command(EPSCommand::RunScript(0)).await;
+        let telemetry = run_for(&mut control_loop, &links, Duration::from_millis(300)).await;
+        let expected = [
+            executed(edit, Err(TcFailure::ScriptRunning)),
+            executed(again, Err(TcFailure::ScriptRunning)),
+        ]
+        .concat();
+        assert!(acks(&telemetry) == expected);
+        assert!(!board.rocket_hd_enabled());
+        assert_eq!(board.sources(), [false, true, false]);
+        let completed = progress_container(0, 4, steps.len(), ScriptState::Completed);
+        assert!(progress(&telemetry).last() == Some(&completed.bytes().to_vec()));
+
+        // storing hands the script over to the script service
+        links.command(EPSCommand::StoreScript(0)).await;
+        run_for(&mut control_loop, &links, Duration::from_millis(10)).await;
+        let Ok((0, stored)) = links.scripts.try_receive() else {
+            panic!("script not forwarded");
+        };
+        assert!(stored.steps().len() == steps.len());
+    });
+}
+
+#[test]
+fn scripts_abort_on_protection_faults() {
+    let board = Board::new();
+    let links = Links::new();
+    let mut control_loop =
+        block_on(board.control_loop(&links, ModeReason::PowerOn, EpsConfig::DEFAULT));
+
+    block_on(async {
+        let steps = [
+            Step::DisableSink(Sink::GPS),
+            Step::Wait(200),
+            Step::DisableSink(Sink::RocketHD),
+        ];
+        upload(&mut control_loop, &links, 1, &steps).await;
+        let invalid = links
+            .command(EPSCommand::SetScriptStep(1, 5, 1, 0, 0))
+            .await;
+        let unknown = links.command(EPSCommand::RunScript(u8::MAX)).await;
+        let run = links.command(EPSCommand::RunScript(1)).await;
+        let telemetry = run_for(&mut control_loop, &links, Duration::from_millis(100)).await;
+        let expected = [
+            executed(invalid, Err(TcFailure::InvalidStep)),
+            executed(unknown, Err(TcFailure::UnknownScript)),
+            executed(run, Ok(())),
+        ]
+        .concat();
+        assert!(acks(&telemetry) == expected);
+        assert!(!board.gps_enabled());
+
+        links
+            .protection
+            .send(ProtectionEvent::SinkFuse(
+                Sink::SensorUpper,
+                FuseStatus::Tripped,
+            ))
+            .await;
+        let telemetry = run_for(&mut control_loop, &links, Duration::from_millis(300)).await;
+        assert!(board.rocket_hd_enabled());
+        let aborted = progress_container(1, 2, steps.len(), ScriptState::AbortedByProtection);
+        assert!(progress(&telemetry) == [aborted.bytes().to_vec()]);
+    });
+}
@@ -0,0 +1,50 @@
+use critical_section as _;
+
+use eps_software::{
+    adc::conversion::{
+        CurrentSense, calculate_current_ma, calculate_power_10mw, current_ma_to_raw,
+    },
+    board::{AuxPwrCurrentSense, BatteryCurrentSense, SinkCurrentSense},
+};
+
+/// converting to raw and back loses at most `tolerance_ma` in the measurable range
+fn assert_round_trip<S: CurrentSense>(currents_ma: impl Iterator<Item = i16>, tolerance_ma: i16) {
+    for current in currents_ma {
+        let measured = calculate_current_ma::<S>(current_ma_to_raw::<S>(current));
+        assert!((measured - current).abs() <= tolerance_ma);
+    }
+}
+
+#[test]
+fn bidirectional_battery_current() {
+    // centered on half the adc range, 500mV/A at the default 3.3V reference
+    assert!(calculate_current_ma::<BatteryCurrentSense>(2048) == 0);
+    assert!(current_ma_to_raw::<BatteryCurrentSense>(1_000) == 2668);
+    assert!(calculate_current_ma::<BatteryCurrentSense>(2668) == 998);
+    assert!(calculate_current_ma::<BatteryCurrentSense>(0) == -3_300);
+    assert!(calculate_current_ma::<BatteryCurrentSense>(4095) == 3_298);
+    assert_round_trip::<BatteryCurrentSense>(-3_200..=3_200, 3);
+}
+
+#[test]
+fn unidirectional_currents() {
+    assert!(calculate_current_ma::<AuxPwrCurrentSense>(0) == 0);
+    assert!(calculate_current_ma::<AuxPwrCurrentSense>(4095) == 6_598);
+    // reverse currents are below the adc range
+    assert!(current_ma_to_raw::<AuxPwrCurrentSense>(-100) == 0);
+    assert_round_trip::<AuxPwrCurrentSense>(0..=6_500, 3);
+
+    // the larger sink shunt gives 1mV/mA
+    assert!(calculate_current_ma::<SinkCurrentSense>(4095) == 3_299);
+    assert!(current_ma_to_raw::<SinkCurrentSense>(10_000) == 4095);
+    assert_round_trip::<SinkCurrentSense>(0..=3_200, 1);
+}
+
+#[test]
+fn power_from_voltage_and_current() {
+    assert!(calculate_power_10mw(7_40, 1_000) == 7_40);
+    assert!(calculate_power_10mw(7_40, 250) == 1_85);
+    // charging currents give negative power
+    assert!(calculate_power_10mw(8_20, -500) == -4_10);
+    assert!(calculate_power_10mw(0, 3_000) == 0);
+}
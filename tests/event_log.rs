@@ -0,0 +1,158 @@
+mod common;
+
+use core::cell::Cell;
+use std::sync::Mutex;
+
+use critical_section as _;
+
+use common::erased_memory;
+use embassy_futures::block_on;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
+use embassy_time::{Duration, Timer};
+use eps_software::{
+    EpsTMContainer,
+    event_log::{
+        self, CHUNK_LEN, Entry, ErrorBurst, Event, EventLogService, LogCommand,
+        store::EventLogStore,
+    },
+    mock::flash::MockFlash,
+    telecommand::TimeTag,
+};
+use south_common::{TelemetryContainer, TelemetryDefinition, telemetry::eps as tm};
+
+// the RAM log is shared by all tests logging events
+static LOG: Mutex<()> = Mutex::new(());
+
+fn entry(index: usize) -> Entry {
+    Entry {
+        time: TimeTag::Mission(index as u32 * 100),
+        event: Event::SinkChange,
+        params: [index as u8, (index >> 8) as u8, 0],
+    }
+}
+
+fn load(memory: &[Cell<u8>]) -> EventLogStore<MockFlash<'_>> {
+    let mut store = EventLogStore::new(MockFlash::new(memory), 0);
+    store.load().unwrap();
+    store
+}
+
+/// all valid entries, oldest first
+fn entries(store: &mut EventLogStore<MockFlash<'_>>) -> Vec<Entry> {
+    (0..store.len())
+        .filter_map(|position| store.read(position).unwrap())
+        .collect()
+}
+
+#[test]
+fn entries_are_packed_with_their_time_reference() {
+    let entry = Entry {
+        time: TimeTag::Boot(0x0403_0201),
+        event: Event::Protection,
+        params: [6, 1, 0],
+    };
+    assert!(entry.encode() == [1, 2, 3, 4, Event::Protection as u8, 6, 1, 0]);
+    assert!(Entry::decode(entry.encode()) == Some(entry));
+    let entry = Entry {
+        time: TimeTag::Mission(7),
+        ..entry
+    };
+    assert!(entry.encode()[4] == 0x80 | Event::Protection as u8);
+    assert!(Entry::decode(entry.encode()) == Some(entry));
+    assert!(Entry::decode([0, 0, 0, 0, 0x7F, 0, 0, 0]).is_none());
+}
+
+#[test]
+fn stored_entries_are_loaded_after_reset() {
+    let memory = erased_memory();
+    let mut store = load(&memory);
+    assert!(store.is_empty());
+    for index in 0..3 {
+        store.append(&entry(index)).unwrap();
+    }
+
+    let mut store = load(&memory);
+    assert!(entries(&mut store) == (0..3).map(entry).collect::<Vec<_>>());
+    store.append(&entry(3)).unwrap();
+    assert!(entries(&mut load(&memory)) == (0..4).map(entry).collect::<Vec<_>>());
+}
+
+#[test]
+fn logged_events_are_dumped_in_chunks() {
+    let _log = LOG.lock().unwrap();
+    let memory = erased_memory();
+    let cmds: Channel<CriticalSectionRawMutex, LogCommand, 2> = Channel::new();
+    let tm: Channel<CriticalSectionRawMutex, EpsTMContainer, 64> = Channel::new();
+    let mut service = EventLogService::new(load(&memory), cmds.dyn_receiver(), tm.dyn_sender());
+
+    block_on(async {
+        for index in 0..CHUNK_LEN + 2 {
+            event_log::log(Event::SinkChange, [index as u8, 0, 0]);
+        }
+        // written to flash right away
+        service.run().await;
+        assert!(load(&memory).len() == CHUNK_LEN + 2);
+
+        cmds.send(LogCommand::Dump(1)).await;
+        service.run().await;
+        let telemetry: Vec<_> = core::iter::from_fn(|| tm.try_receive().ok()).collect();
+        let (chunk, dumped) = telemetry.split_last().unwrap();
+        assert!(chunk.id() == tm::EventLogChunk.id());
+        assert!(chunk.bytes() == [1, 2, 2, 0]);
+        let params: Vec<_> = dumped
+            .iter()
+            .map(|container| {
+                assert!(container.id() == tm::EventLogEntry.id());
+                Entry::decode(container.bytes().try_into().unwrap()).unwrap()
+            })
+            .map(|entry| entry.params[0] as usize)
+            .collect();
+        assert!(params == [CHUNK_LEN, CHUNK_LEN + 1]);
+
+        cmds.send(LogCommand::Clear).await;
+        service.run().await;
+        assert!(load(&memory).is_empty());
+    });
+}
+
+#[test]
+fn only_the_first_error_of_a_burst_is_logged() {
+    let _log = LOG.lock().unwrap();
+    let memory = erased_memory();
+    let cmds: Channel<CriticalSectionRawMutex, LogCommand, 2> = Channel::new();
+    let tm: Channel<CriticalSectionRawMutex, EpsTMContainer, 2> = Channel::new();
+    let mut service = EventLogService::new(load(&memory), cmds.dyn_receiver(), tm.dyn_sender());
+    let mut errors = ErrorBurst::new(Event::CanTxError, Duration::from_millis(200));
+
+    block_on(async {
+        for index in 0..4 {
+            errors.error([index, 0, 0]);
+        }
+        // successes in between don't end the burst
+        errors.ok();
+        errors.error([4, 0, 0]);
+        service.run().await;
+        let logged = entries(&mut load(&memory));
+        assert!(logged.len() == 1);
+        assert!(logged[0].event == Event::CanTxError);
+        assert!(logged[0].params == [0, 0, 0]);
+
+        Timer::after(Duration::from_millis(300)).await;
+        errors.ok();
+        errors.ok();
+        service.run().await;
+        let logged = entries(&mut load(&memory));
+        assert!(logged.len() == 2);
+        assert!(logged[1].event == Event::ErrorBurst);
+        assert!(logged[1].params == [Event::CanTxError as u8, 4, 0]);
+
+        // a single error after the gap starts a new burst
+        errors.error([5, 0, 0]);
+        service.run().await;
+        Timer::after(Duration::from_millis(300)).await;
+        errors.ok();
+        let logged = entries(&mut load(&memory));
+        assert!(logged.len() == 3);
+        assert!(logged[2].params == [5, 0, 0]);
+    });
+}
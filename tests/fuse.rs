@@ -0,0 +1,171 @@
+use critical_section as _;
+
+use embassy_futures::block_on;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel, watch::Watch};
+use embassy_time::{Duration, Timer};
+use eps_software::{
+    EpsTMContainer,
+    adc::stats::SharedStatistics,
+    protection::{
+        ProtectionEvent,
+        overcurrent::{EFuse, FuseLimits, FuseStatus, RetryPolicy},
+    },
+    pwr_src::{sink_ctrl::SINKS, sink_monitor::SinkMonitor},
+};
+use south_common::{telemetry::eps as tm, types::Sink};
+
+const fn limits(retry: RetryPolicy) -> FuseLimits {
+    FuseLimits {
+        limit_ma: 1_000,
+        trip_delay: Duration::from_millis(0),
+        retry,
+    }
+}
+
+#[test]
+fn latch_off_fuse_stays_open_until_reset() {
+    let mut fuse = EFuse::new(limits(RetryPolicy::LatchOff));
+    assert!(fuse.update(1_000).is_none());
+    assert!(fuse.update(1_001) == Some(FuseStatus::Latched));
+    assert!(fuse.update(0).is_none());
+    assert!(fuse.status() == FuseStatus::Latched);
+
+    assert!(fuse.reset() == Some(FuseStatus::Closed));
+    assert!(fuse.reset().is_none());
+    assert!(fuse.update(0).is_none());
+}
+
+#[test]
+fn short_overcurrent_does_not_trip() {
+    let mut fuse = EFuse::new(FuseLimits {
+        trip_delay: Duration::from_millis(50),
+        ..limits(RetryPolicy::LatchOff)
+    });
+    block_on(async {
+        assert!(fuse.update(2_000).is_none());
+        Timer::after(Duration::from_millis(30)).await;
+        // dropping below the limit restarts the trip delay
+        assert!(fuse.update(500).is_none());
+        assert!(fuse.update(2_000).is_none());
+        Timer::after(Duration::from_millis(30)).await;
+        assert!(fuse.update(2_000).is_none());
+        Timer::after(Duration::from_millis(30)).await;
+        assert!(fuse.update(2_000) == Some(FuseStatus::Latched));
+    });
+}
+
+#[test]
+fn retrying_fuse_latches_after_max_retries() {
+    let mut fuse = EFuse::new(limits(RetryPolicy::Retry {
+        max_retries: 2,
+        delay: Duration::from_millis(20),
+    }));
+    block_on(async {
+        for retries in 1..=2 {
+            assert!(fuse.update(2_000) == Some(FuseStatus::Tripped));
+            assert!(fuse.retries() == retries);
+            assert!(fuse.update(0).is_none());
+            Timer::after(Duration::from_millis(25)).await;
+            assert!(fuse.update(0) == Some(FuseStatus::Closed));
+        }
+        assert!(fuse.update(2_000) == Some(FuseStatus::Latched));
+        Timer::after(Duration::from_millis(25)).await;
+        assert!(fuse.update(0).is_none());
+
+        // a reset forgets the previous trips
+        assert!(fuse.reset() == Some(FuseStatus::Closed));
+        assert!(fuse.retries() == 0);
+        assert!(fuse.update(2_000) == Some(FuseStatus::Tripped));
+    });
+}
+
+#[test]
+fn backoff_delay_doubles_up_to_the_maximum() {
+    let mut fuse = EFuse::new(limits(RetryPolicy::Backoff {
+        max_retries: 3,
+        initial_delay: Duration::from_millis(20),
+        max_delay: Duration::from_millis(50),
+    }));
+    block_on(async {
+        // 20ms, 40ms and 50ms instead of 80ms
+        for (early, late) in [(10, 15), (30, 15), (40, 15)] {
+            assert!(fuse.update(2_000) == Some(FuseStatus::Tripped));
+            Timer::after(Duration::from_millis(early)).await;
+            assert!(fuse.update(0).is_none());
+            Timer::after(Duration::from_millis(late)).await;
+            assert!(fuse.update(0) == Some(FuseStatus::Closed));
+        }
+        assert!(fuse.update(2_000) == Some(FuseStatus::Latched));
+    });
+}
+
+#[test]
+fn backoff_survives_large_retry_counts() {
+    let mut fuse = EFuse::new(limits(RetryPolicy::Backoff {
+        max_retries: u8::MAX,
+        initial_delay: Duration::from_micros(1),
+        max_delay: Duration::from_micros(1),
+    }));
+    block_on(async {
+        for retries in 1..=40 {
+            assert!(fuse.update(2_000) == Some(FuseStatus::Tripped));
+            assert!(fuse.retries() == retries);
+            Timer::after(Duration::from_millis(1)).await;
+            assert!(fuse.update(0) == Some(FuseStatus::Closed));
+        }
+    });
+}
+
+#[test]
+fn full_telemetry_channel_does_not_stall_the_fuses() {
+    let bus_voltage: Watch<CriticalSectionRawMutex, i16, 1> = Watch::new();
+    let currents: [Watch<CriticalSectionRawMutex, i16, 1>; SINKS.len()] =
+        [const { Watch::new() }; SINKS.len()];
+    let bus_voltage_stats = SharedStatistics::new();
+    let current_stats = [const { SharedStatistics::new() }; SINKS.len()];
+    let fuse_resets: Channel<CriticalSectionRawMutex, Sink, 2> = Channel::new();
+    let protection: Channel<CriticalSectionRawMutex, ProtectionEvent, 4> = Channel::new();
+    let tm_channel: Channel<CriticalSectionRawMutex, EpsTMContainer, 1> = Channel::new();
+    let mut sink_monitor = SinkMonitor::new(
+        bus_voltage.dyn_receiver().unwrap(),
+        currents
+            .each_ref()
+            .map(|watch| watch.dyn_receiver().unwrap()),
+        &bus_voltage_stats,
+        current_stats.each_ref(),
+        [limits(RetryPolicy::LatchOff); SINKS.len()],
+        fuse_resets.dyn_receiver(),
+        protection.dyn_sender(),
+        tm_channel.dyn_sender(),
+    );
+
+    bus_voltage.sender().send(7_40);
+    currents
+        .iter()
+        .for_each(|current| current.sender().send(100));
+    // nobody drains the telemetry
+    let container = EpsTMContainer::new(&tm::BusVoltage, &7_40i16).unwrap();
+    tm_channel.try_send(container).unwrap();
+
+    block_on(async {
+        sink_monitor.run().await;
+        assert!(sink_monitor.dropped_tm() == 16);
+
+        currents[2].sender().send(1_500);
+        sink_monitor.run().await;
+        assert!(matches!(
+            protection.try_receive(),
+            Ok(ProtectionEvent::SinkFuse(Sink::GPS, FuseStatus::Latched))
+        ));
+
+        // reset by telecommand closes the latched fuse
+        currents[2].sender().send(100);
+        fuse_resets.send(Sink::GPS).await;
+        sink_monitor.run().await;
+        assert!(matches!(
+            protection.try_receive(),
+            Ok(ProtectionEvent::SinkFuse(Sink::GPS, FuseStatus::Closed))
+        ));
+        assert!(protection.try_receive().is_err());
+    });
+}
@@ -0,0 +1,90 @@
+use critical_section as _;
+
+use embassy_time::Instant;
+use eps_software::mission_time::{FRAME_LENS, MissionClock, stamped};
+
+#[test]
+fn unsynced_clock_has_no_mission_time() {
+    let clock = MissionClock::new();
+    assert!(clock.mission_ms(Instant::from_millis(1_000)).is_none());
+    assert!(clock.instant(1_000).is_none());
+}
+
+#[test]
+fn mission_time_follows_uptime_after_sync() {
+    let mut clock = MissionClock::new();
+    let report = clock.sync(Instant::from_millis(1_000), 50_000);
+    assert!(report.error_ms == 0);
+    assert!(clock.mission_ms(Instant::from_millis(3_500)) == Some(52_500));
+    assert!(clock.instant(53_000) == Some(Instant::from_millis(4_000)));
+    // mission times before the sync map back as well
+    assert!(clock.instant(49_500) == Some(Instant::from_millis(500)));
+}
+
+#[test]
+fn drift_is_estimated_from_syncs() {
+    let mut clock = MissionClock::new();
+    // mission time runs 1000 ppm faster than the local clock
+    let mut report = clock.sync(Instant::from_millis(0), 0);
+    for local_s in (10..=600).step_by(10) {
+        let mission_ms = local_s * 1_001;
+        report = clock.sync(Instant::from_secs(local_s), mission_ms as u32);
+    }
+    assert!((900..=1_000).contains(&clock.drift_ppm()));
+    assert!(report.error_ms.abs() <= 1, "{}", report.error_ms);
+    // predictions between syncs include the drift
+    let next = Instant::from_secs(610);
+    assert!(clock.mission_ms(next).unwrap().abs_diff(610_610) <= 1);
+}
+
+#[test]
+fn jumps_of_mission_time_are_taken_over() {
+    let mut clock = MissionClock::new();
+    clock.sync(Instant::from_secs(1), 10_000);
+    // the flight computer restarted its mission time
+    let report = clock.sync(Instant::from_secs(2), 500);
+    assert!(report.error_ms == 500 - 11_000);
+    assert!(report.drift_ppm == 0);
+    assert!(clock.mission_ms(Instant::from_secs(3)) == Some(1_500));
+}
+
+#[test]
+fn jumps_are_refused_while_held() {
+    let mut clock = MissionClock::new();
+    clock.sync(Instant::from_secs(1), 10_000);
+    clock.hold_jumps(true);
+    let report = clock.sync(Instant::from_secs(2), 500);
+    assert!(report.error_ms == 500 - 11_000);
+    assert!(clock.mission_ms(Instant::from_secs(3)) == Some(12_000));
+    // small corrections are still taken over
+    let report = clock.sync(Instant::from_secs(3), 12_200);
+    assert!(report.error_ms == 200);
+    assert!(clock.mission_ms(Instant::from_secs(4)) == Some(13_200));
+
+    clock.hold_jumps(false);
+    clock.sync(Instant::from_secs(4), 500);
+    assert!(clock.mission_ms(Instant::from_secs(5)) == Some(1_500));
+}
+
+#[test]
+fn stamped_telemetry_fills_valid_frame_lengths() {
+    for len in 0..=59 {
+        let payload = vec![0xAA; len];
+        let frame = stamped(&payload).unwrap();
+        assert!(
+            FRAME_LENS.contains(&frame.len()),
+            "{} -> {}",
+            len,
+            frame.len()
+        );
+        let (data, stamp) = frame.split_at(frame.len() - 5);
+        assert!(data[..len] == payload[..]);
+        assert!(data[len..].iter().all(|b| *b == 0));
+        // uptime reference, the clock of this test binary is never synced
+        assert!(stamp[0] == 0);
+    }
+    // 4 byte topics are padded to a 12 byte frame
+    assert!(stamped(&[0; 4]).unwrap().len() == 12);
+    assert!(stamped(&[0; 3]).unwrap().len() == 8);
+    assert!(stamped(&[0; 60]).is_none());
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use core::cell::Cell;
+
+use common::{erased_memory, interrupt_write};
+use eps_software::{
+    mock::flash::{MOCK_PAGE_SIZE, MockFlash},
+    record_store::{RecordStore, record_size},
+};
+
+const SIZE: usize = record_size(4);
+const SLOTS: usize = MOCK_PAGE_SIZE / SIZE;
+const MAGIC: u16 = 0x1234;
+
+type Store<'a> = RecordStore<MockFlash<'a>, SIZE>;
+
+fn load(memory: &[Cell<u8>]) -> Store<'_> {
+    let mut store = RecordStore::new(MockFlash::new(memory), 0, MAGIC, 1);
+    store.load().unwrap();
+    store
+}
+
+/// payloads of all valid records, oldest first
+fn payloads(store: &mut Store<'_>) -> Vec<u32> {
+    (0..store.len())
+        .filter_map(|position| store.read(position).unwrap())
+        .map(|record| u32::from_le_bytes(record.payload()[..4].try_into().unwrap()))
+        .collect()
+}
+
+fn append(store: &mut Store<'_>, values: core::ops::Range<usize>) {
+    for value in values {
+        store.append(&(value as u32).to_le_bytes()).unwrap();
+    }
+}
+
+#[test]
+fn records_are_loaded_after_reset() {
+    let memory = erased_memory();
+    let mut store = load(&memory);
+    assert!(store.is_empty());
+    assert!(store.latest().unwrap().is_none());
+    append(&mut store, 0..3);
+
+    let mut store = load(&memory);
+    assert!(payloads(&mut store) == [0, 1, 2]);
+    assert!(store.latest().unwrap().unwrap().payload()[..4] == 2u32.to_le_bytes());
+}
+
+#[test]
+fn oldest_records_are_dropped_when_both_pages_are_full() {
+    let memory = erased_memory();
+    let mut store = load(&memory);
+    append(&mut store, 0..SLOTS + 5);
+    // the first page is kept while the second one fills up
+    let expected: Vec<_> = (0..SLOTS as u32 + 5).collect();
+    assert!(payloads(&mut store) == expected);
+    assert!(payloads(&mut load(&memory)) == expected);
+
+    let mut store = load(&memory);
+    assert!(!store.page_full());
+    append(&mut store, SLOTS + 5..2 * SLOTS);
+    assert!(store.page_full());
+    append(&mut store, 2 * SLOTS..2 * SLOTS + 1);
+    let expected: Vec<_> = (SLOTS as u32..2 * SLOTS as u32 + 1).collect();
+    assert!(payloads(&mut store) == expected);
+    assert!(payloads(&mut load(&memory)) == expected);
+}
+
+#[test]
+fn interrupted_write_is_skipped() {
+    let memory = erased_memory();
+    let mut store = load(&memory);
+    append(&mut store, 0..2);
+    interrupt_write(&memory, SIZE, SIZE);
+
+    let mut store = load(&memory);
+    assert!(store.len() == 2);
+    assert!(store.read(1).unwrap().is_none());
+    assert!(store.latest().unwrap().unwrap().payload()[..4] == 0u32.to_le_bytes());
+    append(&mut store, 2..3);
+    assert!(payloads(&mut load(&memory)) == [0, 2]);
+}
+
+#[test]
+fn cleared_store_is_empty_after_reset() {
+    let memory = erased_memory();
+    let mut store = load(&memory);
+    append(&mut store, 0..SLOTS + 1);
+    store.clear().unwrap();
+    assert!(store.is_empty());
+    assert!(load(&memory).is_empty());
+
+    append(&mut store, 0..1);
+    assert!(payloads(&mut load(&memory)) == [0]);
+}
+
+#[test]
+fn records_of_other_layouts_are_erased() {
+    let memory = erased_memory();
+    append(&mut load(&memory), 0..3);
+
+    let mut store = RecordStore::new(MockFlash::new(&memory), 0, MAGIC, 2);
+    store.load().unwrap();
+    assert!(store.is_empty());
+    assert!(memory.iter().all(|cell| cell.get() == 0xFF));
+    store.append(&7u32.to_le_bytes()).unwrap();
+    assert!(load(&memory).is_empty());
+}
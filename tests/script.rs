@@ -0,0 +1,77 @@
+mod common;
+
+use core::cell::Cell;
+
+use common::erased_memory;
+use eps_software::{
+    mock::flash::{MOCK_PAGE_SIZE, MockFlash},
+    script::{
+        SCRIPT_COUNT, STEP_SIZE, Script, Scripts, Step,
+        store::{RECORD_SIZE, ScriptStore},
+    },
+};
+use south_common::types::{FlipFlopState, Sink};
+
+fn script(steps: &[Step]) -> Script {
+    let mut script = Script::default();
+    for (index, step) in steps.iter().enumerate() {
+        script.set_step(index as u8, *step).unwrap();
+    }
+    script
+}
+
+fn encoded(script: &Script) -> Vec<[u8; STEP_SIZE]> {
+    script.steps().iter().map(Step::encode).collect()
+}
+
+fn load(memory: &[Cell<u8>]) -> Scripts {
+    ScriptStore::new(MockFlash::new(memory), 0).load().unwrap()
+}
+
+#[test]
+fn erased_flash_has_empty_scripts() {
+    let memory = erased_memory();
+    assert!(load(&memory).iter().all(|script| script.steps().is_empty()));
+}
+
+#[test]
+fn stored_scripts_are_loaded_after_reset() {
+    let memory = erased_memory();
+    let mut store = ScriptStore::new(MockFlash::new(&memory), 0);
+    let mut scripts = store.load().unwrap();
+    scripts[0] = script(&[Step::EnableSink(Sink::GPS), Step::Wait(2_000)]);
+    store.commit(&scripts, 0).unwrap();
+    scripts[3] = script(&[Step::SetSource(FlipFlopState::AuxPwr)]);
+    store.commit(&scripts, 3).unwrap();
+    scripts[0] = script(&[Step::DisableSink(Sink::RocketLST)]);
+    store.commit(&scripts, 0).unwrap();
+    // not stored, only changed in memory
+    scripts[5] = script(&[Step::Wait(1)]);
+
+    let loaded = load(&memory);
+    assert!(encoded(&loaded[0]) == encoded(&scripts[0]));
+    assert!(encoded(&loaded[3]) == encoded(&scripts[3]));
+    assert!(loaded[5].steps().is_empty());
+    assert!(store.commit(&scripts, SCRIPT_COUNT as u8).is_err());
+}
+
+#[test]
+fn scripts_are_rewritten_to_other_page_when_full() {
+    let memory = erased_memory();
+    let mut store = ScriptStore::new(MockFlash::new(&memory), 0);
+    let mut scripts = store.load().unwrap();
+    scripts[1] = script(&[Step::EnableSink(Sink::RocketHD)]);
+    store.commit(&scripts, 1).unwrap();
+    let slots = MOCK_PAGE_SIZE / RECORD_SIZE;
+    for i in 0..slots as u16 {
+        scripts[0] = script(&[Step::Wait(i)]);
+        store.commit(&scripts, 0).unwrap();
+    }
+    // the first page is still intact
+    assert!(memory[MOCK_PAGE_SIZE].get() != 0xFF);
+    assert!(memory[0].get() != 0xFF);
+
+    let loaded = load(&memory);
+    assert!(encoded(&loaded[0]) == encoded(&scripts[0]));
+    assert!(encoded(&loaded[1]) == encoded(&scripts[1]));
+}
@@ -0,0 +1,77 @@
+use critical_section as _;
+
+use embassy_futures::block_on;
+use embassy_time::{Duration, Timer};
+use eps_software::pwr_src::battery::soc::SocEstimator;
+
+#[test]
+fn first_sample_starts_from_the_open_circuit_voltage() {
+    // 3.7V per cell is half charged
+    let estimate = SocEstimator::new(3_400).update(7_40, 0, None);
+    assert!(estimate.soc_permille == 500);
+    assert!(estimate.remaining_capacity_mah == 1_700);
+    assert!(estimate.time_to_empty_min == u16::MAX);
+
+    // clamped to the ends of the table
+    assert!(SocEstimator::new(3_400).update(5_00, 0, None).soc_permille == 0);
+    assert!(SocEstimator::new(3_400).update(8_60, 0, None).soc_permille == 1000);
+    // interpolated between table points, 3.74V per cell
+    assert!(SocEstimator::new(3_400).update(7_48, 0, None).soc_permille == 550);
+}
+
+#[test]
+fn cold_cells_reduce_usable_capacity() {
+    let mut soc = SocEstimator::new(3_400);
+    for (temperature, remaining_mah) in [
+        (None, 1_700),
+        (Some(25_0), 1_700),
+        (Some(5_0), 1_445),
+        (Some(0_0), 1_360),
+        (Some(-30_0), 1_020),
+    ] {
+        let estimate = soc.update(7_40, 0, temperature);
+        assert!(estimate.remaining_capacity_mah == remaining_mah);
+        // the state of charge itself is not derated
+        assert!(estimate.soc_permille == 500);
+    }
+}
+
+#[test]
+fn discharge_current_gives_time_to_empty() {
+    let mut soc = SocEstimator::new(3_400);
+    soc.update(7_40, 0, None);
+    // 1700mAh at 1.3A
+    let estimate = soc.update(7_40, 1_300, None);
+    assert!(estimate.time_to_empty_min == 78);
+    let estimate = soc.update(7_40, 1_300, Some(0_0));
+    assert!(estimate.time_to_empty_min == 62);
+    // charging never runs empty
+    assert!(soc.update(7_40, -500, None).time_to_empty_min == u16::MAX);
+}
+
+#[test]
+fn charge_is_counted_and_clamped() {
+    // a tiny pack so milliseconds of current are visible
+    let mut soc = SocEstimator::new(1);
+    soc.update(7_40, 0, None);
+
+    block_on(async {
+        Timer::after(Duration::from_millis(100)).await;
+        // 10A for 100ms are 0.28mAh
+        let estimate = soc.update(7_40, 10_000, None);
+        assert!(estimate.soc_permille < 300);
+
+        Timer::after(Duration::from_millis(200)).await;
+        let estimate = soc.update(7_40, 30_000, None);
+        assert!(estimate.soc_permille == 0);
+        assert!(estimate.time_to_empty_min == 0);
+
+        // a short rest does not recalibrate on the open circuit voltage
+        Timer::after(Duration::from_millis(100)).await;
+        assert!(soc.update(8_40, 0, None).soc_permille == 0);
+
+        Timer::after(Duration::from_millis(200)).await;
+        let estimate = soc.update(7_40, -30_000, None);
+        assert!(estimate.soc_permille == 1000);
+    });
+}
@@ -0,0 +1,98 @@
+use core::cell::Cell;
+
+use critical_section as _;
+
+use embassy_futures::block_on;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, watch::Watch};
+use eps_software::{
+    adc::{
+        AdcCtrl, AdcCtrlChannel,
+        stats::{SharedStatistics, Statistics},
+    },
+    mock::adc::{MockAdc, MockAdcChannel},
+};
+use south_common::{TelemetryContainer, TelemetryDefinition, telemetry::eps as tm};
+
+#[test]
+fn measurements_are_aggregated() {
+    let mut stats = Statistics::EMPTY;
+    assert!(stats.mean().is_none());
+    for value in [7_40, 6_10, 7_20, 7_30] {
+        stats.add(value);
+    }
+    assert!(stats.min == 6_10);
+    assert!(stats.max == 7_40);
+    assert!(stats.count == 4);
+    assert!(stats.mean() == Some(7_00));
+
+    let mut stats = Statistics::EMPTY;
+    stats.add(-3);
+    stats.add(-4);
+    // rounded towards zero
+    assert!(stats.mean() == Some(-3));
+}
+
+#[test]
+fn statistics_are_packed_for_telemetry() {
+    let mut stats = Statistics::EMPTY;
+    stats.add(-2);
+    stats.add(6);
+    let container = stats.container(&tm::BusVoltageStats);
+    assert!(container.id() == tm::BusVoltageStats.id());
+    let bytes = container.bytes();
+    assert!(i16::from_le_bytes([bytes[0], bytes[1]]) == -2);
+    assert!(i16::from_le_bytes([bytes[2], bytes[3]]) == 6);
+    assert!(i16::from_le_bytes([bytes[4], bytes[5]]) == 2);
+    assert!(u16::from_le_bytes([bytes[6], bytes[7]]) == 2);
+
+    let empty = Statistics::EMPTY.container(&tm::BusVoltageStats);
+    assert!(empty.bytes() == [0; 8]);
+}
+
+#[test]
+fn taking_the_statistics_starts_a_new_interval() {
+    let stats = SharedStatistics::new();
+    stats.add(1);
+    stats.add(5);
+    let taken = stats.take();
+    assert!(taken.count == 2);
+    assert!(taken.max == 5);
+    assert!(stats.take() == Statistics::EMPTY);
+    stats.add(3);
+    assert!(stats.take().min == 3);
+}
+
+#[test]
+fn every_measurement_of_the_adc_is_counted() {
+    // one external input and the internal temperature sensor
+    let inputs = [Cell::new(100), Cell::new(0)];
+    let watches: [Watch<CriticalSectionRawMutex, i16, 1>; 2] = [Watch::new(), Watch::new()];
+    let stats = [SharedStatistics::new(), SharedStatistics::new()];
+    let mut adc: AdcCtrl<'_, MockAdc<'_>, 2> = AdcCtrl::new(
+        MockAdc::new(&inputs),
+        watches[1].dyn_sender(),
+        &stats[1],
+        [AdcCtrlChannel::new(
+            MockAdcChannel(0),
+            watches[0].dyn_sender(),
+            &stats[0],
+            |raw| raw as i16,
+        )],
+    );
+
+    block_on(async {
+        // a short dip between two reports
+        for raw in [100, 40, 90] {
+            inputs[0].set(raw);
+            adc.run().await;
+        }
+    });
+    // the watch only holds the latest value
+    assert!(watches[0].try_get() == Some(90));
+    let taken = stats[0].take();
+    assert!(taken.min == 40);
+    assert!(taken.max == 100);
+    assert!(taken.count == 3);
+    assert!(taken.mean() == Some(76));
+    assert!(stats[1].take().count == 3);
+}
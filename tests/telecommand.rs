@@ -0,0 +1,166 @@
+// flatsat builds accept every frame
+#![cfg(not(feature = "flatsat"))]
+
+mod common;
+
+use core::cell::Cell;
+
+use common::erased_memory;
+use eps_software::{
+    mock::flash::{MOCK_PAGE_SIZE, MockFlash},
+    telecommand::{
+        AckStage, TcFailure, TcSequencer, ack_container,
+        auth::{COUNTER_RESERVE, Key, KeyStore, TcAuth, sign},
+    },
+};
+use south_common::TelemetryContainer;
+
+const KEY: Key = [0x42; 32];
+const PAYLOAD: [u8; 3] = [1, 2, 3];
+
+/// authentication as after a reset, with the key pages in `memory`
+fn load_auth(memory: &[Cell<u8>]) -> TcAuth<MockFlash<'_>> {
+    TcAuth::new(KeyStore::new(MockFlash::new(memory), 0))
+}
+
+/// authentication with `KEY` freshly provisioned
+fn provisioned_auth(memory: &[Cell<u8>]) -> TcAuth<MockFlash<'_>> {
+    let mut store = KeyStore::new(MockFlash::new(memory), 0);
+    store.provision(&KEY).unwrap();
+    load_auth(memory)
+}
+
+fn frame(key: &Key, counter: u32, payload: &[u8]) -> Vec<u8> {
+    let mut frame = payload.to_vec();
+    frame.extend_from_slice(&counter.to_le_bytes());
+    frame.extend_from_slice(&sign(key, counter, payload));
+    frame
+}
+
+#[test]
+fn authentic_frames_are_accepted_once() {
+    let memory = erased_memory();
+    let mut auth = provisioned_auth(&memory);
+
+    let first = frame(&KEY, 1, &PAYLOAD);
+    assert!(auth.verify(&first) == Ok(&PAYLOAD[..]));
+    assert!(auth.verify(&first) == Err(TcFailure::Replayed));
+    // counters may skip values but never go back
+    assert!(auth.verify(&frame(&KEY, 10, &PAYLOAD)) == Ok(&PAYLOAD[..]));
+    assert!(auth.verify(&frame(&KEY, 9, &PAYLOAD)) == Err(TcFailure::Replayed));
+    assert!(auth.rejects().replayed == 2);
+    assert!(auth.rejects().invalid == 0);
+}
+
+#[test]
+fn forged_frames_are_rejected() {
+    let memory = erased_memory();
+    let mut auth = provisioned_auth(&memory);
+
+    assert!(auth.verify(&frame(&[0; 32], 1, &PAYLOAD)) == Err(TcFailure::Unauthenticated));
+    let mut modified = frame(&KEY, 1, &PAYLOAD);
+    modified[0] ^= 1;
+    assert!(auth.verify(&modified) == Err(TcFailure::Unauthenticated));
+    assert!(auth.verify(&PAYLOAD) == Err(TcFailure::Unauthenticated));
+    assert!(auth.rejects().invalid == 3);
+    // the counter of a forged frame is not taken over
+    assert!(auth.verify(&frame(&KEY, 1, &PAYLOAD)) == Ok(&PAYLOAD[..]));
+}
+
+#[test]
+fn counters_stay_used_after_reset() {
+    let memory = erased_memory();
+    let mut auth = provisioned_auth(&memory);
+    assert!(auth.verify(&frame(&KEY, 5, &PAYLOAD)) == Ok(&PAYLOAD[..]));
+
+    // the whole reserved range is rejected after a reset
+    let mut auth = load_auth(&memory);
+    let reserved = 5 + COUNTER_RESERVE;
+    assert!(auth.verify(&frame(&KEY, 6, &PAYLOAD)) == Err(TcFailure::Replayed));
+    assert!(auth.verify(&frame(&KEY, reserved, &PAYLOAD)) == Err(TcFailure::Replayed));
+    assert!(auth.verify(&frame(&KEY, reserved + 1, &PAYLOAD)) == Ok(&PAYLOAD[..]));
+}
+
+#[test]
+fn frames_are_rejected_without_key() {
+    let memory = erased_memory();
+    let mut auth = load_auth(&memory);
+    assert!(auth.verify(&frame(&KEY, 1, &PAYLOAD)) == Err(TcFailure::Unauthenticated));
+}
+
+#[test]
+fn provisioning_the_stored_key_keeps_its_counters() {
+    let memory = erased_memory();
+    let mut auth = provisioned_auth(&memory);
+    assert!(auth.verify(&frame(&KEY, 5, &PAYLOAD)) == Ok(&PAYLOAD[..]));
+
+    KeyStore::new(MockFlash::new(&memory), 0)
+        .ensure(&KEY)
+        .unwrap();
+    let mut auth = load_auth(&memory);
+    assert!(auth.verify(&frame(&KEY, 6, &PAYLOAD)) == Err(TcFailure::Replayed));
+}
+
+#[test]
+fn reservations_continue_on_the_other_page() {
+    // key records per page
+    const SLOTS: u32 = MOCK_PAGE_SIZE as u32 / 48;
+    let memory = erased_memory();
+    let mut auth = provisioned_auth(&memory);
+
+    // every frame beyond the reserved range appends a record
+    let mut counter = 1;
+    for _ in 0..2 * SLOTS + 1 {
+        assert!(auth.verify(&frame(&KEY, counter, &PAYLOAD)) == Ok(&PAYLOAD[..]));
+        counter += COUNTER_RESERVE + 1;
+    }
+    let mut auth = load_auth(&memory);
+    let reserved = counter - 1;
+    assert!(auth.verify(&frame(&KEY, reserved, &PAYLOAD)) == Err(TcFailure::Replayed));
+    assert!(auth.verify(&frame(&KEY, reserved + 1, &PAYLOAD)) == Ok(&PAYLOAD[..]));
+
+    let new_key = [0x17; 32];
+    KeyStore::new(MockFlash::new(&memory), 0)
+        .ensure(&new_key)
+        .unwrap();
+    let mut auth = load_auth(&memory);
+    assert!(auth.verify(&frame(&KEY, reserved + 2, &PAYLOAD)) == Err(TcFailure::Unauthenticated));
+    assert!(auth.verify(&frame(&new_key, 1, &PAYLOAD)) == Ok(&PAYLOAD[..]));
+}
+
+#[test]
+fn rejected_frames_are_numbered() {
+    let memory = erased_memory();
+    let mut auth = provisioned_auth(&memory);
+    let mut sequencer = TcSequencer::new();
+
+    let Err(ack) = sequencer.receive(&frame(&[0; 32], 1, &PAYLOAD), &mut auth) else {
+        panic!("forged frame accepted");
+    };
+    let expected = ack_container(0, AckStage::Acceptance, Err(TcFailure::Unauthenticated));
+    assert!(ack.bytes() == expected.bytes());
+    // authentic, but without a telecommand
+    let Err(ack) = sequencer.receive(&frame(&KEY, 1, &[]), &mut auth) else {
+        panic!("empty frame parsed");
+    };
+    let expected = ack_container(1, AckStage::Acceptance, Err(TcFailure::Malformed));
+    assert!(ack.bytes() == expected.bytes());
+}
+
+#[test]
+fn time_tags_are_checked() {
+    let memory = erased_memory();
+    let mut auth = provisioned_auth(&memory);
+    let mut sequencer = TcSequencer::new();
+
+    // truncated time tag and unknown time reference
+    for (counter, header) in [(1, &[0, 1, 2][..]), (2, &[7, 0, 0, 0, 0][..])] {
+        let Err(ack) = sequencer.receive_time_tagged(&frame(&KEY, counter, header), &mut auth)
+        else {
+            panic!("invalid time tag accepted");
+        };
+        let seq = counter as u16 - 1;
+        let expected = ack_container(seq, AckStage::Acceptance, Err(TcFailure::Malformed));
+        assert!(ack.bytes() == expected.bytes());
+    }
+}
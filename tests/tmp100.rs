@@ -0,0 +1,215 @@
+use critical_section as _;
+
+use embassy_futures::block_on;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::{Duration, Timer};
+use eps_software::{
+    config::{EpsConfig, param::Param},
+    mock::i2c::{MockI2c, MockTmp100},
+    pwr_src::battery::{
+        temp_sensor::{TempSensor, TempSensorState},
+        tmp100_drv::{
+            Addr0State, FaultQueue, Polarity, Resolution, ThermostatMode, Tmp100, scan_bus,
+        },
+    },
+};
+
+#[test]
+fn temperatures_below_zero_are_sign_extended() {
+    let addr = Addr0State::Low;
+    let devices = [MockTmp100::new(addr.get_addr())];
+    let interface = Mutex::<ThreadModeRawMutex, _>::new(MockI2c::new(&devices));
+
+    block_on(async {
+        let mut tmp100 = Tmp100::new(&interface, Resolution::BITS12, addr)
+            .await
+            .unwrap();
+        for temp in [23_5, 0, -0_5, -25_0, -55_0] {
+            devices[0].set_temperature_tenth_deg(temp);
+            assert!(tmp100.read_temp().await == Ok(temp));
+        }
+
+        // lower resolutions drop the fraction bits
+        let mut tmp100 = Tmp100::new(&interface, Resolution::BITS9, addr)
+            .await
+            .unwrap();
+        devices[0].set_temperature_tenth_deg(-25_0);
+        assert!(tmp100.read_temp_raw().await == Ok(-50));
+        assert!(tmp100.read_temp().await == Ok(-25_0));
+    });
+}
+
+#[test]
+fn thermostat_limits_round_trip() {
+    let addr = Addr0State::High;
+    let devices = [MockTmp100::new(addr.get_addr())];
+    let interface = Mutex::<ThreadModeRawMutex, _>::new(MockI2c::new(&devices));
+
+    block_on(async {
+        let mut tmp100 = Tmp100::new(&interface, Resolution::BITS12, addr)
+            .await
+            .unwrap();
+        // power on defaults
+        assert!(tmp100.read_limits().await == Ok((75_0, 80_0)));
+
+        tmp100.set_limits(-20_0, 60_5).await.unwrap();
+        assert!(devices[0].register(0b10) == (-20i16 * 256) as u16);
+        assert!(devices[0].register(0b11) == 60 * 256 + 128);
+        assert!(tmp100.read_limits().await == Ok((-20_0, 60_5)));
+
+        // the pointer is back on the temperature register
+        devices[0].set_temperature_tenth_deg(30_0);
+        assert!(tmp100.read_temp().await == Ok(30_0));
+    });
+}
+
+#[test]
+fn config_register_round_trip() {
+    let addr = Addr0State::Floating;
+    let devices = [MockTmp100::new(addr.get_addr())];
+    let interface = Mutex::<ThreadModeRawMutex, _>::new(MockI2c::new(&devices));
+
+    block_on(async {
+        let mut tmp100 = Tmp100::new(&interface, Resolution::BITS11, addr)
+            .await
+            .unwrap();
+        assert!(tmp100.read_config().await == Ok(0b0100_0000));
+
+        tmp100
+            .set_thermostat_mode(ThermostatMode::Interrupt)
+            .await
+            .unwrap();
+        tmp100.set_polarity(Polarity::ActiveHigh).await.unwrap();
+        tmp100.set_fault_queue(FaultQueue::Faults4).await.unwrap();
+        tmp100.set_shutdown(true).await.unwrap();
+        assert!(tmp100.read_config().await == Ok(0b0101_0111));
+        assert!(devices[0].register(0b01) == 0b0101_0111 << 8);
+
+        tmp100
+            .set_thermostat_mode(ThermostatMode::Comparator)
+            .await
+            .unwrap();
+        tmp100.set_polarity(Polarity::ActiveLow).await.unwrap();
+        tmp100.set_fault_queue(FaultQueue::Faults6).await.unwrap();
+        tmp100.set_shutdown(false).await.unwrap();
+        tmp100.set_resolution(Resolution::BITS9).await.unwrap();
+        assert!(tmp100.read_config().await == Ok(0b0001_1000));
+    });
+}
+
+#[test]
+fn one_shot_conversion_and_alert() {
+    let addr = Addr0State::Low;
+    let devices = [MockTmp100::new(addr.get_addr())];
+    let interface = Mutex::<ThreadModeRawMutex, _>::new(MockI2c::new(&devices));
+
+    block_on(async {
+        let mut tmp100 = Tmp100::new(&interface, Resolution::BITS9, addr)
+            .await
+            .unwrap();
+        tmp100.set_shutdown(true).await.unwrap();
+        // alert bit cleared reads as an active alert with the default active low polarity
+        assert!(tmp100.read_alert().await == Ok(true));
+
+        devices[0].set_temperature_tenth_deg(-10_0);
+        assert!(tmp100.read_temp_one_shot().await == Ok(-10_0));
+        // the written one shot bit reads back as the alert flag, shutdown stays set
+        assert!(devices[0].register(0b01) >> 8 == 0b1000_0001);
+        assert!(tmp100.read_alert().await == Ok(false));
+
+        tmp100.set_polarity(Polarity::ActiveHigh).await.unwrap();
+        assert!(tmp100.read_alert().await == Ok(false));
+        tmp100.start_one_shot().await.unwrap();
+        assert!(tmp100.read_alert().await == Ok(true));
+    });
+}
+
+#[test]
+fn bus_scan_reports_answering_addresses() {
+    let devices = [
+        MockTmp100::new(Addr0State::Low.get_addr()),
+        MockTmp100::new(Addr0State::High.get_addr()),
+    ];
+    let interface = Mutex::<ThreadModeRawMutex, _>::new(MockI2c::new(&devices));
+    let scan = block_on(scan_bus(&interface));
+    assert!(scan == 0b101);
+    assert!(scan & Addr0State::Low.scan_bit() != 0);
+    assert!(scan & Addr0State::High.scan_bit() != 0);
+    assert!(scan & Addr0State::Floating.scan_bit() == 0);
+
+    let interface = Mutex::<ThreadModeRawMutex, _>::new(MockI2c::new(&[]));
+    assert!(block_on(scan_bus(&interface)) == 0);
+}
+
+#[test]
+fn battery_sensor_addresses_are_configurable() {
+    let mut eps_config = EpsConfig::DEFAULT;
+    // the two sensors share the bus, the defaults have to differ
+    let bat_1_addr = eps_config.bat_1_temp_sensor_addr().get_addr();
+    let bat_2_addr = eps_config.bat_2_temp_sensor_addr().get_addr();
+    assert!(bat_1_addr == 0b1001000);
+    assert!(bat_2_addr == 0b1001010);
+
+    eps_config
+        .set(Param::Bat2TempSensorAddr, Addr0State::Floating as i32)
+        .unwrap();
+    assert!(eps_config.bat_2_temp_sensor_addr().get_addr() == 0b1001001);
+    assert!(
+        eps_config
+            .set(Param::Bat1TempSensorAddr, Addr0State::Low as i32 + 1)
+            .is_err()
+    );
+    assert!(eps_config.bat_1_temp_sensor_addr().get_addr() == bat_1_addr);
+
+    for (id, addr) in [(0, 0b1001001), (1, 0b1001010), (2, 0b1001000)] {
+        assert!(Addr0State::from_id(id).map(|state| state.get_addr()) == Some(addr));
+    }
+    assert!(Addr0State::from_id(3).is_none());
+}
+
+#[test]
+fn lost_sensor_is_reconnected_with_backoff() {
+    let addr = Addr0State::Low;
+    let devices = [MockTmp100::new(addr.get_addr())];
+    let interface = Mutex::<ThreadModeRawMutex, _>::new(MockI2c::new(&devices));
+
+    block_on(async {
+        devices[0].set_temperature_tenth_deg(21_0);
+        let mut sensor = TempSensor::new(&interface, addr, Resolution::BITS12).await;
+        assert!(sensor.take_state_change() == Some(TempSensorState::Connected));
+        assert!(sensor.read().await == Some(21_0));
+
+        // single failed reads are counted, the sensor is dropped after three in a row
+        devices[0].set_present(false);
+        for _ in 0..3 {
+            assert!(sensor.read().await.is_none());
+        }
+        assert!(sensor.state() == TempSensorState::Disconnected);
+        assert!(sensor.take_state_change() == Some(TempSensorState::Disconnected));
+        assert!(sensor.take_state_change().is_none());
+        assert!(sensor.errors() == 3);
+        assert!(interface.lock().await.recoveries() == 0);
+
+        // the first reconnect is tried right away and recovers the bus before
+        assert!(sensor.read().await.is_none());
+        assert!(interface.lock().await.recoveries() == 1);
+        assert!(sensor.errors() == 4);
+        assert!(sensor.read().await.is_none());
+        assert!(interface.lock().await.recoveries() == 1);
+
+        // retried after 1s, then after 2s
+        Timer::after(Duration::from_millis(1_100)).await;
+        assert!(sensor.read().await.is_none());
+        assert!(interface.lock().await.recoveries() == 2);
+        Timer::after(Duration::from_millis(1_100)).await;
+        assert!(sensor.read().await.is_none());
+        assert!(interface.lock().await.recoveries() == 2);
+
+        devices[0].set_present(true);
+        Timer::after(Duration::from_millis(1_000)).await;
+        assert!(sensor.read().await == Some(21_0));
+        assert!(interface.lock().await.recoveries() == 3);
+        assert!(sensor.take_state_change() == Some(TempSensorState::Connected));
+        assert!(sensor.errors() == 5);
+    });
+}